@@ -35,11 +35,14 @@ pub trait Api {
   fn get_block_stats(&self, height: usize) -> Result<GetBlockStatsResult, jsonrpc_core::Error>;
 
   #[rpc(name = "getblock")]
-  fn get_block(&self, blockhash: BlockHash, verbosity: u64) -> Result<String, jsonrpc_core::Error>;
+  fn get_block(&self, blockhash: BlockHash, verbosity: u64) -> Result<Value, jsonrpc_core::Error>;
 
   #[rpc(name = "getblockcount")]
   fn get_block_count(&self) -> Result<u64, jsonrpc_core::Error>;
 
+  #[rpc(name = "getrawmempool")]
+  fn get_raw_mempool(&self) -> Result<Vec<Txid>, jsonrpc_core::Error>;
+
   #[rpc(name = "gettxout")]
   fn get_tx_out(
     &self,
@@ -11,7 +11,7 @@ impl Server {
     Self { network, state }
   }
 
-  fn state(&self) -> MutexGuard<State> {
+  fn state(&self) -> MutexGuard<'_, State> {
     self.state.lock().unwrap()
   }
 
@@ -216,11 +216,54 @@ impl Api for Server {
     &self,
     block_hash: BlockHash,
     verbosity: u64,
-  ) -> Result<String, jsonrpc_core::Error> {
-    assert_eq!(verbosity, 0, "Verbosity level {verbosity} is unsupported");
-    match self.state().blocks.get(&block_hash) {
-      Some(block) => Ok(hex::encode(serialize(block))),
-      None => Err(Self::not_found()),
+  ) -> Result<Value, jsonrpc_core::Error> {
+    let state = self.state();
+
+    let Some(block) = state.blocks.get(&block_hash) else {
+      return Err(Self::not_found());
+    };
+
+    match verbosity {
+      0 => Ok(Value::String(hex::encode(serialize(block)))),
+      // Only the fields of transaction inputs and their prevouts are included
+      3 => Ok(serde_json::json!({
+        "hash": block_hash,
+        "tx": block
+          .txdata
+          .iter()
+          .map(|tx| {
+            serde_json::json!({
+              "txid": tx.compute_txid(),
+              "vin": tx
+                .input
+                .iter()
+                .map(|input| {
+                  if input.previous_output.is_null() {
+                    return serde_json::json!({
+                      "coinbase": hex::encode(input.script_sig.as_bytes()),
+                    });
+                  }
+
+                  let prevout = &state.transactions[&input.previous_output.txid].output
+                    [usize::try_from(input.previous_output.vout).unwrap()];
+
+                  serde_json::json!({
+                    "txid": input.previous_output.txid,
+                    "vout": input.previous_output.vout,
+                    "prevout": {
+                      "value": prevout.value.to_btc(),
+                      "scriptPubKey": {
+                        "hex": hex::encode(prevout.script_pubkey.as_bytes()),
+                      },
+                    },
+                  })
+                })
+                .collect::<Vec<Value>>(),
+            })
+          })
+          .collect::<Vec<Value>>(),
+      })),
+      _ => panic!("Verbosity level {verbosity} is unsupported"),
     }
   }
 
@@ -236,6 +279,17 @@ impl Api for Server {
     )
   }
 
+  fn get_raw_mempool(&self) -> Result<Vec<Txid>, jsonrpc_core::Error> {
+    Ok(
+      self
+        .state()
+        .mempool
+        .iter()
+        .map(Transaction::compute_txid)
+        .collect(),
+    )
+  }
+
   fn get_tx_out(
     &self,
     txid: Txid,
@@ -731,7 +785,11 @@ impl Api for Server {
         None => Err(Self::not_found()),
       }
     } else {
-      match state.transactions.get(&txid) {
+      match state
+        .transactions
+        .get(&txid)
+        .or_else(|| state.mempool.iter().find(|tx| tx.compute_txid() == txid))
+      {
         Some(tx) => Ok(Value::String(hex::encode(serialize(tx)))),
         None => Err(Self::not_found()),
       }
@@ -1,4 +1,7 @@
-use jsonrpsee::{core::RpcResult, proc_macros::rpc};
+use jsonrpsee::{
+  core::{RpcResult, SubscriptionResult},
+  proc_macros::rpc,
+};
 use serde::{Deserialize, Serialize};
 
 #[derive(Serialize, Deserialize, Clone)]
@@ -24,6 +27,16 @@ pub struct BRC20Tx {
   pub parent_id: Option<String>,
 }
 
+/// A page of the BRC-20 transfers of a range of blocks, ordered by block
+/// height and then by `tx_id`. `next` is the `tx_id` of the last transfer if
+/// more follow it in the range, to be passed as `after` to get the following
+/// page, and is `None` on the last one.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct BRC20TxPage {
+  pub txes: Vec<BRC20Tx>,
+  pub next: Option<String>,
+}
+
 #[derive(Serialize, Deserialize, Clone)]
 pub struct BitmapInscription {
   pub tx_id: String,
@@ -49,6 +62,15 @@ pub struct BlockInfo {
   pub timestamp: u64,
 }
 
+/// A page of block hashes and timestamps, one for every height from the
+/// requested one on. `next` is the height to request the following page
+/// from, and is `None` on the last one.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct BlockInfoPage {
+  pub blocks: Vec<BlockInfo>,
+  pub next: Option<u32>,
+}
+
 #[derive(Serialize, Deserialize, Clone)]
 pub struct InscriptionInfo {
   pub _inscription_id: String,
@@ -59,6 +81,18 @@ pub struct InscriptionInfo {
   pub content_hex: String,
   pub content_type_hex: String,
   pub _metaprotocol_hex: String,
+  /// All parents. `parent_id` is the first of them.
+  pub parent_ids: Vec<String>,
+  pub content_encoding_hex: Option<String>,
+  /// CBOR encoded metadata.
+  pub metadata_hex: Option<String>,
+  pub delegate_id: Option<String>,
+  pub pointer: Option<u64>,
+  /// The body decoded from its `br` or `gzip` content encoding.
+  pub decoded_content_hex: Option<String>,
+  /// Format of the stored record. Version 0 records predate the envelope
+  /// fields above, which are empty for them.
+  pub record_version: u8,
 }
 
 #[derive(Serialize, Deserialize, Clone)]
@@ -68,13 +102,24 @@ pub struct InscriptionEntry {
   pub inscription_number: i32,
   pub sequence_number: u32,
   pub is_json_or_text: bool,
+  /// Whether the inscription has a curse, even if the jubilee vindicated it
+  /// and it is numbered as blessed. Entries no longer store a flag for this,
+  /// it is derived from the `cursed` and `vindicated` charms instead, which
+  /// are set in exactly the cases the flag was.
   pub is_cursed_for_brc20: bool,
   pub txcnt_limit: i16,
+  /// Why the inscription is cursed, e.g. `pushnum` or `reinscription`.
+  /// `unknown` for cursed inscriptions indexed before curses were recorded.
+  pub curse: Option<String>,
+  /// Whether the inscription is unbound, i.e. not on a sat.
+  pub unbound: bool,
 }
 
+/// `info` is only recorded for tracked inscriptions, and is `None` for the
+/// rest, including every unbound inscription.
 #[derive(Serialize, Deserialize, Clone)]
 pub struct InscriptionInformation {
-  pub info: InscriptionInfo,
+  pub info: Option<InscriptionInfo>,
   pub entry: InscriptionEntry,
 }
 
@@ -85,6 +130,93 @@ pub struct UTXOInfo {
   pub satpoint_offsets: Vec<u64>,
 }
 
+#[derive(Serialize, Deserialize, Clone)]
+pub struct PkscriptInscription {
+  pub inscription_id: String,
+  pub sequence_number: u32,
+  pub satpoint: String,
+}
+
+/// A page of the inscriptions held by a script pubkey, ordered by sequence
+/// number. `next` is passed as `after` to get the following page, and is
+/// `None` on the last one.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct PkscriptInscriptions {
+  pub inscriptions: Vec<PkscriptInscription>,
+  pub next: Option<u32>,
+}
+
+/// A tracked transfer of an inscription, recorded as transfer `transfer_index`
+/// of block `block_height`. `old_satpoint` is `None` for its inscription.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct InscriptionTransfer {
+  pub block_height: u32,
+  pub transfer_index: u32,
+  pub txid: String,
+  pub old_satpoint: Option<String>,
+  pub new_satpoint: String,
+  pub new_pkscript: String,
+  pub new_wallet: String,
+  pub new_output_value: u64,
+  pub sent_as_fee: bool,
+}
+
+/// A transfer of a tracked inscription by a mempool transaction, as it would
+/// be recorded if the transaction confirmed in the next block.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct PendingTransfer {
+  pub inscription_id: String,
+  pub sequence_number: u32,
+  pub txid: String,
+  pub old_satpoint: String,
+  pub new_satpoint: Option<String>,
+  pub new_pkscript: String,
+  pub new_wallet: String,
+  pub sent_as_fee: bool,
+}
+
+/// Pushed to `subscribeInscriptionEvents` subscribers. Inscription events are
+/// only sent for tracked inscriptions, and only once their block can be read
+/// through the other methods, followed by `block_indexed` for that block.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum InscriptionEvent {
+  InscriptionCreated {
+    block_height: u32,
+    charms: u16,
+    inscription_id: String,
+    location: Option<String>,
+    parent_inscription_ids: Vec<String>,
+    sequence_number: u32,
+  },
+  InscriptionTransferred {
+    block_height: u32,
+    inscription_id: String,
+    new_location: String,
+    old_location: String,
+    sequence_number: u32,
+  },
+  BlockIndexed {
+    block_height: u32,
+  },
+  /// Blocks above `block_height` were rolled back after a reorg and will be
+  /// indexed again.
+  Rollback {
+    block_height: u32,
+  },
+}
+
+impl InscriptionEvent {
+  pub fn block_height(&self) -> u32 {
+    match self {
+      Self::InscriptionCreated { block_height, .. }
+      | Self::InscriptionTransferred { block_height, .. }
+      | Self::BlockIndexed { block_height }
+      | Self::Rollback { block_height } => *block_height,
+    }
+  }
+}
+
 #[rpc(server, client)]
 pub trait Brc20Api {
   #[method(name = "getBlockIndexTimes")]
@@ -96,6 +228,29 @@ pub trait Brc20Api {
   #[method(name = "getBlockHashAndTs")]
   async fn get_block_hash_and_ts(&self, block_height: u32) -> RpcResult<Option<BlockInfo>>;
 
+  /// Like `getBlockBRC20Txes`, for blocks `from_height` to `to_height`
+  /// inclusive, which must have been indexed. At most `limit` transfers are
+  /// returned, 1000 by default and 10000 at most, starting after the one
+  /// with `tx_id` `after`.
+  #[method(name = "getBlockRangeBRC20Txes")]
+  async fn get_block_range_brc20_txes(
+    &self,
+    from_height: u32,
+    to_height: u32,
+    after: Option<String>,
+    limit: Option<u32>,
+  ) -> RpcResult<BRC20TxPage>;
+
+  /// Like `getBlockHashAndTs`, for blocks `from_height` to `to_height`
+  /// inclusive, which must have been indexed. At most 10000 blocks are
+  /// returned at once.
+  #[method(name = "getBlockHashesAndTs")]
+  async fn get_block_hashes_and_ts(
+    &self,
+    from_height: u32,
+    to_height: u32,
+  ) -> RpcResult<BlockInfoPage>;
+
   #[method(name = "getLatestBlockHeight")]
   async fn get_latest_block_height(&self) -> RpcResult<Option<u32>>;
 
@@ -105,6 +260,14 @@ pub trait Brc20Api {
     inscription_id: String,
   ) -> RpcResult<Option<InscriptionInformation>>;
 
+  /// Like `getInscriptionInfo`, for up to 1000 inscriptions at once. Results
+  /// are in the order of `inscription_ids`.
+  #[method(name = "getInscriptionInfos")]
+  async fn get_inscription_infos(
+    &self,
+    inscription_ids: Vec<String>,
+  ) -> RpcResult<Vec<Option<InscriptionInformation>>>;
+
   #[method(name = "getUTXOInfo")]
   async fn get_utxo_info(&self, outpoint: String) -> RpcResult<Option<UTXOInfo>>;
 
@@ -123,4 +286,52 @@ pub trait Brc20Api {
   #[method(name = "getBlockSNSInscrs")]
   async fn get_block_sns_inscrs(&self, block_height: u32)
   -> RpcResult<Option<Vec<SNSInscription>>>;
+
+  #[method(name = "getInscriptionFilters")]
+  async fn get_inscription_filters(&self) -> RpcResult<Vec<String>>;
+
+  /// Transfers of tracked inscriptions by transactions in bitcoind's mempool,
+  /// if the indexer runs with `--watch-mempool`. Transfers are dropped once
+  /// their transaction confirms or leaves the mempool.
+  #[method(name = "getPendingTransfers")]
+  async fn get_pending_transfers(&self) -> RpcResult<Vec<PendingTransfer>>;
+
+  /// Tracked inscriptions held by the hex encoded `pkscript`, if the index
+  /// was built with `--index-addresses`. At most `limit` are returned, 100
+  /// by default and 1000 at most, starting after sequence number `after`.
+  #[method(name = "getInscriptionsByPkscript")]
+  async fn get_inscriptions_by_pkscript(
+    &self,
+    pkscript: String,
+    after: Option<u32>,
+    limit: Option<u32>,
+  ) -> RpcResult<PkscriptInscriptions>;
+
+  /// Like `getInscriptionsByPkscript`, for the script pubkey of `address`.
+  #[method(name = "getInscriptionsByAddress")]
+  async fn get_inscriptions_by_address(
+    &self,
+    address: String,
+    after: Option<u32>,
+    limit: Option<u32>,
+  ) -> RpcResult<PkscriptInscriptions>;
+
+  /// Every tracked transfer of `inscription_id`, oldest first, or `None` if
+  /// the inscription isn't indexed.
+  #[method(name = "getInscriptionTransfers")]
+  async fn get_inscription_transfers(
+    &self,
+    inscription_id: String,
+  ) -> RpcResult<Option<Vec<InscriptionTransfer>>>;
+}
+
+/// Kept apart from `Brc20Api` since subscriptions need a WebSocket client.
+#[rpc(server, client)]
+pub trait InscriptionEventsApi {
+  #[subscription(
+    name = "subscribeInscriptionEvents" => "inscriptionEvent",
+    unsubscribe = "unsubscribeInscriptionEvents",
+    item = InscriptionEvent
+  )]
+  async fn subscribe_inscription_events(&self) -> SubscriptionResult;
 }
@@ -1,12 +1,13 @@
 use std::path::PathBuf;
 
 use bitcoin::Network::{Bitcoin, Regtest, Signet, Testnet, Testnet4};
-use db_reader::{Config, start_rpc_server};
+use db_reader::{Config, RocksDbSettings, start_rpc_server};
 
 fn parse_args() -> Config {
   let mut network = Bitcoin;
   let mut db_path = None;
   let mut api_url = None;
+  let mut rocksdb = RocksDbSettings::default();
 
   for (idx, arg) in std::env::args().enumerate() {
     match arg.as_str() {
@@ -31,6 +32,17 @@ fn parse_args() -> Config {
           std::process::exit(1);
         }
       }
+      "--rocksdb-settings" => {
+        if let Some(settings) = std::env::args().nth(idx + 1) {
+          rocksdb = serde_json::from_str(&settings).unwrap_or_else(|err| {
+            eprintln!("Invalid RocksDB settings: {err}");
+            std::process::exit(1);
+          });
+        } else {
+          eprintln!("No JSON provided after --rocksdb-settings");
+          std::process::exit(1);
+        }
+      }
       "--help" | "-h" => {
         println!("Usage: db_reader [OPTIONS]");
         println!("Options:");
@@ -43,6 +55,9 @@ fn parse_args() -> Config {
         println!(
           "  --api-url <url>    Specify the API Host and Port to bind to (default: 127.0.0.1:11030)."
         );
+        println!(
+          "  --rocksdb-settings <json>  RocksDB settings, as in the `rocksdb` section of ord's config."
+        );
         println!("  -h, --help  Show this help message.");
         std::process::exit(0);
       }
@@ -54,15 +69,22 @@ fn parse_args() -> Config {
     network,
     db_path,
     api_url,
+    reader_gate: None,
+    inscription_events: None,
+    pending_transfers: None,
+    rocksdb,
+    shutdown: None,
   }
 }
 
 #[tokio::main]
 async fn main() {
+  let config = parse_args();
+  let nofile_limit = config.rocksdb.nofile_limit;
   rlimit::Resource::NOFILE
-    .set(65536, 131072)
+    .set(nofile_limit.unwrap_or(65536), nofile_limit.unwrap_or(131072))
     .expect("Failed to set NOFILE limit");
-  start_rpc_server(parse_args()).await.unwrap_or_else(|err| {
+  start_rpc_server(config).await.unwrap_or_else(|err| {
     eprintln!("Error running RPC server: {}", err);
     std::process::exit(1);
   });
@@ -1,8 +1,16 @@
 use bitcoin::Network;
 use std::path::PathBuf;
+use std::sync::mpsc::Receiver;
+
+use crate::{InscriptionEvent, PendingTransfers, ReaderGate, RocksDbSettings, Shutdown};
 
 pub struct Config {
   pub network: Network,
   pub db_path: Option<PathBuf>,
   pub api_url: Option<String>,
+  pub reader_gate: Option<ReaderGate>,
+  pub inscription_events: Option<Receiver<InscriptionEvent>>,
+  pub pending_transfers: Option<PendingTransfers>,
+  pub rocksdb: RocksDbSettings,
+  pub shutdown: Option<Shutdown>,
 }
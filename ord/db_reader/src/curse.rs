@@ -0,0 +1,71 @@
+/// Why an inscription is cursed, stored as the last byte of its inscription
+/// entry, or zero if it isn't.
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+pub enum Curse {
+  DuplicateField = 1,
+  IncompleteField = 2,
+  NotAtOffsetZero = 3,
+  NotInFirstInput = 4,
+  Pointer = 5,
+  Pushnum = 6,
+  Reinscription = 7,
+  Stutter = 8,
+  UnrecognizedEvenField = 9,
+}
+
+impl Curse {
+  const ALL: [Self; 9] = [
+    Self::DuplicateField,
+    Self::IncompleteField,
+    Self::NotAtOffsetZero,
+    Self::NotInFirstInput,
+    Self::Pointer,
+    Self::Pushnum,
+    Self::Reinscription,
+    Self::Stutter,
+    Self::UnrecognizedEvenField,
+  ];
+
+  pub fn from_byte(byte: u8) -> Option<Self> {
+    Self::ALL.into_iter().find(|curse| *curse as u8 == byte)
+  }
+
+  /// The name returned by `getInscriptionInfo`
+  pub fn name(self) -> &'static str {
+    match self {
+      Self::DuplicateField => "duplicate_field",
+      Self::IncompleteField => "incomplete_field",
+      Self::NotAtOffsetZero => "not_at_offset_zero",
+      Self::NotInFirstInput => "not_in_first_input",
+      Self::Pointer => "pointer",
+      Self::Pushnum => "pushnum",
+      Self::Reinscription => "reinscription",
+      Self::Stutter => "stutter",
+      Self::UnrecognizedEvenField => "unrecognized_even_field",
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn curses_round_trip_through_their_byte() {
+    for curse in Curse::ALL {
+      assert_eq!(Curse::from_byte(curse as u8), Some(curse));
+    }
+
+    assert_eq!(Curse::from_byte(0), None);
+    assert_eq!(Curse::from_byte(10), None);
+  }
+
+  #[test]
+  fn curse_names() {
+    assert_eq!(Curse::Pushnum.name(), "pushnum");
+    assert_eq!(
+      Curse::UnrecognizedEvenField.name(),
+      "unrecognized_even_field"
+    );
+  }
+}
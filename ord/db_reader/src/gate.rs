@@ -0,0 +1,56 @@
+use std::sync::{Arc, RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+/// Coordinates the RPC server with the process that owns the primary database.
+///
+/// The RPC server enters the gate while it opens its secondary instance or
+/// catches it up with the primary. The writer pauses the gate while the
+/// primary database files are being replaced (e.g. when a savepoint is
+/// restored after a reorg), which waits for a catch-up in progress and holds
+/// off new ones. Every pause bumps the epoch so the RPC server knows it has
+/// to reopen its secondary instance rather than catch it up.
+#[derive(Clone, Default)]
+pub struct ReaderGate {
+  epoch: Arc<RwLock<u64>>,
+}
+
+pub struct ReaderPass<'a> {
+  epoch: RwLockReadGuard<'a, u64>,
+}
+
+pub struct ReaderPause<'a> {
+  epoch: RwLockWriteGuard<'a, u64>,
+}
+
+impl ReaderGate {
+  pub fn enter(&self) -> ReaderPass<'_> {
+    ReaderPass {
+      epoch: self.epoch.read().unwrap_or_else(|err| err.into_inner()),
+    }
+  }
+
+  pub fn try_enter(&self) -> Option<ReaderPass<'_>> {
+    self
+      .epoch
+      .try_read()
+      .ok()
+      .map(|epoch| ReaderPass { epoch })
+  }
+
+  pub fn pause(&self) -> ReaderPause<'_> {
+    ReaderPause {
+      epoch: self.epoch.write().unwrap_or_else(|err| err.into_inner()),
+    }
+  }
+}
+
+impl ReaderPass<'_> {
+  pub fn epoch(&self) -> u64 {
+    *self.epoch
+  }
+}
+
+impl Drop for ReaderPause<'_> {
+  fn drop(&mut self) {
+    *self.epoch += 1;
+  }
+}
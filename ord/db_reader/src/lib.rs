@@ -4,6 +4,23 @@ pub use api::*;
 mod config;
 pub use config::*;
 
+mod curse;
+pub use curse::*;
+
+mod gate;
+pub use gate::*;
+
+mod pending;
+pub use pending::*;
+
+mod rocksdb_settings;
+pub use rocksdb_settings::*;
+
+mod shutdown;
+pub use shutdown::*;
+
+#[cfg(feature = "server")]
+mod relay;
 #[cfg(feature = "server")]
 mod server;
 #[cfg(feature = "server")]
@@ -0,0 +1,27 @@
+use std::sync::{Arc, RwLock};
+
+use crate::PendingTransfer;
+
+/// Transfers of tracked inscriptions by mempool transactions, published by the
+/// indexer's mempool watcher and served by `getPendingTransfers`.
+#[derive(Clone, Default)]
+pub struct PendingTransfers {
+  transfers: Arc<RwLock<Arc<Vec<PendingTransfer>>>>,
+}
+
+impl PendingTransfers {
+  pub fn get(&self) -> Arc<Vec<PendingTransfer>> {
+    self
+      .transfers
+      .read()
+      .unwrap_or_else(|err| err.into_inner())
+      .clone()
+  }
+
+  pub fn replace(&self, transfers: Vec<PendingTransfer>) {
+    *self
+      .transfers
+      .write()
+      .unwrap_or_else(|err| err.into_inner()) = Arc::new(transfers);
+  }
+}
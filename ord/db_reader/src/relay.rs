@@ -0,0 +1,105 @@
+use std::collections::VecDeque;
+use std::sync::mpsc::Receiver;
+
+use tokio::sync::broadcast;
+
+use crate::InscriptionEvent;
+
+/// Forwards events from the indexer to subscribers once the secondary
+/// instance has caught up with the blocks they belong to, so subscribers can
+/// immediately query the data an event refers to.
+pub(crate) struct EventRelay {
+  incoming: Option<Receiver<InscriptionEvent>>,
+  last_height: Option<u32>,
+  pending: VecDeque<InscriptionEvent>,
+  sender: broadcast::Sender<InscriptionEvent>,
+}
+
+impl EventRelay {
+  pub(crate) fn new(
+    incoming: Option<Receiver<InscriptionEvent>>,
+    last_height: Option<u32>,
+    sender: broadcast::Sender<InscriptionEvent>,
+  ) -> Self {
+    Self {
+      incoming,
+      last_height,
+      pending: VecDeque::new(),
+      sender,
+    }
+  }
+
+  /// Called after catching up with the primary, with the latest block height
+  /// now readable from the secondary instance.
+  pub(crate) fn relay(&mut self, height: Option<u32>) {
+    let subscribed = self.sender.receiver_count() > 0;
+
+    if let Some(incoming) = &self.incoming {
+      for event in incoming.try_iter() {
+        // Nobody would receive them, and queueing them during the initial
+        // sync would hold on to every event of a commit interval
+        if !subscribed {
+          continue;
+        }
+
+        if let InscriptionEvent::Rollback { block_height } = event {
+          self
+            .pending
+            .retain(|pending| pending.block_height() <= block_height);
+        }
+
+        self.pending.push_back(event);
+      }
+    }
+
+    if !subscribed {
+      self.pending.clear();
+      self.last_height = height;
+      return;
+    }
+
+    let Some(height) = height else {
+      return;
+    };
+
+    while let Some(event) = self.pending.front() {
+      match *event {
+        InscriptionEvent::Rollback { block_height } => {
+          self.last_height = self.last_height.map(|last| last.min(block_height));
+        }
+        ref event if event.block_height() <= height => {
+          self.announce_blocks_until(event.block_height());
+        }
+        _ => break,
+      }
+
+      if let Some(event) = self.pending.pop_front() {
+        self.send(event);
+      }
+    }
+
+    if self.last_height.is_some_and(|last| last > height) {
+      self.send(InscriptionEvent::Rollback {
+        block_height: height,
+      });
+      self.last_height = Some(height);
+    }
+
+    self.announce_blocks_until(height + 1);
+  }
+
+  /// Sends `BlockIndexed` for every block below `end` not announced yet.
+  fn announce_blocks_until(&mut self, end: u32) {
+    let next = self.last_height.map_or(0, |last| last + 1);
+
+    for block_height in next..end {
+      self.send(InscriptionEvent::BlockIndexed { block_height });
+      self.last_height = Some(block_height);
+    }
+  }
+
+  fn send(&self, event: InscriptionEvent) {
+    // Fails only if every subscriber has gone away in the meantime
+    self.sender.send(event).ok();
+  }
+}
@@ -0,0 +1,251 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Deserializer, Serialize, de};
+
+/// Column families of ord's index, the ones `column_families` can configure.
+pub const INDEX_COLUMN_FAMILIES: &[&str] = &[
+  "height_to_block_header",
+  "height_to_last_sequence_number",
+  "outpoint_to_utxo_entry",
+  "inscription_id_to_sequence_number",
+  "inscription_number_to_sequence_number",
+  "inscription_id_to_txcnt",
+  "sequence_number_to_inscription_entry",
+  "statistic_to_count",
+  "ord_transfers",
+  "ord_inscription_info",
+  "ord_index_stats",
+  "undo_log",
+  "script_pubkey_to_inscriptions",
+  "sequence_number_to_script_pubkey",
+  "sequence_number_to_transfers",
+];
+
+/// RocksDB tuning shared by the indexer's primary instance and the secondary
+/// instance opened by the RPC server. Column family options left unset in
+/// `column_families` fall back to the top level ones.
+#[derive(Default, Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct RocksDbSettings {
+  pub block_cache_size: Option<usize>,
+  pub bloom_filter_bits: Option<f64>,
+  #[serde(deserialize_with = "deserialize_column_families")]
+  pub column_families: BTreeMap<String, ColumnFamilySettings>,
+  pub compression: Option<Compression>,
+  pub db_write_buffer_size: Option<usize>,
+  pub max_background_jobs: Option<i32>,
+  pub max_open_files: Option<i32>,
+  pub nofile_limit: Option<u64>,
+  pub write_buffer_size: Option<usize>,
+}
+
+#[derive(Default, Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct ColumnFamilySettings {
+  pub bloom_filter_bits: Option<f64>,
+  pub compression: Option<Compression>,
+  pub write_buffer_size: Option<usize>,
+}
+
+/// Rejects settings for column families the index doesn't have, which would
+/// otherwise be silently ignored, e.g. when misspelled.
+fn deserialize_column_families<'de, D: Deserializer<'de>>(
+  deserializer: D,
+) -> Result<BTreeMap<String, ColumnFamilySettings>, D::Error> {
+  let column_families = BTreeMap::<String, ColumnFamilySettings>::deserialize(deserializer)?;
+
+  if let Some(name) = column_families
+    .keys()
+    .find(|name| !INDEX_COLUMN_FAMILIES.contains(&name.as_str()))
+  {
+    return Err(de::Error::custom(format!(
+      "unknown column family `{name}`, expected one of {}",
+      INDEX_COLUMN_FAMILIES.join(", "),
+    )));
+  }
+
+  Ok(column_families)
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Compression {
+  None,
+  Snappy,
+  Zlib,
+  Bz2,
+  Lz4,
+  Lz4hc,
+  Zstd,
+}
+
+impl RocksDbSettings {
+  /// Memtable size of each column family, 8 GiB unless configured.
+  pub const DEFAULT_WRITE_BUFFER_SIZE: usize = 8192 * 1024 * 1024;
+
+  pub const DEFAULT_MAX_OPEN_FILES: i32 = 256;
+
+  /// Fills unset fields from `source`. Column families are merged by name.
+  pub fn or(mut self, source: RocksDbSettings) -> Self {
+    for (name, column_family) in source.column_families {
+      let merged = match self.column_families.remove(&name) {
+        Some(ours) => ColumnFamilySettings {
+          bloom_filter_bits: ours.bloom_filter_bits.or(column_family.bloom_filter_bits),
+          compression: ours.compression.or(column_family.compression),
+          write_buffer_size: ours.write_buffer_size.or(column_family.write_buffer_size),
+        },
+        None => column_family,
+      };
+      self.column_families.insert(name, merged);
+    }
+
+    Self {
+      block_cache_size: self.block_cache_size.or(source.block_cache_size),
+      bloom_filter_bits: self.bloom_filter_bits.or(source.bloom_filter_bits),
+      column_families: self.column_families,
+      compression: self.compression.or(source.compression),
+      db_write_buffer_size: self.db_write_buffer_size.or(source.db_write_buffer_size),
+      max_background_jobs: self.max_background_jobs.or(source.max_background_jobs),
+      max_open_files: self.max_open_files.or(source.max_open_files),
+      nofile_limit: self.nofile_limit.or(source.nofile_limit),
+      write_buffer_size: self.write_buffer_size.or(source.write_buffer_size),
+    }
+  }
+
+  /// The options of column family `name`, with the top level ones applied
+  /// where it has no override.
+  pub fn column_family(&self, name: &str) -> ColumnFamilySettings {
+    let column_family = self.column_families.get(name).cloned().unwrap_or_default();
+
+    ColumnFamilySettings {
+      bloom_filter_bits: column_family.bloom_filter_bits.or(self.bloom_filter_bits),
+      compression: column_family.compression.or(self.compression),
+      write_buffer_size: column_family.write_buffer_size.or(self.write_buffer_size),
+    }
+  }
+}
+
+#[cfg(feature = "server")]
+mod options {
+  use rocksdb::{BlockBasedOptions, Cache, ColumnFamilyDescriptor, DBCompressionType, Options};
+
+  use super::*;
+
+  impl From<Compression> for DBCompressionType {
+    fn from(compression: Compression) -> Self {
+      match compression {
+        Compression::None => Self::None,
+        Compression::Snappy => Self::Snappy,
+        Compression::Zlib => Self::Zlib,
+        Compression::Bz2 => Self::Bz2,
+        Compression::Lz4 => Self::Lz4,
+        Compression::Lz4hc => Self::Lz4hc,
+        Compression::Zstd => Self::Zstd,
+      }
+    }
+  }
+
+  impl RocksDbSettings {
+    /// Database wide options. Callers set the flags their instance needs,
+    /// like creating missing column families.
+    pub fn db_options(&self) -> Options {
+      let mut options = Options::default();
+
+      options.set_max_open_files(self.max_open_files.unwrap_or(Self::DEFAULT_MAX_OPEN_FILES));
+      options.set_write_buffer_size(
+        self
+          .write_buffer_size
+          .unwrap_or(Self::DEFAULT_WRITE_BUFFER_SIZE),
+      );
+
+      if let Some(db_write_buffer_size) = self.db_write_buffer_size {
+        options.set_db_write_buffer_size(db_write_buffer_size);
+      }
+
+      if let Some(max_background_jobs) = self.max_background_jobs {
+        options.set_max_background_jobs(max_background_jobs);
+      }
+
+      options
+    }
+
+    /// Descriptors for the column families `names`, sharing one block cache
+    /// if a block cache size is configured.
+    pub fn column_family_descriptors(&self, names: &[&str]) -> Vec<ColumnFamilyDescriptor> {
+      let cache = self.block_cache_size.map(Cache::new_lru_cache);
+
+      names
+        .iter()
+        .map(|name| {
+          let column_family = self.column_family(name);
+
+          let mut options = Options::default();
+
+          options.set_write_buffer_size(
+            column_family
+              .write_buffer_size
+              .unwrap_or(Self::DEFAULT_WRITE_BUFFER_SIZE),
+          );
+
+          if let Some(compression) = column_family.compression {
+            options.set_compression_type(compression.into());
+          }
+
+          if cache.is_some() || column_family.bloom_filter_bits.is_some() {
+            let mut table_options = BlockBasedOptions::default();
+
+            if let Some(cache) = &cache {
+              table_options.set_block_cache(cache);
+            }
+
+            if let Some(bits) = column_family.bloom_filter_bits {
+              table_options.set_bloom_filter(bits, false);
+            }
+
+            options.set_block_based_table_factory(&table_options);
+          }
+
+          ColumnFamilyDescriptor::new(*name, options)
+        })
+        .collect()
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn column_family_settings_are_parsed() {
+    let settings = serde_json::from_str::<RocksDbSettings>(
+      r#"{"compression":"lz4","column_families":{"ord_transfers":{"compression":"zstd"}}}"#,
+    )
+    .unwrap();
+
+    assert_eq!(
+      settings.column_family("ord_transfers").compression,
+      Some(Compression::Zstd),
+    );
+    assert_eq!(
+      settings.column_family("undo_log").compression,
+      Some(Compression::Lz4),
+    );
+  }
+
+  #[test]
+  fn unknown_column_families_are_rejected() {
+    let err = serde_json::from_str::<RocksDbSettings>(
+      r#"{"column_families":{"ord_transfer":{"compression":"zstd"}}}"#,
+    )
+    .unwrap_err()
+    .to_string();
+
+    assert!(
+      err.starts_with(
+        "unknown column family `ord_transfer`, expected one of height_to_block_header, "
+      ),
+      "{err}",
+    );
+  }
+}
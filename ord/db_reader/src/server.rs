@@ -2,30 +2,437 @@ use std::cmp::Ordering;
 use std::error::Error;
 use std::fmt::{Display, Formatter};
 use std::net::SocketAddr;
-use std::path::PathBuf;
-use std::sync::{Arc, Mutex};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
 
+use crate::relay::EventRelay;
 use crate::{
-  BRC20Tx, BitmapInscription, BlockInfo, Brc20ApiServer, IndexTimes, InscriptionEntry,
-  InscriptionInfo, InscriptionInformation, SNSInscription, UTXOInfo,
+  BRC20Tx, BRC20TxPage, BitmapInscription, BlockInfo, BlockInfoPage, Brc20ApiServer, Curse,
+  IndexTimes, InscriptionEntry, InscriptionEvent, InscriptionEventsApiServer, InscriptionInfo,
+  InscriptionInformation, InscriptionTransfer, PendingTransfer, PendingTransfers,
+  PkscriptInscription, PkscriptInscriptions, ReaderGate, RocksDbSettings, SNSInscription,
+  Shutdown, UTXOInfo,
 };
 use bitcoin::Network::{self, Bitcoin, Regtest, Signet, Testnet, Testnet4};
 use hyper::Method;
 use jsonrpsee::core::middleware::RpcServiceBuilder;
-use jsonrpsee::core::{RpcResult, async_trait};
+use jsonrpsee::core::{RpcResult, SubscriptionResult, async_trait};
+use jsonrpsee::PendingSubscriptionSink;
 use jsonrpsee::server::Server;
 use jsonrpsee::types::{ErrorObject, ErrorObjectOwned};
-use rocksdb::{ColumnFamilyDescriptor, DB, IteratorMode, Options};
-use signal_hook::consts::SIGINT;
-use signal_hook::iterator::Signals;
+use rocksdb::{DB, IteratorMode};
+use tokio::signal::unix::{SignalKind, signal};
+use tokio::sync::broadcast::{self, error::RecvError};
+use tokio::sync::watch;
+use tokio::time::{Duration, MissedTickBehavior};
 use tower::ServiceBuilder;
 use tower_http::cors::{Any, CorsLayer};
 
 use crate::Config;
 
+/// Key of `Statistic::InscriptionFilters` in the `statistic_to_count` column family
+const INSCRIPTION_FILTERS_STATISTIC: u64 = 19;
+
+/// Key of `Statistic::IndexAddresses` in the `statistic_to_count` column family
+const INDEX_ADDRESSES_STATISTIC: u64 = 22;
+
+/// Version byte of `ord_inscription_info` records written before envelope
+/// fields were recorded
+const LEGACY_INSCRIPTION_INFO_VERSION: u8 = 0;
+
+/// Latest version of `ord_inscription_info` records this reader understands
+const INSCRIPTION_INFO_VERSION: u8 = 2;
+
+/// Flags of the `Cursed`, `Unbound` and `Vindicated` charms
+const CURSED_CHARM: u16 = 1 << 1;
+const UNBOUND_CHARM: u16 = 1 << 8;
+const VINDICATED_CHARM: u16 = 1 << 10;
+
+const DEFAULT_PKSCRIPT_PAGE: u32 = 100;
+const MAX_PKSCRIPT_PAGE: u32 = 1000;
+
+const DEFAULT_BRC20_TX_PAGE: u32 = 1000;
+const MAX_BRC20_TX_PAGE: u32 = 10_000;
+
+const MAX_BLOCK_INFO_PAGE: u32 = 10_000;
+
+const MAX_INSCRIPTION_INFOS: usize = 1000;
+
+/// The secondary instance requests read from, shared with the task that
+/// catches it up with the primary while requests are being served. Secondary
+/// instances don't support snapshots, so every catch-up starts a new
+/// generation, which requests reading several keys use to detect that they
+/// overlapped one.
+#[derive(Clone)]
+struct SecondaryDb {
+  db: Arc<DB>,
+  epoch: u64,
+  generation: u64,
+  catching_up: bool,
+}
+
+#[derive(Clone)]
 struct RpcServer {
-  db: Arc<Mutex<DB>>,
+  events: broadcast::Sender<InscriptionEvent>,
   network: Network,
+  pending_transfers: Option<PendingTransfers>,
+  secondary: watch::Receiver<SecondaryDb>,
+}
+
+impl RpcServer {
+  /// The secondary instance, for requests that read a single key.
+  fn db(&self) -> Arc<DB> {
+    self.secondary.borrow().db.clone()
+  }
+
+  /// Runs `read` against the secondary instance, and runs it again if the
+  /// secondary instance caught up with the primary in the meantime, so that
+  /// every response reflects a single commit of the primary.
+  async fn read<T>(&self, read: impl Fn(&DB) -> RpcResult<T>) -> RpcResult<T> {
+    let mut secondary = self.secondary.clone();
+
+    loop {
+      let (db, generation) = {
+        let secondary = secondary
+          .wait_for(|secondary| !secondary.catching_up)
+          .await
+          .map_err(|err| wrap_rpc_error(Box::new(err)))?;
+
+        (secondary.db.clone(), secondary.generation)
+      };
+
+      let result = read(&db);
+
+      if secondary.borrow().generation == generation {
+        return result;
+      }
+    }
+  }
+
+  /// BRC-20 transfers recorded in `ord_transfers` from `start_key` up to, but
+  /// excluding, `end_key`. Once `limit` are found, the scan stops and returns
+  /// the `tx_id` of the last one if more follow.
+  fn brc20_txes(
+    &self,
+    db: &DB,
+    start_key: &[u8],
+    end_key: &[u8],
+    limit: Option<usize>,
+  ) -> RpcResult<(Vec<BRC20Tx>, Option<String>)> {
+    let mut inscription_info_map = std::collections::HashMap::new();
+    let mut invalid_brc20_map = std::collections::HashMap::new();
+
+    let ord_transfers = db.cf_handle("ord_transfers").ok_or_else(|| {
+      wrap_rpc_error(Box::new(std::io::Error::new(
+        std::io::ErrorKind::NotFound,
+        "Column family 'ord_transfers' not found",
+      )))
+    })?;
+    let ord_inscription_info = db.cf_handle("ord_inscription_info").ok_or_else(|| {
+      wrap_rpc_error(Box::new(std::io::Error::new(
+        std::io::ErrorKind::NotFound,
+        "Column family 'ord_inscription_info' not found",
+      )))
+    })?;
+
+    let mut iter = db.raw_iterator_cf(ord_transfers);
+    iter.seek(start_key);
+    let mut txes = Vec::new();
+    let mut next = None;
+    while iter.valid() && compare_be_arrays(iter.key().unwrap(), end_key) == Ordering::Less {
+      let raw = iter.value().unwrap().to_vec();
+      let transfer_info = get_transfer_info_from_raw(raw);
+
+      let inscription_id = transfer_info.inscription_id.clone();
+      if invalid_brc20_map.contains_key(&inscription_id) {
+        iter.next();
+        continue;
+      }
+
+      let inscription_info = if inscription_info_map.contains_key(&inscription_id) {
+        inscription_info_map.get(&inscription_id).unwrap()
+      } else {
+        let inscription_id_key = get_inscription_id_key(&inscription_id);
+        let raw_info = db
+          .get_cf(ord_inscription_info, &inscription_id_key)
+          .unwrap()
+          .unwrap();
+        let info = get_inscription_info_from_raw(raw_info, inscription_id.clone())?;
+        inscription_info_map.insert(inscription_id.clone(), info.clone());
+
+        &info.clone()
+      };
+
+      if !is_valid_brc20(inscription_info) {
+        invalid_brc20_map.insert(inscription_id, ());
+        iter.next();
+        continue;
+      }
+
+      if limit == Some(txes.len()) {
+        next = txes.last().map(|tx: &BRC20Tx| tx.tx_id.clone());
+        break;
+      }
+
+      let block_height = u32::from_be_bytes(iter.key().unwrap()[0..4].try_into().unwrap());
+      let tx_index = u32::from_be_bytes(iter.key().unwrap()[4..8].try_into().unwrap());
+      let tx_id = format!("{}:{}", block_height, tx_index);
+
+      txes.push(BRC20Tx {
+        tx_id,
+        inscription_id,
+        inscription_number: inscription_info.inscription_number,
+        old_satpoint: transfer_info.old_satpoint,
+        new_satpoint: transfer_info.new_satpoint,
+        txid: transfer_info.txid,
+        new_pkscript: transfer_info.new_pkscript.clone(),
+        new_wallet: get_wallet(&transfer_info.new_pkscript, self.network),
+        sent_as_fee: transfer_info.sent_as_fee,
+        content: serde_json::from_slice(
+          hex::decode(json_content_hex(inscription_info))
+            .unwrap_or(vec![])
+            .as_slice(),
+        )
+        .unwrap_or(serde_json::Value::Null),
+        byte_len: inscription_info.content_hex.len() as u32 / 2, // Each byte is represented by 2 hex characters
+        parent_id: inscription_info.parent_id.clone(),
+      });
+
+      iter.next();
+    }
+
+    Ok((txes, next))
+  }
+
+  async fn inscriptions_by_pkscript(
+    &self,
+    pkscript: &[u8],
+    after: Option<u32>,
+    limit: Option<u32>,
+  ) -> RpcResult<PkscriptInscriptions> {
+    self
+      .read(|db| {
+        let statistic_to_count = db.cf_handle("statistic_to_count").ok_or_else(|| {
+          wrap_rpc_error(Box::new(std::io::Error::new(
+            std::io::ErrorKind::NotFound,
+            "Column family 'statistic_to_count' not found",
+          )))
+        })?;
+        let script_pubkey_to_inscriptions =
+          db.cf_handle("script_pubkey_to_inscriptions").ok_or_else(|| {
+            wrap_rpc_error(Box::new(std::io::Error::new(
+              std::io::ErrorKind::NotFound,
+              "Column family 'script_pubkey_to_inscriptions' not found",
+            )))
+          })?;
+
+        let index_addresses = db
+          .get_cf(statistic_to_count, INDEX_ADDRESSES_STATISTIC.to_be_bytes())
+          .map_err(|err| wrap_rpc_error(Box::new(err)))?
+          .is_some_and(|raw| u64::from_be_bytes(raw[0..8].try_into().unwrap()) != 0);
+
+        if !index_addresses {
+          return Err(wrap_rpc_error("index was not built with --index-addresses".into()));
+        }
+
+        let pkscript_len =
+          u16::try_from(pkscript.len()).map_err(|_| wrap_rpc_error("pkscript is too long".into()))?;
+        let prefix = [&pkscript_len.to_be_bytes(), pkscript].concat();
+
+        let Some(start) = after.map_or(Some(0), |after| after.checked_add(1)) else {
+          return Ok(PkscriptInscriptions {
+            inscriptions: Vec::new(),
+            next: None,
+          });
+        };
+
+        let limit = usize::try_from(limit.unwrap_or(DEFAULT_PKSCRIPT_PAGE).clamp(1, MAX_PKSCRIPT_PAGE)).unwrap();
+
+        let mut iter = db.raw_iterator_cf(script_pubkey_to_inscriptions);
+        iter.seek([prefix.as_slice(), &start.to_be_bytes()].concat());
+
+        let mut inscriptions = Vec::<PkscriptInscription>::new();
+        let mut next = None;
+        while let (Some(key), Some(value)) = (iter.key(), iter.value()) {
+          if key.len() != prefix.len() + 4 || !key.starts_with(&prefix) {
+            break;
+          }
+
+          if inscriptions.len() == limit {
+            next = inscriptions.last().map(|inscription| inscription.sequence_number);
+            break;
+          }
+
+          inscriptions.push(PkscriptInscription {
+            inscription_id: load_inscription_id(&value[0..36]).unwrap(),
+            sequence_number: u32::from_be_bytes(key[prefix.len()..].try_into().unwrap()),
+            satpoint: load_satpoint(&value[36..80]).unwrap_or_default(),
+          });
+
+          iter.next();
+        }
+
+        Ok(PkscriptInscriptions { inscriptions, next })
+      })
+      .await
+  }
+}
+
+const COLUMN_FAMILIES: &[&str] = &[
+  "height_to_block_header",
+  "inscription_id_to_sequence_number",
+  "sequence_number_to_inscription_entry",
+  "outpoint_to_utxo_entry",
+  "ord_transfers",
+  "ord_inscription_info",
+  "ord_index_stats",
+  "statistic_to_count",
+  "script_pubkey_to_inscriptions",
+  "sequence_number_to_transfers",
+];
+
+fn open_secondary_db(index_path: &Path, rocksdb: &RocksDbSettings) -> Result<DB, rocksdb::Error> {
+  let mut options = rocksdb.db_options();
+  // Secondary instances must keep table files open, since the primary may
+  // delete them at any time
+  options.set_max_open_files(-1);
+
+  DB::open_cf_descriptors_as_secondary(
+    &options,
+    &index_path.join("index.db"),
+    &index_path.join("secondary.db"),
+    rocksdb.column_family_descriptors(COLUMN_FAMILIES),
+  )
+}
+
+/// Catches the secondary instance up with the primary, or reopens it if the
+/// primary database was replaced while the reader gate was paused. Returns
+/// `None` while the gate is paused.
+fn catch_up_with_primary(
+  secondary: &watch::Sender<SecondaryDb>,
+  gate: &ReaderGate,
+  index_path: &Path,
+  rocksdb: &RocksDbSettings,
+) -> Option<Result<Arc<DB>, rocksdb::Error>> {
+  let pass = gate.try_enter()?;
+
+  let SecondaryDb { db, epoch, .. } = secondary.borrow().clone();
+
+  if epoch != pass.epoch() {
+    println!("Primary database was replaced, reopening secondary database");
+
+    // Requests keep reading the old instance until the new one is ready
+    return Some(open_secondary_db(index_path, rocksdb).map(|db| {
+      let db = Arc::new(db);
+
+      secondary.send_modify(|secondary| {
+        secondary.db = db.clone();
+        secondary.epoch = pass.epoch();
+        secondary.generation += 1;
+      });
+
+      db
+    }));
+  }
+
+  secondary.send_modify(|secondary| {
+    secondary.catching_up = true;
+    secondary.generation += 1;
+  });
+
+  let result = db.try_catch_up_with_primary();
+
+  secondary.send_modify(|secondary| secondary.catching_up = false);
+
+  Some(result.map(|()| db))
+}
+
+fn latest_block_height(db: &DB) -> Option<u32> {
+  let height_to_block_header = db.cf_handle("height_to_block_header")?;
+
+  db.iterator_cf(height_to_block_header, IteratorMode::End)
+    .next()
+    .transpose()
+    .unwrap_or(None)
+    .map(|(height, _header)| u32::from_be_bytes((*height).try_into().unwrap()))
+}
+
+/// The entry of `inscription_id`, and its info if it is tracked
+fn inscription_information(
+  db: &DB,
+  inscription_id: &str,
+) -> RpcResult<Option<InscriptionInformation>> {
+  let ord_inscription_info = db.cf_handle("ord_inscription_info").ok_or_else(|| {
+    wrap_rpc_error(Box::new(std::io::Error::new(
+      std::io::ErrorKind::NotFound,
+      "Column family 'ord_inscription_info' not found",
+    )))
+  })?;
+  let inscription_id_to_sequence_number = db
+    .cf_handle("inscription_id_to_sequence_number")
+    .ok_or_else(|| {
+      wrap_rpc_error(Box::new(std::io::Error::new(
+        std::io::ErrorKind::NotFound,
+        "Column family 'inscription_id_to_sequence_number' not found",
+      )))
+    })?;
+  let sequence_number_to_inscription_entry = db
+    .cf_handle("sequence_number_to_inscription_entry")
+    .ok_or_else(|| {
+      wrap_rpc_error(Box::new(std::io::Error::new(
+        std::io::ErrorKind::NotFound,
+        "Column family 'sequence_number_to_inscription_entry' not found",
+      )))
+    })?;
+
+  let inscription_id_key = get_inscription_id_key(inscription_id);
+  // Check if the inscription_id exists in the inscription_id_to_sequence_number column family
+  let sequence_number_raw = db
+    .get_cf(inscription_id_to_sequence_number, &inscription_id_key)
+    .unwrap();
+  if sequence_number_raw.is_none() {
+    return Ok(None);
+  }
+  let sequence_number =
+    u32::from_be_bytes(sequence_number_raw.unwrap()[0..4].try_into().unwrap());
+
+  // Now check if the sequence_number exists in the sequence_number_to_inscription_entry column family
+  let entry_raw = db
+    .get_cf(
+      sequence_number_to_inscription_entry,
+      sequence_number.to_be_bytes(),
+    )
+    .unwrap();
+  if entry_raw.is_none() {
+    return Ok(None);
+  }
+  // If both checks passed, we can retrieve the inscription info
+  let entry = get_inscription_entry_from_raw(entry_raw.unwrap().to_vec());
+
+  let info = db
+    .get_cf(ord_inscription_info, &inscription_id_key)
+    .unwrap()
+    .map(|raw| get_inscription_info_from_raw(raw, inscription_id.to_string()))
+    .transpose()?;
+
+  Ok(Some(InscriptionInformation { info, entry }))
+}
+
+/// Fails unless blocks `from_height` to `to_height` have all been indexed
+fn check_indexed_range(db: &DB, from_height: u32, to_height: u32) -> RpcResult<()> {
+  if from_height > to_height {
+    return Err(wrap_rpc_error(
+      format!("from_height {from_height} is above to_height {to_height}").into(),
+    ));
+  }
+
+  match latest_block_height(db) {
+    Some(latest) if to_height <= latest => Ok(()),
+    Some(latest) => Err(wrap_rpc_error(
+      format!("block {to_height} is not indexed yet, the latest is {latest}").into(),
+    )),
+    None => Err(wrap_rpc_error("no blocks are indexed yet".into())),
+  }
 }
 
 fn wrap_rpc_error(error: Box<dyn Error>) -> ErrorObject<'static> {
@@ -33,9 +440,7 @@ fn wrap_rpc_error(error: Box<dyn Error>) -> ErrorObject<'static> {
 }
 
 fn get_times_from_raw(raw: Option<Vec<u8>>) -> Option<IndexTimes> {
-  if raw.is_none() {
-    return None;
-  }
+  raw.as_ref()?;
   let raw = raw?;
   let mut iter = raw.chunks(16);
   let fetch_tm = u128::from_be_bytes(iter.next()?.try_into().ok()?);
@@ -62,7 +467,77 @@ fn load_inscription_id(raw: &[u8]) -> Option<String> {
   Some(format!("{}i{}", txid, index))
 }
 
-fn get_inscription_info_from_raw(raw: Vec<u8>, inscription_id: String) -> InscriptionInfo {
+/// Parses an `ord_inscription_info` record, which starts with its format
+/// version. Legacy records carry a single parent and no other envelope fields.
+/// Records written by a newer version of ord are rejected rather than misread.
+fn get_inscription_info_from_raw(
+  raw: Vec<u8>,
+  inscription_id: String,
+) -> RpcResult<InscriptionInfo> {
+  let record_version = raw[0];
+  let raw = &raw[1..];
+
+  if record_version == LEGACY_INSCRIPTION_INFO_VERSION {
+    return Ok(get_legacy_inscription_info_from_raw(raw, inscription_id));
+  }
+
+  if record_version > INSCRIPTION_INFO_VERSION {
+    return Err(wrap_rpc_error(
+      format!("unknown ord_inscription_info record version {record_version}").into(),
+    ));
+  }
+
+  let mut rest = raw;
+  let mut take = |len: usize| {
+    let (head, tail) = rest.split_at(len);
+    rest = tail;
+    head
+  };
+
+  let inscription_number = i32::from_be_bytes(take(4).try_into().unwrap());
+  let cursed_for_brc20 = take(1)[0] != 0;
+  let is_json = take(1)[0] != 0;
+  let parent_count = u32::from_be_bytes(take(4).try_into().unwrap());
+  let parent_ids = (0..parent_count)
+    .filter_map(|_| load_inscription_id(take(36)))
+    .collect::<Vec<String>>();
+
+  let mut field = || {
+    (take(1)[0] != 0).then(|| {
+      let len = u32::from_be_bytes(take(4).try_into().unwrap());
+      take(len as usize).to_vec()
+    })
+  };
+
+  let content = field();
+  let content_type = field();
+  let metaprotocol = field();
+  let content_encoding = field();
+  let metadata = field();
+  let delegate = field();
+  let pointer = field();
+  let decoded_content = if record_version >= 2 { field() } else { None };
+
+  Ok(InscriptionInfo {
+    _inscription_id: inscription_id,
+    inscription_number,
+    cursed_for_brc20,
+    parent_id: parent_ids.first().cloned(),
+    parent_ids,
+    is_json,
+    content_hex: hex::encode(content.unwrap_or_default()),
+    content_type_hex: hex::encode(content_type.unwrap_or_default()),
+    _metaprotocol_hex: hex::encode(metaprotocol.unwrap_or_default()),
+    content_encoding_hex: content_encoding.map(hex::encode),
+    metadata_hex: metadata.map(hex::encode),
+    delegate_id: delegate.and_then(|delegate| load_inscription_id(&delegate)),
+    pointer: pointer.map(|pointer| u64::from_be_bytes(pointer[0..8].try_into().unwrap())),
+    decoded_content_hex: decoded_content.map(hex::encode),
+    record_version,
+  })
+}
+
+fn get_legacy_inscription_info_from_raw(raw: &[u8], inscription_id: String) -> InscriptionInfo {
   let inscription_number = i32::from_be_bytes(raw[0..4].try_into().ok().unwrap());
   let cursed_for_brc20 = raw[4] != 0;
   let parent_id = load_inscription_id(&raw[5..41]);
@@ -93,11 +568,18 @@ fn get_inscription_info_from_raw(raw: Vec<u8>, inscription_id: String) -> Inscri
     _inscription_id: inscription_id,
     inscription_number,
     cursed_for_brc20,
+    parent_ids: parent_id.iter().cloned().collect(),
     parent_id,
     is_json,
     content_hex,
     content_type_hex,
     _metaprotocol_hex: metaprotocol_hex,
+    content_encoding_hex: None,
+    metadata_hex: None,
+    delegate_id: None,
+    pointer: None,
+    decoded_content_hex: None,
+    record_version: LEGACY_INSCRIPTION_INFO_VERSION,
   }
 }
 
@@ -107,8 +589,17 @@ fn get_inscription_entry_from_raw(raw: Vec<u8>) -> InscriptionEntry {
   let inscription_number = i32::from_be_bytes(raw[38..42].try_into().unwrap());
   let sequence_number = u32::from_be_bytes(raw[42..46].try_into().unwrap());
   let is_json_or_text = raw[46] != 0;
-  let is_cursed_for_brc20 = raw[47] != 0;
-  let txcnt_limit = i16::from_be_bytes(raw[48..50].try_into().unwrap());
+  let txcnt_limit = i16::from_be_bytes(raw[47..49].try_into().unwrap());
+  // Vindicated inscriptions are cursed for brc20, but not numbered as cursed
+  let is_cursed_for_brc20 = charms & (CURSED_CHARM | VINDICATED_CHARM) != 0;
+  let curse = match raw.get(49) {
+    Some(0) => None,
+    Some(&curse) => Some(
+      Curse::from_byte(curse)
+        .map_or_else(|| format!("unknown_{curse}"), |curse| curse.name().to_string()),
+    ),
+    None => is_cursed_for_brc20.then(|| "unknown".to_string()),
+  };
 
   InscriptionEntry {
     charms,
@@ -118,6 +609,8 @@ fn get_inscription_entry_from_raw(raw: Vec<u8>) -> InscriptionEntry {
     is_json_or_text,
     is_cursed_for_brc20,
     txcnt_limit,
+    curse,
+    unbound: charms & UNBOUND_CHARM != 0,
   }
 }
 
@@ -165,11 +658,9 @@ impl Display for VarintError {
 impl std::error::Error for VarintError {}
 
 fn get_utxo_entry_from_raw(raw: Vec<u8>) -> UTXOInfo {
-  let sats;
-
   let mut offset = 0;
   let (value, varint_len) = varint_decode(&raw).unwrap();
-  sats = value as u64;
+  let sats = value as u64;
   offset += varint_len;
 
   let mut parsed_inscriptions = Vec::new();
@@ -223,7 +714,7 @@ struct TransferInfo {
   old_satpoint: Option<String>,
   new_satpoint: String,
   sent_as_fee: bool,
-  _new_output_value: u64,
+  new_output_value: u64,
   txid: String,
   new_pkscript: String,
 }
@@ -241,7 +732,7 @@ fn get_transfer_info_from_raw(raw: Vec<u8>) -> TransferInfo {
     old_satpoint,
     new_satpoint,
     sent_as_fee,
-    _new_output_value: new_output_value,
+    new_output_value,
     txid,
     new_pkscript,
   }
@@ -258,6 +749,16 @@ fn compare_be_arrays(a: &[u8], b: &[u8]) -> Ordering {
   a.len().cmp(&b.len())
 }
 
+/// The body holding the JSON of a JSON inscription. Bodies with a content
+/// encoding ord could decode are never JSON themselves, and ord only counts
+/// their decoded JSON for protocols configured to allow it.
+fn json_content_hex(inscription_info: &InscriptionInfo) -> &str {
+  match &inscription_info.decoded_content_hex {
+    Some(decoded_content_hex) if inscription_info.is_json => decoded_content_hex,
+    _ => &inscription_info.content_hex,
+  }
+}
+
 fn is_valid_brc20(inscription_info: &InscriptionInfo) -> bool {
   if inscription_info.cursed_for_brc20 {
     return false;
@@ -283,7 +784,7 @@ fn is_valid_brc20(inscription_info: &InscriptionInfo) -> bool {
   }
 
   let json_data: serde_json::Value =
-    match serde_json::from_slice(&hex::decode(&inscription_info.content_hex).unwrap()) {
+    match serde_json::from_slice(&hex::decode(json_content_hex(inscription_info)).unwrap()) {
       Ok(data) => data,
       Err(_) => return false, // Invalid JSON
     };
@@ -303,7 +804,7 @@ fn is_valid_brc20(inscription_info: &InscriptionInfo) -> bool {
     }
   }
 
-  return true;
+  true
 }
 
 fn is_valid_bitmap(inscription_info: &InscriptionInfo) -> bool {
@@ -363,6 +864,19 @@ fn get_inscription_id_key(inscription_id: &str) -> Vec<u8> {
   key
 }
 
+/// Parses a `tx_id` of a `BRC20Tx`, `<BLOCK_HEIGHT>:<TRANSFER_INDEX>`, into
+/// the `ord_transfers` key it was made from
+fn parse_tx_id(tx_id: &str) -> RpcResult<Vec<u8>> {
+  let (block_height, transfer_index) = tx_id
+    .split_once(':')
+    .and_then(|(block_height, transfer_index)| {
+      Some((block_height.parse::<u32>().ok()?, transfer_index.parse::<u32>().ok()?))
+    })
+    .ok_or_else(|| wrap_rpc_error(format!("invalid tx_id `{tx_id}`").into()))?;
+
+  Ok([block_height.to_be_bytes(), transfer_index.to_be_bytes()].concat())
+}
+
 fn parse_outpoint(outpoint: &str) -> (Vec<u8>, u32) {
   let parts: Vec<&str> = outpoint.split(':').collect();
   if parts.len() != 2 {
@@ -385,7 +899,7 @@ fn get_outpoint_key(outpoint: &str) -> Vec<u8> {
 #[async_trait]
 impl Brc20ApiServer for RpcServer {
   async fn get_block_index_times(&self, block_height: u32) -> RpcResult<Option<IndexTimes>> {
-    let db = self.db.lock().expect("DB Error");
+    let db = self.db();
     let ord_index_stats = db.cf_handle("ord_index_stats").ok_or_else(|| {
       wrap_rpc_error(Box::new(std::io::Error::new(
         std::io::ErrorKind::NotFound,
@@ -394,98 +908,29 @@ impl Brc20ApiServer for RpcServer {
     })?;
 
     Ok(
-      db.get_cf(ord_index_stats, &block_height.to_be_bytes())
-        .map(|time| get_times_from_raw(time))
+      db.get_cf(ord_index_stats, block_height.to_be_bytes())
+        .map(get_times_from_raw)
         .unwrap(),
     )
   }
 
   async fn get_block_brc20_txes(&self, block_height: u32) -> RpcResult<Option<Vec<BRC20Tx>>> {
-    let mut inscription_info_map = std::collections::HashMap::new();
-    let mut invalid_brc20_map = std::collections::HashMap::new();
-
-    let db = self.db.lock().expect("DB Error");
-    let ord_transfers = db.cf_handle("ord_transfers").ok_or_else(|| {
-      wrap_rpc_error(Box::new(std::io::Error::new(
-        std::io::ErrorKind::NotFound,
-        "Column family 'ord_transfers' not found",
-      )))
-    })?;
-    let ord_inscription_info = db.cf_handle("ord_inscription_info").ok_or_else(|| {
-      wrap_rpc_error(Box::new(std::io::Error::new(
-        std::io::ErrorKind::NotFound,
-        "Column family 'ord_inscription_info' not found",
-      )))
-    })?;
-
-    // scan ord_transfers from block_height.0u32 to (block_height+1).0u32
-    let start_key = block_height.to_be_bytes();
-    let end_key = (block_height + 1).to_be_bytes();
-    let mut iter = db.raw_iterator_cf(ord_transfers);
-    iter.seek(start_key);
-    let mut txes = Vec::new();
-    while iter.valid() && compare_be_arrays(iter.key().unwrap(), &end_key) == Ordering::Less {
-      let raw = iter.value().unwrap().to_vec();
-      let transfer_info = get_transfer_info_from_raw(raw);
-
-      let inscription_id = transfer_info.inscription_id.clone();
-      if invalid_brc20_map.contains_key(&inscription_id) {
-        iter.next();
-        continue;
-      }
-
-      let inscription_info = if inscription_info_map.contains_key(&inscription_id) {
-        inscription_info_map.get(&inscription_id).unwrap()
-      } else {
-        let inscription_id_key = get_inscription_id_key(&inscription_id);
-        let raw_info = db
-          .get_cf(ord_inscription_info, &inscription_id_key)
-          .unwrap()
-          .unwrap();
-        let info = get_inscription_info_from_raw(raw_info, inscription_id.clone());
-        inscription_info_map.insert(inscription_id.clone(), info.clone());
-
-        &info.clone()
-      };
-
-      if !is_valid_brc20(inscription_info) {
-        invalid_brc20_map.insert(inscription_id, ());
-        iter.next();
-        continue;
-      }
-
-      let block_height = u32::from_be_bytes(iter.key().unwrap()[0..4].try_into().unwrap());
-      let tx_index = u32::from_be_bytes(iter.key().unwrap()[4..8].try_into().unwrap());
-      let tx_id = format!("{}:{}", block_height, tx_index);
-
-      txes.push(BRC20Tx {
-        tx_id,
-        inscription_id,
-        inscription_number: inscription_info.inscription_number,
-        old_satpoint: transfer_info.old_satpoint,
-        new_satpoint: transfer_info.new_satpoint,
-        txid: transfer_info.txid,
-        new_pkscript: transfer_info.new_pkscript.clone(),
-        new_wallet: get_wallet(&transfer_info.new_pkscript, self.network),
-        sent_as_fee: transfer_info.sent_as_fee,
-        content: serde_json::from_slice(
-          hex::decode(&inscription_info.content_hex)
-            .unwrap_or(vec![])
-            .as_slice(),
-        )
-        .unwrap_or(serde_json::Value::Null),
-        byte_len: inscription_info.content_hex.len() as u32 / 2, // Each byte is represented by 2 hex characters
-        parent_id: inscription_info.parent_id.clone(),
-      });
-
-      iter.next();
-    }
-
-    Ok(Some(txes))
+    self
+      .read(|db| {
+        let (txes, _next) = self.brc20_txes(
+          db,
+          &block_height.to_be_bytes(),
+          &(block_height + 1).to_be_bytes(),
+          None,
+        )?;
+
+        Ok(Some(txes))
+      })
+      .await
   }
 
   async fn get_block_hash_and_ts(&self, block_height: u32) -> RpcResult<Option<BlockInfo>> {
-    let db = self.db.lock().expect("DB Error");
+    let db = self.db();
     let height_to_block_header = db.cf_handle("height_to_block_header").ok_or_else(|| {
       wrap_rpc_error(Box::new(std::io::Error::new(
         std::io::ErrorKind::NotFound,
@@ -494,7 +939,7 @@ impl Brc20ApiServer for RpcServer {
     })?;
 
     let key = block_height.to_be_bytes();
-    if let Some(raw) = db.get_cf(height_to_block_header, &key).unwrap() {
+    if let Some(raw) = db.get_cf(height_to_block_header, key).unwrap() {
       let header: bitcoin::block::Header = bitcoin::consensus::encode::deserialize(&raw).unwrap();
       let hash = header.block_hash().to_string();
       let timestamp = header.time as u64;
@@ -507,8 +952,84 @@ impl Brc20ApiServer for RpcServer {
     }
   }
 
+  async fn get_block_range_brc20_txes(
+    &self,
+    from_height: u32,
+    to_height: u32,
+    after: Option<String>,
+    limit: Option<u32>,
+  ) -> RpcResult<BRC20TxPage> {
+    self
+      .read(|db| {
+        check_indexed_range(db, from_height, to_height)?;
+
+        let mut start_key = from_height.to_be_bytes().to_vec();
+        if let Some(after) = &after {
+          // Appending a zero byte gives the first key after it
+          start_key = start_key.max([parse_tx_id(after)?.as_slice(), &[0]].concat());
+        }
+
+        let limit =
+          usize::try_from(limit.unwrap_or(DEFAULT_BRC20_TX_PAGE).clamp(1, MAX_BRC20_TX_PAGE)).unwrap();
+
+        let (txes, next) = self.brc20_txes(
+          db,
+          &start_key,
+          &(to_height + 1).to_be_bytes(),
+          Some(limit),
+        )?;
+
+        Ok(BRC20TxPage { txes, next })
+      })
+      .await
+  }
+
+  async fn get_block_hashes_and_ts(
+    &self,
+    from_height: u32,
+    to_height: u32,
+  ) -> RpcResult<BlockInfoPage> {
+    self
+      .read(|db| {
+        let height_to_block_header = db.cf_handle("height_to_block_header").ok_or_else(|| {
+          wrap_rpc_error(Box::new(std::io::Error::new(
+            std::io::ErrorKind::NotFound,
+            "Column family 'height_to_block_header' not found",
+          )))
+        })?;
+
+        check_indexed_range(db, from_height, to_height)?;
+
+        let last_height = to_height.min(from_height.saturating_add(MAX_BLOCK_INFO_PAGE - 1));
+
+        let mut iter = db.raw_iterator_cf(height_to_block_header);
+        iter.seek(from_height.to_be_bytes());
+
+        let mut blocks = Vec::new();
+        while let (Some(key), Some(value)) = (iter.key(), iter.value()) {
+          if u32::from_be_bytes(key.try_into().unwrap()) > last_height {
+            break;
+          }
+
+          let header: bitcoin::block::Header = bitcoin::consensus::encode::deserialize(value).unwrap();
+          blocks.push(BlockInfo {
+            block_hash: header.block_hash().to_string(),
+            timestamp: header.time as u64,
+          });
+
+          iter.next();
+        }
+
+        Ok(BlockInfoPage {
+          blocks,
+          next: (last_height < to_height).then(|| last_height + 1),
+        })
+      })
+      .await
+  }
+
   async fn get_latest_block_height(&self) -> RpcResult<Option<u32>> {
-    let db = self.db.lock().expect("DB Error");
+    let db = self.db();
     let height_to_block_header = db.cf_handle("height_to_block_header").ok_or_else(|| {
       wrap_rpc_error(Box::new(std::io::Error::new(
         std::io::ErrorKind::NotFound,
@@ -530,71 +1051,33 @@ impl Brc20ApiServer for RpcServer {
     &self,
     inscription_id: String,
   ) -> RpcResult<Option<InscriptionInformation>> {
-    let db = self.db.lock().expect("DB Error");
-    let ord_inscription_info = db.cf_handle("ord_inscription_info").ok_or_else(|| {
-      wrap_rpc_error(Box::new(std::io::Error::new(
-        std::io::ErrorKind::NotFound,
-        "Column family 'ord_inscription_info' not found",
-      )))
-    })?;
-    let inscription_id_to_sequence_number = db
-      .cf_handle("inscription_id_to_sequence_number")
-      .ok_or_else(|| {
-        wrap_rpc_error(Box::new(std::io::Error::new(
-          std::io::ErrorKind::NotFound,
-          "Column family 'inscription_id_to_sequence_number' not found",
-        )))
-      })?;
-    let sequence_number_to_inscription_entry = db
-      .cf_handle("sequence_number_to_inscription_entry")
-      .ok_or_else(|| {
-        wrap_rpc_error(Box::new(std::io::Error::new(
-          std::io::ErrorKind::NotFound,
-          "Column family 'sequence_number_to_inscription_entry' not found",
-        )))
-      })?;
-
-    let inscription_id_key = get_inscription_id_key(&inscription_id);
-    // Check if the inscription_id exists in the inscription_id_to_sequence_number column family
-    let sequence_number_raw = db
-      .get_cf(inscription_id_to_sequence_number, &inscription_id_key)
-      .unwrap();
-    if sequence_number_raw.is_none() {
-      return Ok(None);
-    }
-    let sequence_number =
-      u32::from_be_bytes(sequence_number_raw.unwrap()[0..4].try_into().unwrap());
-
-    // Now check if the sequence_number exists in the sequence_number_to_inscription_entry column family
-    let entry_raw = self
-      .db
-      .lock()
-      .expect("DB Error")
-      .get_cf(
-        sequence_number_to_inscription_entry,
-        &sequence_number.to_be_bytes(),
-      )
-      .unwrap();
-    if entry_raw.is_none() {
-      return Ok(None);
-    }
-    // If both checks passed, we can retrieve the inscription info
-    let entry = get_inscription_entry_from_raw(entry_raw.unwrap().to_vec());
-
-    if let Some(raw) = db
-      .get_cf(ord_inscription_info, &inscription_id_key)
-      .unwrap()
-    {
-      let info = get_inscription_info_from_raw(raw, inscription_id.clone());
+    self
+      .read(|db| inscription_information(db, &inscription_id))
+      .await
+  }
 
-      Ok(Some(InscriptionInformation { info, entry }))
-    } else {
-      Ok(None)
+  async fn get_inscription_infos(
+    &self,
+    inscription_ids: Vec<String>,
+  ) -> RpcResult<Vec<Option<InscriptionInformation>>> {
+    if inscription_ids.len() > MAX_INSCRIPTION_INFOS {
+      return Err(wrap_rpc_error(
+        format!("at most {MAX_INSCRIPTION_INFOS} inscriptions can be requested at once").into(),
+      ));
     }
+
+    self
+      .read(|db| {
+        inscription_ids
+          .iter()
+          .map(|inscription_id| inscription_information(db, inscription_id))
+          .collect()
+      })
+      .await
   }
 
   async fn get_utxo_info(&self, outpoint: String) -> RpcResult<Option<UTXOInfo>> {
-    let db = self.db.lock().expect("DB Error");
+    let db = self.db();
     let outpoint_to_utxo_entry = db.cf_handle("outpoint_to_utxo_entry").ok_or_else(|| {
       wrap_rpc_error(Box::new(std::io::Error::new(
         std::io::ErrorKind::NotFound,
@@ -616,193 +1099,380 @@ impl Brc20ApiServer for RpcServer {
     &self,
     sequence_number: u32,
   ) -> RpcResult<Option<InscriptionInformation>> {
-    let db = self.db.lock().expect("DB Error");
-    let ord_inscription_info = db.cf_handle("ord_inscription_info").ok_or_else(|| {
-      wrap_rpc_error(Box::new(std::io::Error::new(
-        std::io::ErrorKind::NotFound,
-        "Column family 'ord_inscription_info' not found",
-      )))
-    })?;
-    let sequence_number_to_inscription_entry = db
-      .cf_handle("sequence_number_to_inscription_entry")
-      .ok_or_else(|| {
-        wrap_rpc_error(Box::new(std::io::Error::new(
-          std::io::ErrorKind::NotFound,
-          "Column family 'sequence_number_to_inscription_entry' not found",
-        )))
-      })?;
-
-    let entry_raw = db
-      .get_cf(
-        sequence_number_to_inscription_entry,
-        &sequence_number.to_be_bytes(),
-      )
-      .unwrap();
-    if entry_raw.is_none() {
-      return Ok(None);
-    }
-    let entry = get_inscription_entry_from_raw(entry_raw.unwrap().to_vec());
-
-    let inscription_id_key = get_inscription_id_key(&entry.id);
-    if let Some(raw) = self
-      .db
-      .lock()
-      .expect("DB Error")
-      .get_cf(ord_inscription_info, &inscription_id_key)
-      .unwrap()
-    {
-      let info = get_inscription_info_from_raw(raw, entry.id.clone());
+    self
+      .read(|db| {
+        let ord_inscription_info = db.cf_handle("ord_inscription_info").ok_or_else(|| {
+          wrap_rpc_error(Box::new(std::io::Error::new(
+            std::io::ErrorKind::NotFound,
+            "Column family 'ord_inscription_info' not found",
+          )))
+        })?;
+        let sequence_number_to_inscription_entry = db
+          .cf_handle("sequence_number_to_inscription_entry")
+          .ok_or_else(|| {
+            wrap_rpc_error(Box::new(std::io::Error::new(
+              std::io::ErrorKind::NotFound,
+              "Column family 'sequence_number_to_inscription_entry' not found",
+            )))
+          })?;
+
+        let entry_raw = db
+          .get_cf(
+            sequence_number_to_inscription_entry,
+            sequence_number.to_be_bytes(),
+          )
+          .unwrap();
+        if entry_raw.is_none() {
+          return Ok(None);
+        }
+        let entry = get_inscription_entry_from_raw(entry_raw.unwrap().to_vec());
 
-      Ok(Some(InscriptionInformation { info, entry }))
-    } else {
-      Ok(None)
-    }
+        let inscription_id_key = get_inscription_id_key(&entry.id);
+        let info = db
+          .get_cf(ord_inscription_info, &inscription_id_key)
+          .unwrap()
+          .map(|raw| get_inscription_info_from_raw(raw, entry.id.clone()))
+          .transpose()?;
+
+        Ok(Some(InscriptionInformation { info, entry }))
+      })
+      .await
   }
 
   async fn get_block_bitmap_inscrs(
     &self,
     block_height: u32,
   ) -> RpcResult<Option<Vec<BitmapInscription>>> {
-    let db = self.db.lock().expect("DB Error");
-    let ord_transfers = db.cf_handle("ord_transfers").ok_or_else(|| {
-      wrap_rpc_error(Box::new(std::io::Error::new(
-        std::io::ErrorKind::NotFound,
-        "Column family 'ord_transfers' not found",
-      )))
-    })?;
-    let ord_inscription_info = db.cf_handle("ord_inscription_info").ok_or_else(|| {
-      wrap_rpc_error(Box::new(std::io::Error::new(
-        std::io::ErrorKind::NotFound,
-        "Column family 'ord_inscription_info' not found",
-      )))
-    })?;
-
-    // scan ord_transfers from block_height.0u32 to (block_height+1).0u32
-    let start_key = block_height.to_be_bytes();
-    let end_key = (block_height + 1).to_be_bytes();
-    let mut iter = db.raw_iterator_cf(ord_transfers);
-    iter.seek(start_key);
-    let mut bitmap_inscrs = Vec::new();
-    while iter.valid() && compare_be_arrays(iter.key().unwrap(), &end_key) == Ordering::Less {
-      let raw = iter.value().unwrap().to_vec();
-      let transfer_info = get_transfer_info_from_raw(raw);
-
-      if transfer_info.old_satpoint.is_some() {
-        // This is a transfer, skip it
-        iter.next();
-        continue;
-      }
-
-      let inscription_id = transfer_info.inscription_id.clone();
-
-      let inscription_id_key = get_inscription_id_key(&inscription_id);
-      let raw_info = db
-        .get_cf(ord_inscription_info, &inscription_id_key)
-        .unwrap()
-        .unwrap();
-      let info = get_inscription_info_from_raw(raw_info, inscription_id.clone());
-      let inscription_info = info.clone();
-
-      if !is_valid_bitmap(&inscription_info) {
-        iter.next();
-        continue;
-      }
-
-      let block_height = u32::from_be_bytes(iter.key().unwrap()[0..4].try_into().unwrap());
-      let tx_index = u32::from_be_bytes(iter.key().unwrap()[4..8].try_into().unwrap());
-      let tx_id = format!("{}:{}", block_height, tx_index);
-
-      bitmap_inscrs.push(BitmapInscription {
-        tx_id,
-        inscription_id,
-        inscription_number: inscription_info.inscription_number,
-        txid: transfer_info.txid,
-        content_hex: inscription_info.content_hex.clone(),
-      });
-
-      iter.next();
-    }
-
-    // sort bitmap_inscrs by inscription_number
-    bitmap_inscrs.sort_by(|a, b| a.inscription_number.cmp(&b.inscription_number));
-
-    Ok(Some(bitmap_inscrs))
+    self
+      .read(|db| {
+        let ord_transfers = db.cf_handle("ord_transfers").ok_or_else(|| {
+          wrap_rpc_error(Box::new(std::io::Error::new(
+            std::io::ErrorKind::NotFound,
+            "Column family 'ord_transfers' not found",
+          )))
+        })?;
+        let ord_inscription_info = db.cf_handle("ord_inscription_info").ok_or_else(|| {
+          wrap_rpc_error(Box::new(std::io::Error::new(
+            std::io::ErrorKind::NotFound,
+            "Column family 'ord_inscription_info' not found",
+          )))
+        })?;
+
+        // scan ord_transfers from block_height.0u32 to (block_height+1).0u32
+        let start_key = block_height.to_be_bytes();
+        let end_key = (block_height + 1).to_be_bytes();
+        let mut iter = db.raw_iterator_cf(ord_transfers);
+        iter.seek(start_key);
+        let mut bitmap_inscrs = Vec::new();
+        while iter.valid() && compare_be_arrays(iter.key().unwrap(), &end_key) == Ordering::Less {
+          let raw = iter.value().unwrap().to_vec();
+          let transfer_info = get_transfer_info_from_raw(raw);
+
+          if transfer_info.old_satpoint.is_some() {
+            // This is a transfer, skip it
+            iter.next();
+            continue;
+          }
+
+          let inscription_id = transfer_info.inscription_id.clone();
+
+          let inscription_id_key = get_inscription_id_key(&inscription_id);
+          let raw_info = db
+            .get_cf(ord_inscription_info, &inscription_id_key)
+            .unwrap()
+            .unwrap();
+          let info = get_inscription_info_from_raw(raw_info, inscription_id.clone())?;
+          let inscription_info = info.clone();
+
+          if !is_valid_bitmap(&inscription_info) {
+            iter.next();
+            continue;
+          }
+
+          let block_height = u32::from_be_bytes(iter.key().unwrap()[0..4].try_into().unwrap());
+          let tx_index = u32::from_be_bytes(iter.key().unwrap()[4..8].try_into().unwrap());
+          let tx_id = format!("{}:{}", block_height, tx_index);
+
+          bitmap_inscrs.push(BitmapInscription {
+            tx_id,
+            inscription_id,
+            inscription_number: inscription_info.inscription_number,
+            txid: transfer_info.txid,
+            content_hex: inscription_info.content_hex.clone(),
+          });
+
+          iter.next();
+        }
+
+        // sort bitmap_inscrs by inscription_number
+        bitmap_inscrs.sort_by_key(|a| a.inscription_number);
+
+        Ok(Some(bitmap_inscrs))
+      })
+      .await
   }
 
   async fn get_block_sns_inscrs(
     &self,
     block_height: u32,
   ) -> RpcResult<Option<Vec<SNSInscription>>> {
-    let db = self.db.lock().expect("DB Error");
-    let ord_transfers = db.cf_handle("ord_transfers").ok_or_else(|| {
-      wrap_rpc_error(Box::new(std::io::Error::new(
-        std::io::ErrorKind::NotFound,
-        "Column family 'ord_transfers' not found",
-      )))
-    })?;
-    let ord_inscription_info = db.cf_handle("ord_inscription_info").ok_or_else(|| {
+    self
+      .read(|db| {
+        let ord_transfers = db.cf_handle("ord_transfers").ok_or_else(|| {
+          wrap_rpc_error(Box::new(std::io::Error::new(
+            std::io::ErrorKind::NotFound,
+            "Column family 'ord_transfers' not found",
+          )))
+        })?;
+        let ord_inscription_info = db.cf_handle("ord_inscription_info").ok_or_else(|| {
+          wrap_rpc_error(Box::new(std::io::Error::new(
+            std::io::ErrorKind::NotFound,
+            "Column family 'ord_inscription_info' not found",
+          )))
+        })?;
+
+        // scan ord_transfers from block_height.0u32 to (block_height+1).0u32
+        let start_key = block_height.to_be_bytes();
+        let end_key = (block_height + 1).to_be_bytes();
+        let mut iter = db.raw_iterator_cf(ord_transfers);
+        iter.seek(start_key);
+        let mut sns_inscrs = Vec::new();
+        while iter.valid() && compare_be_arrays(iter.key().unwrap(), &end_key) == Ordering::Less {
+          let raw = iter.value().unwrap().to_vec();
+          let transfer_info = get_transfer_info_from_raw(raw);
+
+          if transfer_info.old_satpoint.is_some() {
+            // This is a transfer, skip it
+            iter.next();
+            continue;
+          }
+
+          let inscription_id = transfer_info.inscription_id.clone();
+
+          let inscription_id_key = get_inscription_id_key(&inscription_id);
+          let raw_info = db
+            .get_cf(ord_inscription_info, &inscription_id_key)
+            .unwrap()
+            .unwrap();
+          let info = get_inscription_info_from_raw(raw_info, inscription_id.clone())?;
+          let inscription_info = info.clone();
+
+          if !is_valid_sns(&inscription_info) {
+            iter.next();
+            continue;
+          }
+
+          let block_height = u32::from_be_bytes(iter.key().unwrap()[0..4].try_into().unwrap());
+          let tx_index = u32::from_be_bytes(iter.key().unwrap()[4..8].try_into().unwrap());
+          let tx_id = format!("{}:{}", block_height, tx_index);
+
+          sns_inscrs.push(SNSInscription {
+            tx_id,
+            inscription_id,
+            inscription_number: inscription_info.inscription_number,
+            txid: transfer_info.txid,
+            content_hex: inscription_info.content_hex.clone(),
+            content_type_hex: inscription_info.content_type_hex.clone(),
+          });
+
+          iter.next();
+        }
+
+        // sort sns_inscrs by inscription_number
+        sns_inscrs.sort_by_key(|a| a.inscription_number);
+
+        Ok(Some(sns_inscrs))
+      })
+      .await
+  }
+
+  async fn get_inscription_filters(&self) -> RpcResult<Vec<String>> {
+    let db = self.db();
+    let statistic_to_count = db.cf_handle("statistic_to_count").ok_or_else(|| {
       wrap_rpc_error(Box::new(std::io::Error::new(
         std::io::ErrorKind::NotFound,
-        "Column family 'ord_inscription_info' not found",
+        "Column family 'statistic_to_count' not found",
       )))
     })?;
 
-    // scan ord_transfers from block_height.0u32 to (block_height+1).0u32
-    let start_key = block_height.to_be_bytes();
-    let end_key = (block_height + 1).to_be_bytes();
-    let mut iter = db.raw_iterator_cf(ord_transfers);
-    iter.seek(start_key);
-    let mut sns_inscrs = Vec::new();
-    while iter.valid() && compare_be_arrays(iter.key().unwrap(), &end_key) == Ordering::Less {
-      let raw = iter.value().unwrap().to_vec();
-      let transfer_info = get_transfer_info_from_raw(raw);
+    match db
+      .get_cf(statistic_to_count, INSCRIPTION_FILTERS_STATISTIC.to_be_bytes())
+      .map_err(|err| wrap_rpc_error(Box::new(err)))?
+    {
+      Some(raw) => serde_json::from_slice(&raw).map_err(|err| wrap_rpc_error(Box::new(err))),
+      None => Ok(Vec::new()),
+    }
+  }
 
-      if transfer_info.old_satpoint.is_some() {
-        // This is a transfer, skip it
-        iter.next();
-        continue;
-      }
+  async fn get_pending_transfers(&self) -> RpcResult<Vec<PendingTransfer>> {
+    let pending_transfers = self
+      .pending_transfers
+      .as_ref()
+      .ok_or_else(|| wrap_rpc_error("indexer is not running with --watch-mempool".into()))?;
 
-      let inscription_id = transfer_info.inscription_id.clone();
+    Ok(pending_transfers.get().to_vec())
+  }
 
-      let inscription_id_key = get_inscription_id_key(&inscription_id);
-      let raw_info = db
-        .get_cf(ord_inscription_info, &inscription_id_key)
-        .unwrap()
-        .unwrap();
-      let info = get_inscription_info_from_raw(raw_info, inscription_id.clone());
-      let inscription_info = info.clone();
+  async fn get_inscriptions_by_pkscript(
+    &self,
+    pkscript: String,
+    after: Option<u32>,
+    limit: Option<u32>,
+  ) -> RpcResult<PkscriptInscriptions> {
+    let pkscript = hex::decode(&pkscript).map_err(|err| wrap_rpc_error(Box::new(err)))?;
 
-      if !is_valid_sns(&inscription_info) {
-        iter.next();
-        continue;
-      }
+    self.inscriptions_by_pkscript(&pkscript, after, limit).await
+  }
 
-      let block_height = u32::from_be_bytes(iter.key().unwrap()[0..4].try_into().unwrap());
-      let tx_index = u32::from_be_bytes(iter.key().unwrap()[4..8].try_into().unwrap());
-      let tx_id = format!("{}:{}", block_height, tx_index);
+  async fn get_inscriptions_by_address(
+    &self,
+    address: String,
+    after: Option<u32>,
+    limit: Option<u32>,
+  ) -> RpcResult<PkscriptInscriptions> {
+    let address = address
+      .parse::<bitcoin::Address<_>>()
+      .map_err(|err| wrap_rpc_error(Box::new(err)))?
+      .require_network(self.network)
+      .map_err(|err| wrap_rpc_error(Box::new(err)))?;
+
+    self.inscriptions_by_pkscript(address.script_pubkey().as_bytes(), after, limit).await
+  }
 
-      sns_inscrs.push(SNSInscription {
-        tx_id,
-        inscription_id,
-        inscription_number: inscription_info.inscription_number,
-        txid: transfer_info.txid,
-        content_hex: inscription_info.content_hex.clone(),
-        content_type_hex: inscription_info.content_type_hex.clone(),
-      });
+  async fn get_inscription_transfers(
+    &self,
+    inscription_id: String,
+  ) -> RpcResult<Option<Vec<InscriptionTransfer>>> {
+    self
+      .read(|db| {
+        let inscription_id_to_sequence_number = db
+          .cf_handle("inscription_id_to_sequence_number")
+          .ok_or_else(|| {
+            wrap_rpc_error(Box::new(std::io::Error::new(
+              std::io::ErrorKind::NotFound,
+              "Column family 'inscription_id_to_sequence_number' not found",
+            )))
+          })?;
+        let sequence_number_to_transfers =
+          db.cf_handle("sequence_number_to_transfers").ok_or_else(|| {
+            wrap_rpc_error(Box::new(std::io::Error::new(
+              std::io::ErrorKind::NotFound,
+              "Column family 'sequence_number_to_transfers' not found",
+            )))
+          })?;
+        let ord_transfers = db.cf_handle("ord_transfers").ok_or_else(|| {
+          wrap_rpc_error(Box::new(std::io::Error::new(
+            std::io::ErrorKind::NotFound,
+            "Column family 'ord_transfers' not found",
+          )))
+        })?;
+
+        let Some(sequence_number) = db
+          .get_cf(
+            inscription_id_to_sequence_number,
+            get_inscription_id_key(&inscription_id),
+          )
+          .map_err(|err| wrap_rpc_error(Box::new(err)))?
+        else {
+          return Ok(None);
+        };
+
+        let mut transfers = Vec::new();
+
+        let mut iter = db.raw_iterator_cf(sequence_number_to_transfers);
+        iter.seek(&sequence_number);
+        while let Some(key) = iter.key() {
+          if !key.starts_with(&sequence_number) {
+            break;
+          }
+
+          let transfer_key = &key[4..];
+          let raw = db
+            .get_cf(ord_transfers, transfer_key)
+            .map_err(|err| wrap_rpc_error(Box::new(err)))?
+            .ok_or_else(|| {
+              wrap_rpc_error(format!("missing transfer {}", hex::encode(transfer_key)).into())
+            })?;
+          let transfer_info = get_transfer_info_from_raw(raw);
+
+          transfers.push(InscriptionTransfer {
+            block_height: u32::from_be_bytes(transfer_key[0..4].try_into().unwrap()),
+            transfer_index: u32::from_be_bytes(transfer_key[4..8].try_into().unwrap()),
+            txid: transfer_info.txid,
+            old_satpoint: transfer_info.old_satpoint,
+            new_satpoint: transfer_info.new_satpoint,
+            new_wallet: get_wallet(&transfer_info.new_pkscript, self.network),
+            new_pkscript: transfer_info.new_pkscript,
+            new_output_value: transfer_info.new_output_value,
+            sent_as_fee: transfer_info.sent_as_fee,
+          });
+
+          iter.next();
+        }
+
+        Ok(Some(transfers))
+      })
+      .await
+  }
+}
 
-      iter.next();
+#[async_trait]
+impl InscriptionEventsApiServer for RpcServer {
+  async fn subscribe_inscription_events(
+    &self,
+    pending: PendingSubscriptionSink,
+  ) -> SubscriptionResult {
+    let mut events = self.events.subscribe();
+    let sink = pending.accept().await?;
+
+    loop {
+      let event = tokio::select! {
+        _ = sink.closed() => return Ok(()),
+        event = events.recv() => match event {
+          Ok(event) => event,
+          Err(RecvError::Lagged(skipped)) => {
+            return Err(format!("subscriber fell behind and missed {skipped} events").into());
+          }
+          Err(RecvError::Closed) => return Ok(()),
+        },
+      };
+
+      if sink
+        .send(serde_json::value::to_raw_value(&event)?)
+        .await
+        .is_err()
+      {
+        return Ok(());
+      }
     }
+  }
+}
 
-    // sort sns_inscrs by inscription_number
-    sns_inscrs.sort_by(|a, b| a.inscription_number.cmp(&b.inscription_number));
+/// Requests `shutdown` on SIGINT or SIGTERM, when the RPC server runs on its
+/// own.
+async fn shut_down_on_signal(shutdown: Shutdown) {
+  let mut terminate = signal(SignalKind::terminate()).expect("Failed to create signal handler");
 
-    Ok(Some(sns_inscrs))
+  tokio::select! {
+    _ = tokio::signal::ctrl_c() => println!("Received SIGINT, stopping RPC server..."),
+    _ = terminate.recv() => println!("Received SIGTERM, stopping RPC server..."),
+  }
+
+  shutdown.request();
+}
+
+async fn shutdown_requested(shutdown: &Shutdown) {
+  while !shutdown.is_requested() {
+    tokio::time::sleep(Duration::from_millis(100)).await;
   }
 }
 
 pub async fn start_rpc_server(config: Config) -> Result<(), Box<dyn Error>> {
-  let mut signals = Signals::new([SIGINT]).expect("Failed to create signal handler");
+  let shutdown = config.shutdown.unwrap_or_else(|| {
+    let shutdown = Shutdown::default();
+    tokio::spawn(shut_down_on_signal(shutdown.clone()));
+    shutdown
+  });
 
   let index_path = if let Some(db_path) = config.db_path {
     db_path
@@ -820,27 +1490,33 @@ pub async fn start_rpc_server(config: Config) -> Result<(), Box<dyn Error>> {
     }
   };
 
-  let column_families = vec![
-    ColumnFamilyDescriptor::new("height_to_block_header", Options::default()),
-    ColumnFamilyDescriptor::new("inscription_id_to_sequence_number", Options::default()),
-    ColumnFamilyDescriptor::new("sequence_number_to_inscription_entry", Options::default()),
-    ColumnFamilyDescriptor::new("outpoint_to_utxo_entry", Options::default()),
-    ColumnFamilyDescriptor::new("ord_transfers", Options::default()),
-    ColumnFamilyDescriptor::new("ord_inscription_info", Options::default()),
-    ColumnFamilyDescriptor::new("ord_index_stats", Options::default()),
-  ];
-
-  let db_path = index_path.join("index.db");
-  let sec_db_path = index_path.join("secondary.db");
-  let db = Arc::new(Mutex::new(
-    DB::open_cf_descriptors_as_secondary(
-      &Options::default(),
-      &db_path,
-      &sec_db_path,
-      column_families,
-    )
-    .expect("Failed to open database"),
-  ));
+  let gate = config.reader_gate.unwrap_or_default();
+
+  // Opening the secondary instance waits for the primary database to be
+  // settled if it's being replaced
+  let (secondary_db, epoch) = tokio::task::spawn_blocking({
+    let gate = gate.clone();
+    let index_path = index_path.clone();
+    let rocksdb = config.rocksdb.clone();
+    move || {
+      let pass = gate.enter();
+      (open_secondary_db(&index_path, &rocksdb), pass.epoch())
+    }
+  })
+  .await?;
+  let secondary_db = secondary_db.expect("Failed to open database");
+  let (events, _) = broadcast::channel(65536);
+  let mut relay = EventRelay::new(
+    config.inscription_events,
+    latest_block_height(&secondary_db),
+    events.clone(),
+  );
+  let (secondary, _) = watch::channel(SecondaryDb {
+    db: Arc::new(secondary_db),
+    epoch,
+    generation: 0,
+    catching_up: false,
+  });
 
   let cors = CorsLayer::new()
         // Allow `POST` when accessing the resource
@@ -851,11 +1527,14 @@ pub async fn start_rpc_server(config: Config) -> Result<(), Box<dyn Error>> {
 
   let http_middleware = ServiceBuilder::new().layer(cors);
   let rpc_middleware = RpcServiceBuilder::new().rpc_logger(1024);
-  let module = RpcServer {
-    db: db.clone(),
+  let rpc_server = RpcServer {
+    events,
     network: config.network,
-  }
-  .into_rpc();
+    pending_transfers: config.pending_transfers,
+    secondary: secondary.subscribe(),
+  };
+  let mut module = Brc20ApiServer::into_rpc(rpc_server.clone());
+  module.merge(InscriptionEventsApiServer::into_rpc(rpc_server))?;
 
   let server_config = jsonrpsee::server::ServerConfig::builder()
         .max_request_body_size(1024 * 1024 * 100) // 100 MB
@@ -876,26 +1555,425 @@ pub async fn start_rpc_server(config: Config) -> Result<(), Box<dyn Error>> {
 
   println!("RPC server started at http://{}", url);
 
-  tokio::spawn(handle.stopped());
+  let mut catch_up = tokio::time::interval(Duration::from_secs(5));
+  catch_up.set_missed_tick_behavior(MissedTickBehavior::Delay);
+  catch_up.tick().await;
 
-  // The server will run indefinitely, handling requests.
-  // You can add more functionality or shutdown logic as needed.
-  // For now, we just keep the main function running.
   loop {
-    tokio::time::sleep(tokio::time::Duration::from_secs(5)).await;
+    tokio::select! {
+      _ = catch_up.tick() => {}
+      _ = shutdown_requested(&shutdown) => break,
+    }
 
-    if signals.pending().next().is_some() {
-      println!("Received SIGINT, stopping RPC server...");
-      break; // Exit the loop on SIGINT
+    let secondary = secondary.clone();
+    let gate = gate.clone();
+    let index_path = index_path.clone();
+    let rocksdb = config.rocksdb.clone();
+
+    // Catching up reads the primary's new WAL and SST files, which would
+    // stall a runtime worker
+    let caught_up = tokio::task::spawn_blocking(move || {
+      catch_up_with_primary(&secondary, &gate, &index_path, &rocksdb)?
+        .map_err(|e| eprintln!("Failed to catch up with primary: {}", e))
+        .ok()
+        .map(|db| latest_block_height(&db))
+    })
+    .await;
+
+    match caught_up {
+      Ok(Some(height)) => relay.relay(height),
+      Ok(None) => {}
+      Err(e) => eprintln!("Failed to catch up with primary: {}", e),
     }
+  }
 
-    db.lock()
-      .expect("DB Error")
-      .try_catch_up_with_primary()
-      .map_err(|e| eprintln!("Failed to catch up with primary: {}", e))
-      .ok();
+  // In-flight requests are answered, subscriptions are closed
+  if handle.stop().is_ok()
+    && tokio::time::timeout(Duration::from_secs(5), handle.stopped())
+      .await
+      .is_err()
+  {
+    eprintln!("RPC server did not stop within 5 seconds");
   }
 
   println!("RPC server stopped.");
   Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use bitcoin::hashes::Hash;
+  use bitcoin::{BlockHash, CompactTarget, TxMerkleNode};
+  use tempfile::TempDir;
+
+  const BRC20: &str = r#"{"p":"brc-20","op":"transfer","tick":"ordi","amt":"1"}"#;
+
+  fn inscription_id(n: u8) -> String {
+    format!("{}i0", hex::encode([n; 32]))
+  }
+
+  /// An `ord_inscription_info` record of a text inscription
+  fn inscription_info(number: i32, is_json: bool, content: &str) -> Vec<u8> {
+    let field = |data: &[u8]| {
+      [
+        vec![1],
+        u32::try_from(data.len()).unwrap().to_be_bytes().to_vec(),
+        data.to_vec(),
+      ]
+      .concat()
+    };
+
+    [
+      vec![2],
+      number.to_be_bytes().to_vec(),
+      vec![0, is_json.into()],
+      0u32.to_be_bytes().to_vec(),
+      field(content.as_bytes()),
+      field(b"text/plain"),
+      // metaprotocol, content encoding, metadata, delegate, pointer and
+      // decoded content
+      vec![0; 6],
+    ]
+    .concat()
+  }
+
+  fn inscription_entry(n: u8, sequence_number: u32) -> Vec<u8> {
+    [
+      0u16.to_be_bytes().to_vec(),
+      get_inscription_id_key(&inscription_id(n)),
+      i32::from(n).to_be_bytes().to_vec(),
+      sequence_number.to_be_bytes().to_vec(),
+      vec![1],
+      2i16.to_be_bytes().to_vec(),
+      vec![0],
+    ]
+    .concat()
+  }
+
+  fn header(height: u32) -> bitcoin::block::Header {
+    bitcoin::block::Header {
+      version: bitcoin::block::Version::ONE,
+      prev_blockhash: BlockHash::all_zeros(),
+      merkle_root: TxMerkleNode::all_zeros(),
+      time: 1_000_000 + height,
+      bits: CompactTarget::from_consensus(0x207fffff),
+      nonce: height,
+    }
+  }
+
+  /// Serves an index of `blocks` blocks, in which blocks 1 to 3 each transfer
+  /// a BRC-20 inscription, a plain text inscription and the BRC-20 one again.
+  fn rpc_server(blocks: u32) -> (TempDir, RpcServer) {
+    let tempdir = TempDir::new().unwrap();
+    let index_path = tempdir.path().to_path_buf();
+    let rocksdb = RocksDbSettings::default();
+
+    {
+      let mut options = rocksdb.db_options();
+      options.create_if_missing(true);
+      options.create_missing_column_families(true);
+
+      let db = DB::open_cf_descriptors(
+        &options,
+        index_path.join("index.db"),
+        rocksdb.column_family_descriptors(COLUMN_FAMILIES),
+      )
+      .unwrap();
+
+      let cf = |name| db.cf_handle(name).unwrap();
+
+      for height in 0..blocks {
+        db.put_cf(
+          cf("height_to_block_header"),
+          height.to_be_bytes(),
+          bitcoin::consensus::encode::serialize(&header(height)),
+        )
+        .unwrap();
+      }
+
+      for (n, is_json, content) in [(1, true, BRC20), (2, false, "hello")] {
+        let key = get_inscription_id_key(&inscription_id(n));
+        let sequence_number = u32::from(n) - 1;
+
+        db.put_cf(
+          cf("ord_inscription_info"),
+          &key,
+          inscription_info(n.into(), is_json, content),
+        )
+        .unwrap();
+        db.put_cf(
+          cf("inscription_id_to_sequence_number"),
+          &key,
+          sequence_number.to_be_bytes(),
+        )
+        .unwrap();
+        db.put_cf(
+          cf("sequence_number_to_inscription_entry"),
+          sequence_number.to_be_bytes(),
+          inscription_entry(n, sequence_number),
+        )
+        .unwrap();
+      }
+
+      for height in 1..=3u32 {
+        for (transfer_index, n) in [(1u32, 1), (2, 2), (3, 1)] {
+          let new_satpoint = [vec![u8::try_from(height).unwrap(); 32], vec![0; 12]].concat();
+
+          db.put_cf(
+            cf("ord_transfers"),
+            [height.to_be_bytes(), transfer_index.to_be_bytes()].concat(),
+            [
+              get_inscription_id_key(&inscription_id(n)),
+              vec![0; 44],
+              new_satpoint,
+              vec![0],
+              546u64.to_be_bytes().to_vec(),
+              vec![u8::try_from(height).unwrap(); 32],
+            ]
+            .concat(),
+          )
+          .unwrap();
+        }
+      }
+    }
+
+    let server = RpcServer {
+      events: broadcast::channel(1).0,
+      network: Regtest,
+      pending_transfers: None,
+      secondary: watch::channel(SecondaryDb {
+        db: Arc::new(open_secondary_db(&index_path, &rocksdb).unwrap()),
+        epoch: 0,
+        generation: 0,
+        catching_up: false,
+      })
+      .1,
+    };
+
+    (tempdir, server)
+  }
+
+  fn tx_ids(page: &BRC20TxPage) -> Vec<&str> {
+    page.txes.iter().map(|tx| tx.tx_id.as_str()).collect()
+  }
+
+  #[tokio::test]
+  async fn block_range_brc20_txes_are_paged() {
+    let (_tempdir, server) = rpc_server(5);
+
+    let page = server
+      .get_block_range_brc20_txes(1, 3, None, None)
+      .await
+      .unwrap();
+    assert_eq!(tx_ids(&page), ["1:1", "1:3", "2:1", "2:3", "3:1", "3:3"]);
+    assert_eq!(page.next, None);
+    assert_eq!(page.txes[0].inscription_id, inscription_id(1));
+    assert_eq!(
+      page.txes[0].content,
+      serde_json::from_str::<serde_json::Value>(BRC20).unwrap()
+    );
+
+    let page = server
+      .get_block_range_brc20_txes(1, 3, None, Some(3))
+      .await
+      .unwrap();
+    assert_eq!(tx_ids(&page), ["1:1", "1:3", "2:1"]);
+    assert_eq!(page.next.as_deref(), Some("2:1"));
+
+    let page = server
+      .get_block_range_brc20_txes(1, 3, page.next, Some(3))
+      .await
+      .unwrap();
+    assert_eq!(tx_ids(&page), ["2:3", "3:1", "3:3"]);
+    assert_eq!(page.next, None);
+
+    let page = server
+      .get_block_range_brc20_txes(2, 2, None, None)
+      .await
+      .unwrap();
+    assert_eq!(tx_ids(&page), ["2:1", "2:3"]);
+    assert_eq!(
+      tx_ids(&page),
+      server
+        .get_block_brc20_txes(2)
+        .await
+        .unwrap()
+        .unwrap()
+        .iter()
+        .map(|tx| tx.tx_id.as_str())
+        .collect::<Vec<&str>>(),
+    );
+  }
+
+  #[tokio::test]
+  async fn ranges_must_be_indexed() {
+    let (_tempdir, server) = rpc_server(5);
+
+    assert_eq!(
+      server
+        .get_block_range_brc20_txes(3, 2, None, None)
+        .await
+        .err()
+        .unwrap()
+        .message(),
+      "from_height 3 is above to_height 2",
+    );
+
+    assert_eq!(
+      server
+        .get_block_hashes_and_ts(0, 5)
+        .await
+        .err()
+        .unwrap()
+        .message(),
+      "block 5 is not indexed yet, the latest is 4",
+    );
+
+    assert_eq!(
+      server
+        .get_block_range_brc20_txes(1, 4, Some("1".into()), None)
+        .await
+        .err()
+        .unwrap()
+        .message(),
+      "invalid tx_id `1`",
+    );
+
+    let (_tempdir, server) = rpc_server(0);
+
+    assert_eq!(
+      server
+        .get_block_hashes_and_ts(0, 0)
+        .await
+        .err()
+        .unwrap()
+        .message(),
+      "no blocks are indexed yet",
+    );
+  }
+
+  #[tokio::test]
+  async fn block_hashes_are_paged() {
+    let (_tempdir, server) = rpc_server(MAX_BLOCK_INFO_PAGE + 2);
+
+    let page = server
+      .get_block_hashes_and_ts(1, MAX_BLOCK_INFO_PAGE + 1)
+      .await
+      .unwrap();
+    assert_eq!(
+      page.blocks.len(),
+      usize::try_from(MAX_BLOCK_INFO_PAGE).unwrap()
+    );
+    assert_eq!(page.next, Some(MAX_BLOCK_INFO_PAGE + 1));
+    assert_eq!(
+      page.blocks[0].block_hash,
+      header(1).block_hash().to_string()
+    );
+    assert_eq!(page.blocks[0].timestamp, 1_000_001);
+
+    let page = server
+      .get_block_hashes_and_ts(page.next.unwrap(), MAX_BLOCK_INFO_PAGE + 1)
+      .await
+      .unwrap();
+    assert_eq!(page.next, None);
+    assert_eq!(
+      page
+        .blocks
+        .iter()
+        .map(|block| &block.block_hash)
+        .collect::<Vec<&String>>(),
+      [&server
+        .get_block_hash_and_ts(MAX_BLOCK_INFO_PAGE + 1)
+        .await
+        .unwrap()
+        .unwrap()
+        .block_hash],
+    );
+  }
+
+  #[tokio::test]
+  async fn inscription_infos_are_returned_in_order() {
+    let (_tempdir, server) = rpc_server(5);
+
+    let infos = server
+      .get_inscription_infos(vec![
+        inscription_id(2),
+        inscription_id(3),
+        inscription_id(1),
+      ])
+      .await
+      .unwrap();
+
+    assert_eq!(infos.len(), 3);
+
+    let info = infos[0].as_ref().unwrap();
+    assert_eq!(info.entry.id, inscription_id(2));
+    assert!(!info.info.as_ref().unwrap().is_json);
+
+    assert!(infos[1].is_none());
+
+    let info = infos[2].as_ref().unwrap();
+    assert_eq!(info.entry.id, inscription_id(1));
+    assert_eq!(info.entry.sequence_number, 0);
+    assert!(info.info.as_ref().unwrap().is_json);
+
+    assert_eq!(
+      server
+        .get_inscription_infos(vec![inscription_id(1); MAX_INSCRIPTION_INFOS + 1])
+        .await
+        .err()
+        .unwrap()
+        .message(),
+      format!("at most {MAX_INSCRIPTION_INFOS} inscriptions can be requested at once"),
+    );
+  }
+
+  #[test]
+  fn inscription_info_from_newer_version_is_rejected() {
+    let mut raw = inscription_info(1, true, BRC20);
+
+    assert_eq!(raw[0], INSCRIPTION_INFO_VERSION);
+
+    raw[0] = INSCRIPTION_INFO_VERSION + 1;
+
+    assert_eq!(
+      get_inscription_info_from_raw(raw, inscription_id(1))
+        .err()
+        .unwrap()
+        .message(),
+      format!(
+        "unknown ord_inscription_info record version {}",
+        INSCRIPTION_INFO_VERSION + 1
+      ),
+    );
+  }
+
+  #[tokio::test]
+  async fn reads_that_overlap_a_catch_up_run_again() {
+    let (_tempdir, server) = rpc_server(5);
+
+    let (secondary, receiver) = watch::channel(server.secondary.borrow().clone());
+
+    let server = RpcServer {
+      secondary: receiver,
+      ..server
+    };
+
+    let runs = std::sync::atomic::AtomicU32::new(0);
+
+    let height = server
+      .read(|db| {
+        if runs.fetch_add(1, std::sync::atomic::Ordering::Relaxed) == 0 {
+          secondary.send_modify(|secondary| secondary.generation += 1);
+        }
+
+        Ok(latest_block_height(db))
+      })
+      .await
+      .unwrap();
+
+    assert_eq!(height, Some(4));
+    assert_eq!(runs.into_inner(), 2);
+  }
+}
@@ -0,0 +1,22 @@
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Tells the RPC server to stop.
+///
+/// The indexer requests shutdown once it has committed its last block, so the
+/// server outlives every write. Without one, the server stops on SIGINT or
+/// SIGTERM.
+#[derive(Clone, Default)]
+pub struct Shutdown {
+  requested: Arc<AtomicBool>,
+}
+
+impl Shutdown {
+  pub fn request(&self) {
+    self.requested.store(true, Ordering::Relaxed);
+  }
+
+  pub fn is_requested(&self) -> bool {
+    self.requested.load(Ordering::Relaxed)
+  }
+}
@@ -1,36 +1,57 @@
 use {
   self::{
-    entry::{Entry, InscriptionEntry, SatRange},
+    block_batch::BlockBatch,
+    block_notifier::BlockNotifier,
+    block_source::{BlockSource, Rpc},
+    entry::{Entry, InscriptionEntry, InscriptionInfoEntry, SatRange},
     event::Event,
+    mempool::Mempool,
+    metrics::Metrics,
     reorg::Reorg,
+    undo_log::UndoLog,
     updater::Updater,
     utxo_entry::{ParsedUtxoEntry, UtxoEntryBuf},
   },
   super::*,
   bitcoin::block::Header,
   bitcoincore_rpc::Client,
-  db_reader::{start_rpc_server, Config},
+  db_reader::{
+    start_rpc_server, Config, Curse, InscriptionEvent, ReaderGate, RocksDbSettings, Shutdown,
+    INDEX_COLUMN_FAMILIES,
+  },
   indicatif::{ProgressBar, ProgressStyle},
   log::log_enabled,
   rocksdb::{
     backup::{BackupEngine, BackupEngineOptions},
-    ColumnFamilyDescriptor, IteratorMode, Options, DB,
+    IteratorMode, Options, DB,
   },
-  std::collections::HashMap,
+  std::{collections::HashMap, net::SocketAddr, ops::Deref, sync::Arc},
   tokio::runtime::Runtime,
 };
 
-pub use updater::get_tx_limits;
-
+mod block_batch;
+mod block_notifier;
+pub(crate) mod block_source;
 pub(crate) mod entry;
 pub mod event;
 mod fetcher;
 mod lot;
+mod mempool;
+mod metrics;
+pub(crate) mod migration;
 pub(crate) mod reorg;
+pub(crate) mod snapshot;
+#[cfg(test)]
+pub(crate) mod testing;
+mod undo_log;
 mod updater;
 mod utxo_entry;
+pub(crate) mod verify;
 
-const SCHEMA_VERSION: u64 = 99100030;
+const SCHEMA_VERSION: u64 = 99100034;
+
+/// Kept in db_reader, which validates RocksDB settings against them
+const COLUMN_FAMILIES: &[&str] = INDEX_COLUMN_FAMILIES;
 
 #[derive(Copy, Clone)]
 pub(crate) enum Statistic {
@@ -38,6 +59,13 @@ pub(crate) enum Statistic {
   BlessedInscriptions = 1,
   CursedInscriptions = 3,
   LastSavepointHeight = 17,
+  TxLimits = 18,
+  InscriptionFilters = 19,
+  CommittedHeight = 20,
+  IndexPrevouts = 21,
+  IndexAddresses = 22,
+  MigrationProgress = 23,
+  DecodedContentProtocols = 24,
 }
 
 impl Statistic {
@@ -122,17 +150,122 @@ impl<T> BitcoinCoreRpcResultExt<T> for Result<T, bitcoincore_rpc::Error> {
   }
 }
 
+/// The primary RocksDB instance. It can be closed and reopened in place so
+/// that a savepoint can be restored without dropping the `Index`.
+pub(crate) struct Database {
+  db: Option<DB>,
+  path: PathBuf,
+  rocksdb: RocksDbSettings,
+}
+
+impl Database {
+  fn open(path: PathBuf, rocksdb: &RocksDbSettings) -> Result<Self> {
+    let mut database = Self {
+      db: None,
+      path,
+      rocksdb: rocksdb.clone(),
+    };
+    database.reopen()?;
+    Ok(database)
+  }
+
+  pub(crate) fn close(&mut self) {
+    self.db = None;
+  }
+
+  pub(crate) fn reopen(&mut self) -> Result {
+    self.close();
+
+    let mut opts = self.rocksdb.db_options();
+    opts.create_if_missing(true);
+    // Writes skip the WAL, so column families must be flushed together to
+    // stay consistent with each other
+    opts.set_atomic_flush(true);
+    opts.create_missing_column_families(true);
+    opts.enable_statistics();
+
+    let column_families = self.rocksdb.column_family_descriptors(COLUMN_FAMILIES);
+
+    self.db = Some(DB::open_cf_descriptors(&opts, &self.path, column_families)?);
+
+    Ok(())
+  }
+
+  fn flush_all(&self) -> Result {
+    let mut flush_opts = rocksdb::FlushOptions::default();
+    flush_opts.set_wait(true);
+
+    let cfs = COLUMN_FAMILIES
+      .iter()
+      .map(|name| {
+        self
+          .cf_handle(name)
+          .ok_or_else(|| anyhow!("Failed to open column family '{name}'"))
+      })
+      .collect::<Result<Vec<_>>>()?;
+
+    self.flush_cfs_opt(&cfs, &flush_opts)?;
+
+    Ok(())
+  }
+}
+
+impl Deref for Database {
+  type Target = DB;
+
+  fn deref(&self) -> &DB {
+    self.db.as_ref().expect("database is closed")
+  }
+}
+
+/// Blocks indexed after the last commit when the index was shut down. `ord`
+/// exits with `EXIT_CODE` on it, so whatever restarts the indexer can tell
+/// that it has blocks to roll back from other failures.
+#[derive(Debug, PartialEq)]
+pub(crate) struct UncommittedBlocks {
+  path: PathBuf,
+  committed_height: u32,
+  block_count: u32,
+}
+
+impl UncommittedBlocks {
+  pub(crate) const EXIT_CODE: i32 = 3;
+}
+
+impl Display for UncommittedBlocks {
+  fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+    write!(
+      f,
+      "index at {} was shut down with uncommitted blocks {}..{}, they will be rolled back when it is next opened",
+      self.path.display(),
+      self.committed_height,
+      self.block_count - 1,
+    )
+  }
+}
+
+impl std::error::Error for UncommittedBlocks {}
+
 pub struct Index {
-  pub(crate) client: Client,
-  db: DB,
+  block_notifier: Option<Arc<BlockNotifier>>,
+  block_source: Box<dyn BlockSource>,
+  db: Database,
   event_sender: Option<tokio::sync::mpsc::Sender<Event>>,
+  inscription_events: std::sync::mpsc::Sender<InscriptionEvent>,
   height_limit: Option<u32>,
   settings: Settings,
   first_index_height: u32,
+  index_addresses: bool,
+  inscription_filters: Vec<Box<dyn InscriptionFilter>>,
+  mempool: Option<Mutex<Mempool>>,
+  metrics: Arc<Metrics>,
   unrecoverably_reorged: AtomicBool,
   write_options: rocksdb::WriteOptions,
   pub(crate) path: PathBuf,
-  _runtime: Runtime,
+  reader_gate: ReaderGate,
+  rpc_server: Option<tokio::task::JoinHandle<()>>,
+  rpc_shutdown: Shutdown,
+  runtime: Runtime,
 }
 
 impl Index {
@@ -144,8 +277,16 @@ impl Index {
     settings: &Settings,
     event_sender: Option<tokio::sync::mpsc::Sender<Event>>,
   ) -> Result<Self> {
-    let client = settings.bitcoin_rpc_client(None)?;
+    Index::open_with_block_source(settings, event_sender, Box::new(Rpc::new(settings)?))
+  }
 
+  /// Opens the index to be updated from `block_source`. Mempool and ZMQ
+  /// watching are only enabled for live sources.
+  pub(crate) fn open_with_block_source(
+    settings: &Settings,
+    event_sender: Option<tokio::sync::mpsc::Sender<Event>>,
+    block_source: Box<dyn BlockSource>,
+  ) -> Result<Self> {
     let path = settings.index().to_owned();
 
     let data_dir = path.parent().unwrap();
@@ -158,29 +299,20 @@ impl Index {
 
     let index_path = path.clone();
 
-    let mut opts = Options::default();
-    opts.create_if_missing(true);
-    opts.set_max_open_files(256);
-    opts.set_write_buffer_size(8192 * 1024 * 1024); // 8 GiB
-    opts.set_atomic_flush(true);
-    opts.create_missing_column_families(true);
-    opts.enable_statistics();
-
-    let mut cf_opts = Options::default();
-    cf_opts.set_write_buffer_size(8192 * 1024 * 1024); // 8 GiB
-
     let write_options = {
       let mut write_options = rocksdb::WriteOptions::default();
       write_options.disable_wal(true);
       write_options
     };
 
+    let nofile_limit = settings.rocksdb().nofile_limit;
+
     match rlimit::Resource::NOFILE.set(
-      option_env!("NOFILE_SOFT_LIMIT")
-        .and_then(|s| s.parse().ok())
+      nofile_limit
+        .or_else(|| option_env!("NOFILE_SOFT_LIMIT").and_then(|s| s.parse().ok()))
         .unwrap_or(1048576),
-      option_env!("NOFILE_HARD_LIMIT")
-        .and_then(|s| s.parse().ok())
+      nofile_limit
+        .or_else(|| option_env!("NOFILE_HARD_LIMIT").and_then(|s| s.parse().ok()))
         .unwrap_or(1048576),
     ) {
       Ok(_) => (),
@@ -189,28 +321,42 @@ impl Index {
       }
     }
 
-    let column_families = vec![
-      ColumnFamilyDescriptor::new("height_to_block_header", cf_opts.clone()),
-      ColumnFamilyDescriptor::new("height_to_last_sequence_number", cf_opts.clone()),
-      ColumnFamilyDescriptor::new("outpoint_to_utxo_entry", cf_opts.clone()),
-      ColumnFamilyDescriptor::new("inscription_id_to_sequence_number", cf_opts.clone()),
-      ColumnFamilyDescriptor::new("inscription_number_to_sequence_number", cf_opts.clone()),
-      ColumnFamilyDescriptor::new("inscription_id_to_txcnt", cf_opts.clone()),
-      ColumnFamilyDescriptor::new("sequence_number_to_inscription_entry", cf_opts.clone()),
-      ColumnFamilyDescriptor::new("statistic_to_count", cf_opts.clone()),
-      ColumnFamilyDescriptor::new("ord_transfers", cf_opts.clone()),
-      ColumnFamilyDescriptor::new("ord_inscription_info", cf_opts.clone()),
-      ColumnFamilyDescriptor::new("ord_index_stats", cf_opts.clone()),
-    ];
-
-    let db_path = path.join("index.db");
-    let db = DB::open_cf_descriptors(&opts, &db_path, column_families)?;
+    let db = Database::open(path.join("index.db"), settings.rocksdb())?;
     let statistic_to_count = db
       .cf_handle("statistic_to_count")
       .ok_or_else(|| anyhow!("Failed to open column family 'statistic_to_count'"))?;
 
-    let schema_version = db.get_cf(statistic_to_count, &Statistic::Schema.key().to_be_bytes());
-    if schema_version.is_err() || schema_version.as_ref().unwrap().is_none() {
+    let schema_version = db
+      .get_cf(statistic_to_count, Statistic::Schema.key().to_be_bytes())
+      .ok()
+      .flatten()
+      .map(|schema_version| u64::from_be_bytes(schema_version.try_into().unwrap()));
+
+    if let Some(schema_version) = schema_version {
+      println!(
+        "Index schema version {} at {}",
+        schema_version,
+        index_path.display()
+      );
+
+      if schema_version != SCHEMA_VERSION {
+        migration::migrate(&db, &index_path, schema_version, false)?;
+      }
+
+      Self::check_recorded_setting(&db, Statistic::TxLimits, "transfer limits", settings.tx_limits())?;
+      Self::check_recorded_setting(
+        &db,
+        Statistic::InscriptionFilters,
+        "inscription filters",
+        &settings.inscription_filters().to_vec(),
+      )?;
+      Self::check_recorded_setting(
+        &db,
+        Statistic::DecodedContentProtocols,
+        "decoded content protocols",
+        settings.decoded_content_protocols(),
+      )?;
+    } else {
       println!(
         "Initializing index schema version {} at {}",
         SCHEMA_VERSION,
@@ -220,34 +366,98 @@ impl Index {
       // If the schema version is not set, we need to initialize it.
       db.put_cf_opt(
         statistic_to_count,
-        &Statistic::Schema.key().to_be_bytes(),
-        &SCHEMA_VERSION.to_be_bytes(),
+        Statistic::Schema.key().to_be_bytes(),
+        SCHEMA_VERSION.to_be_bytes(),
+        &write_options,
+      )?;
+      // Transfer limits, inscription filters and decoded content protocols
+      // decide what gets written for every inscription, so record the ones
+      // the index is built with
+      db.put_cf_opt(
+        statistic_to_count,
+        Statistic::TxLimits.key().to_be_bytes(),
+        serde_json::to_vec(settings.tx_limits())?,
+        &write_options,
+      )?;
+      db.put_cf_opt(
+        statistic_to_count,
+        Statistic::InscriptionFilters.key().to_be_bytes(),
+        serde_json::to_vec(settings.inscription_filters())?,
+        &write_options,
+      )?;
+      db.put_cf_opt(
+        statistic_to_count,
+        Statistic::DecodedContentProtocols.key().to_be_bytes(),
+        serde_json::to_vec(settings.decoded_content_protocols())?,
+        &write_options,
+      )?;
+      db.put_cf_opt(
+        statistic_to_count,
+        Statistic::IndexPrevouts.key().to_be_bytes(),
+        u64::from(settings.index_prevouts()).to_be_bytes(),
+        &write_options,
+      )?;
+      db.put_cf_opt(
+        statistic_to_count,
+        Statistic::IndexAddresses.key().to_be_bytes(),
+        u64::from(settings.index_addresses()).to_be_bytes(),
         &write_options,
       )?;
       db.flush()?;
-    } else {
-      let schema_version = u64::from_be_bytes(schema_version.unwrap().unwrap().try_into().unwrap());
-      println!(
-        "Index schema version {} at {}",
-        schema_version,
+    }
+
+    let inscription_filters = settings
+      .inscription_filters()
+      .iter()
+      .map(InscriptionFilterKind::build)
+      .collect();
+
+    // Outputs created before the first inscription height can't be added to
+    // an existing index, so only an index created with them has them all
+    let index_prevouts = db
+      .get_cf(statistic_to_count, Statistic::IndexPrevouts.key().to_be_bytes())?
+      .is_some_and(|index_prevouts| u64::from_be_bytes(index_prevouts.try_into().unwrap()) != 0);
+
+    if settings.index_prevouts() && !index_prevouts {
+      log::warn!(
+        "Index at {} was created without --index-prevouts, inputs spending older outputs are fetched from bitcoind",
         index_path.display()
       );
+    }
 
-      if schema_version != SCHEMA_VERSION {
-        bail!(
-          "Incompatible index schema version: expected {}, found {}",
-          SCHEMA_VERSION,
-          schema_version
-        );
-      }
+    // Inscriptions already transferred can't be added to the script pubkey
+    // index later, so it is only kept if the index was created with it
+    let index_addresses = db
+      .get_cf(statistic_to_count, Statistic::IndexAddresses.key().to_be_bytes())?
+      .is_some_and(|index_addresses| u64::from_be_bytes(index_addresses.try_into().unwrap()) != 0);
+
+    if settings.index_addresses() && !index_addresses {
+      log::warn!(
+        "Index at {} was created without --index-addresses, inscriptions are not indexed by script pubkey",
+        index_path.display()
+      );
     }
 
-    let first_index_height = settings.first_inscription_height();
+    let first_index_height = if index_prevouts {
+      0
+    } else {
+      settings.first_inscription_height()
+    };
 
     let chain = settings.chain();
     let db_path = path.clone();
+    let reader_gate = ReaderGate::default();
+    let rpc_reader_gate = reader_gate.clone();
+    let (inscription_events, rpc_inscription_events) = std::sync::mpsc::channel();
+    let rocksdb_settings = settings.rocksdb().clone();
+    let mempool = (settings.watch_mempool() && block_source.live())
+      .then(|| Mempool::new(settings))
+      .transpose()?;
+    let pending_transfers = mempool.as_ref().map(Mempool::pending_transfers);
+    let rpc_shutdown = Shutdown::default();
+    let rpc_server_shutdown = rpc_shutdown.clone();
     let runtime = Runtime::new()?;
-    runtime.spawn(async move {
+    let rpc_server = runtime.spawn(async move {
       println!("Starting RPC server for index at {}", db_path.display());
       start_rpc_server(Config {
         network: match chain {
@@ -259,23 +469,190 @@ impl Index {
         },
         db_path: Some(db_path.canonicalize().unwrap()),
         api_url: std::env::var("DB_READER_API_URL").ok(),
+        reader_gate: Some(rpc_reader_gate),
+        inscription_events: Some(rpc_inscription_events),
+        pending_transfers,
+        rocksdb: rocksdb_settings,
+        shutdown: Some(rpc_server_shutdown),
       })
       .await
       .unwrap()
     });
 
-    Ok(Self {
-      client,
+    let metrics = Arc::new(Metrics::default());
+
+    if let Some(port) = settings.metrics_port() {
+      let metrics = metrics.clone();
+      let address = SocketAddr::new(settings.metrics_address(), port);
+      runtime.spawn(async move {
+        if let Err(err) = metrics.serve(address).await {
+          log::error!("Metrics server failed: {err}");
+        }
+      });
+    }
+
+    let block_notifier = settings
+      .bitcoin_zmq_hashblock()
+      .filter(|_| block_source.live())
+      .map(BlockNotifier::subscribe)
+      .transpose()?;
+
+    let index = Self {
+      block_notifier,
+      block_source,
       db,
       event_sender,
+      inscription_events,
       first_index_height,
       height_limit: settings.height_limit(),
+      index_addresses,
+      inscription_filters,
+      mempool: mempool.map(Mutex::new),
+      metrics,
       settings: settings.clone(),
       unrecoverably_reorged: AtomicBool::new(false),
       path,
       write_options,
-      _runtime: runtime,
-    })
+      reader_gate,
+      rpc_server: Some(rpc_server),
+      rpc_shutdown,
+      runtime,
+    };
+
+    index.roll_back_uncommitted_blocks()?;
+
+    Ok(index)
+  }
+
+  /// Rolls back blocks indexed after the last commit. Their outputs are only
+  /// written on commit, so if the index was not shut down cleanly they may
+  /// have been persisted without them.
+  fn roll_back_uncommitted_blocks(&self) -> Result {
+    let committed_height = self.committed_height()?;
+
+    let block_count = self.block_count()?;
+
+    if block_count <= committed_height {
+      return Ok(());
+    }
+
+    println!(
+      "Index has uncommitted blocks {}..{}, rolling them back",
+      committed_height,
+      block_count - 1,
+    );
+
+    UndoLog::rollback(self, committed_height..block_count).with_context(|| {
+      format!(
+        "failed to roll back to committed height {committed_height}, the index at {} must be rebuilt",
+        self.path.display()
+      )
+    })?;
+
+    self.emit_rollback()?;
+
+    println!("successfully rolled back uncommitted blocks");
+
+    Ok(())
+  }
+
+  /// Height up to which blocks have been committed, exclusive.
+  fn committed_height(&self) -> Result<u32> {
+    let statistic_to_count = self
+      .db
+      .cf_handle("statistic_to_count")
+      .ok_or_else(|| anyhow!("Failed to open column family 'statistic_to_count'"))?;
+
+    Ok(
+      self
+        .db
+        .get_cf(statistic_to_count, Statistic::CommittedHeight.key().to_be_bytes())?
+        .map(|height| u32::from_be_bytes(height.try_into().unwrap()))
+        .unwrap_or(0),
+    )
+  }
+
+  /// Stops db_reader's RPC server, flushes every column family, and fails
+  /// with `UncommittedBlocks` if blocks were indexed after the last commit, in
+  /// which case they are rolled back when the index is next opened.
+  pub fn shut_down(mut self) -> Result {
+    self.rpc_shutdown.request();
+
+    if let Some(rpc_server) = self.rpc_server.take() {
+      if let Err(err) = self.runtime.block_on(rpc_server) {
+        log::warn!("RPC server failed: {err}");
+      }
+    }
+
+    self.flush()?;
+
+    let committed_height = self.committed_height()?;
+    let block_count = self.block_count()?;
+
+    if block_count > committed_height {
+      return Err(anyhow!(UncommittedBlocks {
+        path: self.path.clone(),
+        committed_height,
+        block_count,
+      }));
+    }
+
+    Ok(())
+  }
+
+  /// Fails if `configured` differs from the value recorded under `statistic`
+  /// when the index was created. A missing record matches the default.
+  fn check_recorded_setting<T>(db: &DB, statistic: Statistic, name: &str, configured: &T) -> Result
+  where
+    T: Serialize + for<'de> Deserialize<'de> + Default + PartialEq,
+  {
+    let statistic_to_count = db
+      .cf_handle("statistic_to_count")
+      .ok_or_else(|| anyhow!("Failed to open column family 'statistic_to_count'"))?;
+
+    let recorded = db
+      .get_cf(statistic_to_count, statistic.key().to_be_bytes())?
+      .map(|recorded| serde_json::from_slice::<T>(&recorded))
+      .transpose()?
+      .unwrap_or_default();
+
+    if &recorded != configured {
+      bail!(
+        "Index was built with {name} {} but {} are configured",
+        serde_json::to_string(&recorded)?,
+        serde_json::to_string(configured)?,
+      );
+    }
+
+    Ok(())
+  }
+
+  /// Sends `event` to the event sender the index was opened with, and to
+  /// db_reader for its event subscribers.
+  pub(crate) fn emit(&self, event: Event) -> Result {
+    if let Some(inscription_event) = event.to_inscription_event() {
+      // Fails only if the RPC server has stopped
+      self.inscription_events.send(inscription_event).ok();
+    }
+
+    if let Some(sender) = &self.event_sender {
+      sender.blocking_send(event)?;
+    }
+
+    Ok(())
+  }
+
+  /// Tells db_reader subscribers that blocks above the current tip were
+  /// rolled back.
+  pub(crate) fn emit_rollback(&self) -> Result {
+    let block_height = self.block_count()?.saturating_sub(1);
+
+    self
+      .inscription_events
+      .send(InscriptionEvent::Rollback { block_height })
+      .ok();
+
+    Ok(())
   }
 
   pub fn have_full_utxo_index(&self) -> bool {
@@ -291,56 +668,97 @@ impl Index {
     outpoint == OutPoint::null() || outpoint == unbound_outpoint()
   }
 
-  pub fn update(&self) -> Result {
+  /// Flushes the memtables of all column families. Writes skip the WAL, so
+  /// nothing is durable until this returns.
+  pub(crate) fn flush(&self) -> Result {
+    self.db.flush_all()
+  }
+
+  pub fn update(&mut self) -> Result {
     loop {
       if SHUTTING_DOWN.load(atomic::Ordering::Relaxed) {
         return Ok(());
       }
 
-      let height_to_block_header = self
-        .db
-        .cf_handle("height_to_block_header")
-        .ok_or_else(|| anyhow!("Failed to open column family 'height_to_block_header'"))?;
+      self.update_to_tip()?;
 
-      let blocks_indexed = self
-        .db
-        .iterator_cf(height_to_block_header, IteratorMode::End)
-        .next()
-        .transpose()?
-        .map(|(height, _header)| u32::from_be_bytes((*height).try_into().unwrap()) + 1)
-        .unwrap_or(0);
+      if !self.block_source.live() {
+        return Ok(());
+      }
+
+      if let Some(mempool) = &self.mempool {
+        if let Err(err) = mempool.lock().unwrap().refresh(self) {
+          log::warn!("Failed to refresh pending transfers: {err}");
+        }
+      }
+
+      self.wait_for_block();
+    }
+  }
 
+  /// Indexes blocks up to the tip, rolling back recoverable reorgs on the
+  /// way.
+  pub(crate) fn update_to_tip(&mut self) -> Result {
+    loop {
       let mut updater = Updater {
-        height: blocks_indexed,
+        height: self.block_count()?,
         index: self,
         outputs_cached: 0,
         outputs_traversed: 0,
         sat_ranges_since_flush: 0,
       };
 
-      match updater.update_index() {
-        Ok(_ok) => {
-          thread::sleep(Duration::from_secs(5));
-        }
-        Err(err) => {
-          log::info!("{err}");
-
-          match err.downcast_ref() {
-            Some(&reorg::Error::Recoverable {
-              height: _,
-              depth: _,
-            }) => {
-              return Err(err); // Reorg::handle_reorg(self, height, depth)?;
-            }
-            Some(&reorg::Error::Unrecoverable) => {
+      let Err(err) = updater.update_index() else {
+        return Ok(());
+      };
+
+      log::info!("{err}");
+
+      match err.downcast_ref() {
+        Some(&reorg::Error::Recoverable { height, depth }) => {
+          if let Err(err) = Reorg::handle_reorg(self, height, depth) {
+            if let Some(reorg::Error::Unrecoverable) = err.downcast_ref() {
               self
                 .unrecoverably_reorged
                 .store(true, atomic::Ordering::Relaxed);
-              return Err(anyhow!(reorg::Error::Unrecoverable));
             }
-            _ => return Err(err),
-          };
+            return Err(err);
+          }
+
+          if let Some(mempool) = &self.mempool {
+            mempool.lock().unwrap().clear();
+          }
+        }
+        Some(&reorg::Error::Unrecoverable) => {
+          self
+            .unrecoverably_reorged
+            .store(true, atomic::Ordering::Relaxed);
+          return Err(anyhow!(reorg::Error::Unrecoverable));
+        }
+        _ => return Err(err),
+      }
+    }
+  }
+
+  /// Waits until a block is announced or the polling interval elapses,
+  /// returning early on shutdown.
+  fn wait_for_block(&self) {
+    let deadline = Instant::now() + Duration::from_secs(5);
+
+    while !SHUTTING_DOWN.load(atomic::Ordering::Relaxed) {
+      let Some(timeout) = deadline.checked_duration_since(Instant::now()) else {
+        return;
+      };
+
+      let timeout = timeout.min(Duration::from_millis(100));
+
+      match &self.block_notifier {
+        Some(block_notifier) => {
+          if block_notifier.wait(timeout) {
+            return;
+          }
         }
+        None => thread::sleep(timeout),
       }
     }
   }
@@ -388,7 +806,7 @@ impl Index {
       match height {
         Some(height) => self
           .db
-          .get_cf(height_to_block_header, &height.to_be_bytes())
+          .get_cf(height_to_block_header, height.to_be_bytes())
           .unwrap(),
         None => self
           .db
@@ -435,7 +853,7 @@ impl Index {
 
     let Some(sequence_number) = self
       .db
-      .get_cf(inscription_id_to_sequence_number, &inscription_id.store())?
+      .get_cf(inscription_id_to_sequence_number, inscription_id.store())?
       .map(|value| u32::from_be_bytes(value.try_into().unwrap()))
     else {
       return Ok(None);
@@ -452,9 +870,46 @@ impl Index {
       .db
       .get_cf(
         sequence_number_to_inscription_entry,
-        &sequence_number.to_be_bytes(),
+        sequence_number.to_be_bytes(),
       )?
-      .map(|value| InscriptionEntry::load(value.try_into().unwrap()));
+      .map(InscriptionEntry::load);
     Ok(entry)
   }
 }
+
+#[cfg(test)]
+mod tests {
+  use {
+    super::*,
+    crate::{index::testing::Context, options::Options},
+  };
+
+  #[test]
+  fn shutting_down_with_uncommitted_blocks_is_a_distinct_error() {
+    let mut context = Context::new(Options::default());
+
+    context.mine_blocks(2);
+
+    let statistic_to_count = context.index.db.cf_handle("statistic_to_count").unwrap();
+    context
+      .index
+      .db
+      .put_cf(
+        statistic_to_count,
+        Statistic::CommittedHeight.key().to_be_bytes(),
+        1u32.to_be_bytes(),
+      )
+      .unwrap();
+
+    let err = context.index.shut_down().unwrap_err();
+
+    assert_eq!(
+      err.downcast_ref::<UncommittedBlocks>(),
+      Some(&UncommittedBlocks {
+        path: context.tempdir.path().join("regtest").join("dbs"),
+        committed_height: 1,
+        block_count: 3,
+      }),
+    );
+  }
+}
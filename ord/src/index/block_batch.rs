@@ -0,0 +1,197 @@
+use {
+  super::*,
+  rocksdb::{ColumnFamily, WriteBatch},
+  std::cell::RefCell,
+};
+
+/// Collects the writes made while indexing a single block, together with its
+/// undo log record, into one `WriteBatch`. Writes skip the WAL, so a memtable
+/// flush may persist the index at any point, but never halfway through a
+/// block.
+///
+/// Reads go through the batch, so values written earlier in the block are
+/// visible before it is written to the database.
+pub(crate) struct BlockBatch<'a> {
+  batch: RefCell<WriteBatch>,
+  db: &'a DB,
+  pending: RefCell<HashMap<(&'static str, Vec<u8>), Option<Vec<u8>>>>,
+  undo_log: UndoLog<'a>,
+}
+
+impl<'a> BlockBatch<'a> {
+  pub(crate) fn new(index: &'a Index) -> Self {
+    Self {
+      batch: RefCell::new(WriteBatch::default()),
+      db: &index.db,
+      pending: RefCell::new(HashMap::new()),
+      undo_log: UndoLog::new(index),
+    }
+  }
+
+  fn cf(&self, column_family: &str) -> Result<&'a ColumnFamily> {
+    self
+      .db
+      .cf_handle(column_family)
+      .ok_or_else(|| anyhow!("Failed to open column family '{column_family}'"))
+  }
+
+  pub(crate) fn get(&self, column_family: &'static str, key: &[u8]) -> Result<Option<Vec<u8>>> {
+    if let Some(value) = self.pending.borrow().get(&(column_family, key.to_vec())) {
+      return Ok(value.clone());
+    }
+
+    Ok(self.db.get_cf(self.cf(column_family)?, key)?)
+  }
+
+  pub(crate) fn put(&self, column_family: &'static str, key: &[u8], value: &[u8]) -> Result {
+    self.undo_log.record(column_family, key)?;
+
+    self
+      .batch
+      .borrow_mut()
+      .put_cf(self.cf(column_family)?, key, value);
+
+    self
+      .pending
+      .borrow_mut()
+      .insert((column_family, key.to_vec()), Some(value.to_vec()));
+
+    Ok(())
+  }
+
+  /// Deletes `key` and returns its value, `None` if it is absent.
+  pub(crate) fn remove(&self, column_family: &'static str, key: &[u8]) -> Result<Option<Vec<u8>>> {
+    let value = self.get(column_family, key)?;
+
+    if value.is_some() {
+      self
+        .undo_log
+        .record_value(column_family, key, value.as_deref());
+
+      self
+        .batch
+        .borrow_mut()
+        .delete_cf(self.cf(column_family)?, key);
+
+      self
+        .pending
+        .borrow_mut()
+        .insert((column_family, key.to_vec()), None);
+    }
+
+    Ok(value)
+  }
+
+  /// Records that `key` is created by this block but written later, so that
+  /// rolling the block back deletes it.
+  pub(crate) fn record_created(&self, column_family: &'static str, key: &[u8]) {
+    self.undo_log.record_value(column_family, key, None);
+  }
+
+  /// Records that `key`, holding `value` but not yet written, is deleted by
+  /// this block, so that rolling the block back writes `value`.
+  pub(crate) fn record_removed(&self, column_family: &'static str, key: &[u8], value: &[u8]) {
+    self.undo_log.record_value(column_family, key, Some(value));
+  }
+
+  /// Writes the block at `height` and its undo log record.
+  pub(crate) fn write(self, height: u32, index: &Index) -> Result {
+    let mut batch = self.batch.into_inner();
+
+    self.undo_log.store(height, &mut batch)?;
+
+    index.db.write_opt(batch, &index.write_options)?;
+
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use {
+    super::*,
+    crate::{index::testing::Context, options::Options},
+  };
+
+  const UTXOS: &str = "outpoint_to_utxo_entry";
+
+  fn outpoint(vout: u32) -> Vec<u8> {
+    OutPoint {
+      txid: Txid::all_zeros(),
+      vout,
+    }
+    .store()
+    .to_vec()
+  }
+
+  #[test]
+  fn reads_see_writes_earlier_in_the_block() {
+    let context = Context::new(Options::default());
+
+    let block_batch = BlockBatch::new(&context.index);
+
+    block_batch.put(UTXOS, &outpoint(0), &[1]).unwrap();
+
+    assert_eq!(block_batch.get(UTXOS, &outpoint(0)).unwrap(), Some(vec![1]));
+    assert_eq!(context.get(UTXOS, &outpoint(0)), None);
+
+    assert_eq!(
+      block_batch.remove(UTXOS, &outpoint(0)).unwrap(),
+      Some(vec![1])
+    );
+    assert_eq!(block_batch.get(UTXOS, &outpoint(0)).unwrap(), None);
+
+    block_batch.write(0, &context.index).unwrap();
+
+    assert_eq!(context.get(UTXOS, &outpoint(0)), None);
+  }
+
+  #[test]
+  fn rollback_restores_prior_values() {
+    let context = Context::new(Options::default());
+
+    let block_batch = BlockBatch::new(&context.index);
+    block_batch.put(UTXOS, &outpoint(0), &[1]).unwrap();
+    block_batch.put(UTXOS, &outpoint(1), &[2]).unwrap();
+    block_batch.write(0, &context.index).unwrap();
+
+    let block_batch = BlockBatch::new(&context.index);
+    block_batch.put(UTXOS, &outpoint(0), &[3]).unwrap();
+    block_batch.put(UTXOS, &outpoint(0), &[4]).unwrap();
+    block_batch.remove(UTXOS, &outpoint(1)).unwrap();
+    block_batch.put(UTXOS, &outpoint(2), &[5]).unwrap();
+    block_batch.write(1, &context.index).unwrap();
+
+    UndoLog::rollback(&context.index, 1..2).unwrap();
+
+    assert_eq!(context.get(UTXOS, &outpoint(0)), Some(vec![1]));
+    assert_eq!(context.get(UTXOS, &outpoint(1)), Some(vec![2]));
+    assert_eq!(context.get(UTXOS, &outpoint(2)), None);
+    assert_eq!(context.get("undo_log", &1u32.to_be_bytes()), None);
+    assert_eq!(context.index.committed_height().unwrap(), 1);
+  }
+
+  #[test]
+  fn rollback_restores_outputs_spent_from_an_uncommitted_block() {
+    let context = Context::new(Options::default());
+
+    // The first block creates an output, which stays in the UTXO cache
+    let block_batch = BlockBatch::new(&context.index);
+    block_batch.record_created(UTXOS, &outpoint(0));
+    block_batch.write(0, &context.index).unwrap();
+
+    // The second block spends it from the cache
+    let block_batch = BlockBatch::new(&context.index);
+    block_batch.record_removed(UTXOS, &outpoint(0), &[1]);
+    block_batch.write(1, &context.index).unwrap();
+
+    // Committing writes nothing, since the cache no longer holds it
+    UndoLog::rollback(&context.index, 1..2).unwrap();
+
+    assert_eq!(context.get(UTXOS, &outpoint(0)), Some(vec![1]));
+
+    UndoLog::rollback(&context.index, 0..1).unwrap();
+
+    assert_eq!(context.get(UTXOS, &outpoint(0)), None);
+  }
+}
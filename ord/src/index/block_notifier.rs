@@ -0,0 +1,258 @@
+use {
+  super::*,
+  std::{
+    io::Write,
+    net::TcpStream,
+    sync::{Arc, Condvar},
+  },
+};
+
+const MORE: u8 = 0x01;
+const LONG: u8 = 0x02;
+const COMMAND: u8 = 0x04;
+
+/// Wakes `Index::update` as soon as Bitcoin Core announces a block on its
+/// `-zmqpubhashblock` endpoint, instead of after the polling interval.
+///
+/// The subscriber speaks just enough ZMTP 3.0 to subscribe to `hashblock`
+/// over TCP with the NULL mechanism, which is what Bitcoin Core offers. It
+/// reconnects on any error, and polling continues in the meantime.
+#[derive(Default)]
+pub(crate) struct BlockNotifier {
+  condvar: Condvar,
+  notified: Mutex<bool>,
+}
+
+impl BlockNotifier {
+  /// Starts a thread subscribed to `endpoint`, given as `tcp://<HOST>:<PORT>`.
+  pub(crate) fn subscribe(endpoint: &str) -> Result<Arc<Self>> {
+    let address = endpoint
+      .strip_prefix("tcp://")
+      .ok_or_else(|| {
+        anyhow!("unsupported ZMQ endpoint `{endpoint}`, expected `tcp://<HOST>:<PORT>`")
+      })?
+      .to_owned();
+
+    let notifier = Arc::new(Self::default());
+
+    let subscriber = notifier.clone();
+    thread::Builder::new()
+      .name("zmq-hashblock".into())
+      .spawn(move || loop {
+        if let Err(err) = subscriber.listen(&address) {
+          log::warn!("Block notifications from {address} failed, reconnecting: {err}");
+        }
+
+        thread::sleep(Duration::from_secs(5));
+      })?;
+
+    Ok(notifier)
+  }
+
+  /// Returns once a block was announced since the last call, or after
+  /// `timeout`, and whether one was.
+  pub(crate) fn wait(&self, timeout: Duration) -> bool {
+    let notified = self.notified.lock().unwrap();
+
+    let (mut notified, _timeout) = self
+      .condvar
+      .wait_timeout_while(notified, timeout, |notified| !*notified)
+      .unwrap();
+
+    mem::take(&mut *notified)
+  }
+
+  fn notify(&self) {
+    *self.notified.lock().unwrap() = true;
+    self.condvar.notify_all();
+  }
+
+  fn listen(&self, address: &str) -> Result {
+    let mut stream = TcpStream::connect(address)?;
+    stream.set_nodelay(true)?;
+
+    // signature, version 3.0, NULL mechanism, as-server false
+    let mut greeting = [0; 64];
+    greeting[0] = 0xff;
+    greeting[9] = 0x7f;
+    greeting[10] = 3;
+    greeting[12..16].copy_from_slice(b"NULL");
+    stream.write_all(&greeting)?;
+
+    let mut peer = [0; 64];
+    stream.read_exact(&mut peer)?;
+
+    ensure!(
+      peer[0] == 0xff && peer[9] == 0x7f && peer[10] >= 3,
+      "peer at {address} does not speak ZMTP 3",
+    );
+
+    ensure!(
+      peer[12..16] == *b"NULL" && peer[16..32].iter().all(|byte| *byte == 0),
+      "peer at {address} requires a security mechanism",
+    );
+
+    let mut ready = vec![5];
+    ready.extend(b"READY");
+    ready.push(11);
+    ready.extend(b"Socket-Type");
+    ready.extend(3u32.to_be_bytes());
+    ready.extend(b"SUB");
+    Self::write_frame(&mut stream, COMMAND, &ready)?;
+
+    let (flags, body) = Self::read_frame(&mut stream)?;
+
+    ensure!(
+      flags & COMMAND != 0 && body.starts_with(b"\x05READY"),
+      "peer at {address} did not complete the ZMTP handshake",
+    );
+
+    // ZMTP 3.0 subscribes with a message starting with 1
+    Self::write_frame(&mut stream, 0, b"\x01hashblock")?;
+
+    log::info!("Subscribed to block notifications at {address}");
+
+    loop {
+      let mut message = Vec::new();
+
+      loop {
+        let (flags, body) = Self::read_frame(&mut stream)?;
+
+        if flags & COMMAND != 0 {
+          continue;
+        }
+
+        message.push(body);
+
+        if flags & MORE == 0 {
+          break;
+        }
+      }
+
+      if message.first().is_some_and(|topic| topic == b"hashblock") {
+        self.notify();
+      }
+    }
+  }
+
+  fn read_frame(stream: &mut TcpStream) -> Result<(u8, Vec<u8>)> {
+    let mut flags = [0];
+    stream.read_exact(&mut flags)?;
+    let flags = flags[0];
+
+    let len = if flags & LONG != 0 {
+      let mut len = [0; 8];
+      stream.read_exact(&mut len)?;
+      u64::from_be_bytes(len)
+    } else {
+      let mut len = [0];
+      stream.read_exact(&mut len)?;
+      len[0].into()
+    };
+
+    // Notifications are small, anything this large is not from bitcoind
+    ensure!(
+      len <= 4 * 1024 * 1024,
+      "ZMQ frame of {len} bytes is too large"
+    );
+
+    let mut body = vec![0; len.try_into().unwrap()];
+    stream.read_exact(&mut body)?;
+
+    Ok((flags, body))
+  }
+
+  fn write_frame(stream: &mut TcpStream, flags: u8, body: &[u8]) -> Result {
+    match u8::try_from(body.len()) {
+      Ok(len) => stream.write_all(&[flags, len])?,
+      Err(_) => {
+        stream.write_all(&[flags | LONG])?;
+        stream.write_all(&u64::try_from(body.len()).unwrap().to_be_bytes())?;
+      }
+    }
+
+    stream.write_all(body)?;
+
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use {super::*, std::net::TcpListener};
+
+  /// Accepts a subscriber as Bitcoin Core would and announces a block. The
+  /// connection is returned so the subscriber isn't disconnected.
+  fn announce_block(listener: TcpListener) -> TcpStream {
+    let (mut stream, _address) = listener.accept().unwrap();
+
+    let mut greeting = [0; 64];
+    greeting[0] = 0xff;
+    greeting[9] = 0x7f;
+    greeting[10] = 3;
+    greeting[12..16].copy_from_slice(b"NULL");
+    greeting[32] = 1;
+    stream.write_all(&greeting).unwrap();
+
+    let mut peer = [0; 64];
+    stream.read_exact(&mut peer).unwrap();
+    assert_eq!(peer[12..16], *b"NULL");
+
+    let (flags, ready) = BlockNotifier::read_frame(&mut stream).unwrap();
+    assert_eq!(flags, COMMAND);
+    assert!(ready.starts_with(b"\x05READY"));
+
+    let mut ready = vec![5];
+    ready.extend(b"READY");
+    ready.push(11);
+    ready.extend(b"Socket-Type");
+    ready.extend(3u32.to_be_bytes());
+    ready.extend(b"PUB");
+    BlockNotifier::write_frame(&mut stream, COMMAND, &ready).unwrap();
+
+    assert_eq!(
+      BlockNotifier::read_frame(&mut stream).unwrap(),
+      (0, b"\x01hashblock".to_vec()),
+    );
+
+    BlockNotifier::write_frame(&mut stream, MORE, b"rawtx").unwrap();
+    BlockNotifier::write_frame(&mut stream, 0, &[0; 32]).unwrap();
+
+    BlockNotifier::write_frame(&mut stream, MORE, b"hashblock").unwrap();
+    BlockNotifier::write_frame(&mut stream, MORE, &[0; 32]).unwrap();
+    BlockNotifier::write_frame(&mut stream, 0, &0u32.to_le_bytes()).unwrap();
+
+    stream
+  }
+
+  #[test]
+  fn announced_blocks_wake_waiters() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let endpoint = format!("tcp://{}", listener.local_addr().unwrap());
+
+    let publisher = thread::spawn(move || announce_block(listener));
+
+    let notifier = BlockNotifier::subscribe(&endpoint).unwrap();
+
+    let _stream = publisher.join().unwrap();
+
+    assert!(notifier.wait(Duration::from_secs(10)));
+    assert!(!notifier.wait(Duration::from_millis(10)));
+  }
+
+  #[test]
+  fn waiting_without_announcements_times_out() {
+    assert!(!BlockNotifier::default().wait(Duration::from_millis(10)));
+  }
+
+  #[test]
+  fn only_tcp_endpoints_are_supported() {
+    assert_eq!(
+      BlockNotifier::subscribe("ipc:///tmp/bitcoind.sock")
+        .err()
+        .unwrap()
+        .to_string(),
+      "unsupported ZMQ endpoint `ipc:///tmp/bitcoind.sock`, expected `tcp://<HOST>:<PORT>`",
+    );
+  }
+}
@@ -0,0 +1,76 @@
+use {
+  super::*,
+  tokio::sync::{broadcast, mpsc},
+  updater::{BlockData, Updater},
+};
+
+pub(crate) use recording::Recording;
+
+mod recording;
+
+/// Where `Updater` gets blocks, and the outputs they spend that aren't in the
+/// UTXO index, from.
+pub(crate) trait BlockSource: Send + Sync {
+  /// Whether the source follows a chain that grows. `Index::update` returns
+  /// once a source that doesn't has been indexed.
+  fn live(&self) -> bool;
+
+  /// Height of the best block plus one.
+  fn block_count(&self) -> Result<u32>;
+
+  /// Height of the best header, which is ahead of the best block while
+  /// syncing.
+  fn header_height(&self) -> Result<u64>;
+
+  fn block_hash(&self, height: u32) -> Result<Option<BlockHash>>;
+
+  /// Sends blocks starting at `height` until the tip or the height limit is
+  /// reached.
+  fn blocks(&self, index: &Index, height: u32) -> Result<std::sync::mpsc::Receiver<BlockData>>;
+
+  /// Resolves the outputs sent to the returned sender, in order.
+  fn prevouts(&self, index: &Index)
+    -> Result<(mpsc::Sender<OutPoint>, broadcast::Receiver<TxOut>)>;
+}
+
+/// Bitcoin Core, over RPC and optionally its block files.
+pub(crate) struct Rpc {
+  client: Client,
+}
+
+impl Rpc {
+  pub(crate) fn new(settings: &Settings) -> Result<Self> {
+    Ok(Self {
+      client: settings.bitcoin_rpc_client(None)?,
+    })
+  }
+}
+
+impl BlockSource for Rpc {
+  fn live(&self) -> bool {
+    true
+  }
+
+  fn block_count(&self) -> Result<u32> {
+    Ok(u32::try_from(self.client.get_block_count()?).unwrap() + 1)
+  }
+
+  fn header_height(&self) -> Result<u64> {
+    Ok(self.client.get_blockchain_info()?.headers)
+  }
+
+  fn block_hash(&self, height: u32) -> Result<Option<BlockHash>> {
+    self.client.get_block_hash(height.into()).into_option()
+  }
+
+  fn blocks(&self, index: &Index, height: u32) -> Result<std::sync::mpsc::Receiver<BlockData>> {
+    Updater::fetch_blocks_from(index, height)
+  }
+
+  fn prevouts(
+    &self,
+    index: &Index,
+  ) -> Result<(mpsc::Sender<OutPoint>, broadcast::Receiver<TxOut>)> {
+    Updater::spawn_fetcher(index)
+  }
+}
@@ -0,0 +1,429 @@
+use {
+  super::*,
+  bitcoin::consensus,
+  std::io::{BufReader, BufWriter, Read, Seek, SeekFrom, Write},
+};
+
+/// Recordings are `MAGIC ‖ network_magic(4) ‖ start_height(4) ‖
+/// block_count(4) ‖ record*`, where every record is
+/// `len(4) ‖ block ‖ spent_output*`, holding the block and the outputs spent
+/// by its inputs, coinbase excepted, in consensus encoding. Integers are
+/// little-endian.
+const MAGIC: &[u8; 8] = b"OPIBLKS1";
+
+const HEADER_LEN: u64 = 20;
+
+#[derive(Serialize, Debug)]
+pub struct RecordingInfo {
+  pub blocks: u32,
+  pub bytes: u64,
+  pub chain: Chain,
+  pub from: u32,
+  pub path: PathBuf,
+  pub to: u32,
+}
+
+#[derive(Deserialize)]
+struct VerboseBlock {
+  tx: Vec<VerboseTransaction>,
+}
+
+#[derive(Deserialize)]
+struct VerboseTransaction {
+  vin: Vec<VerboseInput>,
+}
+
+#[derive(Deserialize)]
+struct VerboseInput {
+  prevout: Option<VerbosePrevout>,
+}
+
+#[derive(Deserialize)]
+struct VerbosePrevout {
+  #[serde(with = "bitcoin::amount::serde::as_btc")]
+  value: Amount,
+  #[serde(rename = "scriptPubKey")]
+  script_pubkey: VerboseScriptPubkey,
+}
+
+#[derive(Deserialize)]
+struct VerboseScriptPubkey {
+  hex: ScriptBuf,
+}
+
+/// Blocks recorded by `ord index record`, replayed without Bitcoin Core so
+/// that indexing them can be reproduced exactly.
+pub(crate) struct Recording {
+  /// Hash and file offset of every block, by height above `start`
+  blocks: Vec<(BlockHash, u64)>,
+  path: PathBuf,
+  start: u32,
+  /// Never sends, but kept open so the updater sees no outputs pending
+  txout_sender: broadcast::Sender<TxOut>,
+}
+
+impl Recording {
+  pub(crate) fn open(settings: &Settings, path: &Path) -> Result<Self> {
+    let mut reader = BufReader::new(
+      File::open(path).with_context(|| format!("failed to open recording {}", path.display()))?,
+    );
+
+    let mut header = [0; 20];
+    reader.read_exact(&mut header)?;
+
+    ensure!(
+      header[..8] == *MAGIC,
+      "{} is not a block recording",
+      path.display()
+    );
+
+    ensure!(
+      header[8..12] == Network::from(settings.chain()).magic().to_bytes(),
+      "{} was not recorded on {}",
+      path.display(),
+      settings.chain(),
+    );
+
+    let start = u32::from_le_bytes(header[12..16].try_into().unwrap());
+    let count = u32::from_le_bytes(header[16..20].try_into().unwrap());
+
+    let mut blocks = Vec::new();
+    let mut offset = HEADER_LEN;
+
+    for height in start..start + count {
+      let mut prefix = [0; 4 + Header::SIZE];
+      reader
+        .read_exact(&mut prefix)
+        .with_context(|| format!("recording ends before block {height}"))?;
+
+      let len = u32::from_le_bytes(prefix[..4].try_into().unwrap());
+      let header = consensus::deserialize::<Header>(&prefix[4..])?;
+
+      blocks.push((header.block_hash(), offset));
+
+      reader.seek_relative(i64::from(len) - i64::try_from(Header::SIZE).unwrap())?;
+      offset += 4 + u64::from(len);
+    }
+
+    Ok(Self {
+      blocks,
+      path: path.into(),
+      start,
+      txout_sender: broadcast::channel(1).0,
+    })
+  }
+
+  fn read_block(reader: &mut impl Read) -> Result<BlockData> {
+    let mut len = [0; 4];
+    reader.read_exact(&mut len)?;
+
+    let mut record = vec![0; u32::from_le_bytes(len).try_into().unwrap()];
+    reader.read_exact(&mut record)?;
+
+    let (block, mut consumed) = consensus::deserialize_partial::<Block>(&record)?;
+
+    let mut spent_outputs = Vec::new();
+
+    for tx in block.txdata.iter().skip(1) {
+      let mut outputs = Vec::with_capacity(tx.input.len());
+
+      for _ in &tx.input {
+        let (txout, len) = consensus::deserialize_partial::<TxOut>(&record[consumed..])?;
+        consumed += len;
+        outputs.push(txout);
+      }
+
+      spent_outputs.push(outputs);
+    }
+
+    ensure!(
+      consumed == record.len(),
+      "record of block {} has {} trailing bytes",
+      block.block_hash(),
+      record.len() - consumed,
+    );
+
+    let mut block = BlockData::from(block);
+    block.spent_outputs = Some(spent_outputs);
+
+    Ok(block)
+  }
+
+  /// Writes blocks `from..=to` and the outputs they spend, as reported by
+  /// Bitcoin Core's `getblock` with verbosity 3, to `path`. The recording is
+  /// written next to it and only moved into place once complete.
+  pub(crate) fn record(
+    settings: &Settings,
+    from: u32,
+    to: u32,
+    path: &Path,
+  ) -> Result<RecordingInfo> {
+    ensure!(from <= to, "--from {from} is above --to {to}");
+
+    let client = settings.bitcoin_rpc_client(None)?;
+
+    let tip = u32::try_from(client.get_block_count()?)?;
+
+    ensure!(to <= tip, "--to {to} is above Bitcoin Core's tip at {tip}");
+
+    let partial = path.with_extension("partial");
+
+    let mut writer = BufWriter::new(
+      File::create(&partial).with_context(|| format!("failed to create {}", partial.display()))?,
+    );
+
+    writer.write_all(MAGIC)?;
+    writer.write_all(&Network::from(settings.chain()).magic().to_bytes())?;
+    writer.write_all(&from.to_le_bytes())?;
+    writer.write_all(&(to - from + 1).to_le_bytes())?;
+
+    let mut bytes = HEADER_LEN;
+
+    for height in from..=to {
+      let hash = client.get_block_hash(height.into())?;
+
+      let block = client.get_block(&hash)?;
+
+      let verbose = client
+        .call::<VerboseBlock>("getblock", &[serde_json::json!(hash), serde_json::json!(3)])?;
+
+      ensure!(
+        verbose.tx.len() == block.txdata.len(),
+        "Bitcoin Core returned {} verbose transactions for block {height} with {} transactions",
+        verbose.tx.len(),
+        block.txdata.len(),
+      );
+
+      let mut record = consensus::serialize(&block);
+
+      for (tx, verbose) in block.txdata.iter().zip(verbose.tx).skip(1) {
+        ensure!(
+          tx.input.len() == verbose.vin.len(),
+          "Bitcoin Core returned {} verbose inputs for transaction {} with {} inputs",
+          verbose.vin.len(),
+          tx.compute_txid(),
+          tx.input.len(),
+        );
+
+        for input in verbose.vin {
+          let prevout = input.prevout.ok_or_else(|| {
+            anyhow!("Bitcoin Core returned no prevouts for block {height}, its undo data may have been pruned")
+          })?;
+
+          record.extend(consensus::serialize(&TxOut {
+            value: prevout.value,
+            script_pubkey: prevout.script_pubkey.hex,
+          }));
+        }
+      }
+
+      writer.write_all(&u32::try_from(record.len())?.to_le_bytes())?;
+      writer.write_all(&record)?;
+
+      bytes += 4 + u64::try_from(record.len()).unwrap();
+
+      log::info!("Recorded block {height} {hash}");
+    }
+
+    writer
+      .into_inner()
+      .map_err(|err| err.into_error())?
+      .sync_all()?;
+
+    fs::rename(&partial, path)?;
+
+    Ok(RecordingInfo {
+      blocks: to - from + 1,
+      bytes,
+      chain: settings.chain(),
+      from,
+      path: path.into(),
+      to,
+    })
+  }
+}
+
+impl BlockSource for Recording {
+  fn live(&self) -> bool {
+    false
+  }
+
+  fn block_count(&self) -> Result<u32> {
+    Ok(self.start + u32::try_from(self.blocks.len()).unwrap())
+  }
+
+  fn header_height(&self) -> Result<u64> {
+    Ok(self.block_count()?.saturating_sub(1).into())
+  }
+
+  fn block_hash(&self, height: u32) -> Result<Option<BlockHash>> {
+    Ok(
+      height
+        .checked_sub(self.start)
+        .and_then(|offset| self.blocks.get(usize::try_from(offset).unwrap()))
+        .map(|(hash, _offset)| *hash),
+    )
+  }
+
+  fn blocks(&self, index: &Index, height: u32) -> Result<std::sync::mpsc::Receiver<BlockData>> {
+    ensure!(
+      height >= self.start,
+      "index is at height {height}, but {} starts at height {}, import a snapshot of height {} first",
+      self.path.display(),
+      self.start,
+      self.start.saturating_sub(1),
+    );
+
+    let (tx, rx) = std::sync::mpsc::sync_channel(32);
+
+    let Some(&(_hash, offset)) = self
+      .blocks
+      .get(usize::try_from(height - self.start).unwrap())
+    else {
+      return Ok(rx);
+    };
+
+    let mut reader = BufReader::new(File::open(&self.path)?);
+    reader.seek(SeekFrom::Start(offset))?;
+
+    let end = self.block_count()?;
+    let end = index
+      .height_limit
+      .map_or(end, |height_limit| end.min(height_limit));
+
+    let first_index_height = index.first_index_height;
+
+    thread::spawn(move || {
+      for height in height..end {
+        let mut block = match Self::read_block(&mut reader) {
+          Ok(block) => block,
+          Err(err) => {
+            log::error!("failed to read block {height} from recording: {err}");
+            break;
+          }
+        };
+
+        if height < first_index_height {
+          block.txdata.clear();
+          block.spent_outputs = None;
+        }
+
+        if let Err(err) = tx.send(block) {
+          log::info!("Block receiver disconnected: {err}");
+          break;
+        }
+      }
+    });
+
+    Ok(rx)
+  }
+
+  fn prevouts(
+    &self,
+    _index: &Index,
+  ) -> Result<(mpsc::Sender<OutPoint>, broadcast::Receiver<TxOut>)> {
+    // Recorded blocks carry the outputs they spend, so nothing is fetched
+    let (outpoint_sender, _outpoint_receiver) = mpsc::channel(1);
+
+    Ok((outpoint_sender, self.txout_sender.subscribe()))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use {
+    super::*,
+    crate::{index::testing::Context, options::Options},
+    mockcore::TransactionTemplate,
+    tempfile::TempDir,
+  };
+
+  fn entries(index: &Index, column_family: &str) -> Vec<(Box<[u8]>, Box<[u8]>)> {
+    index
+      .db
+      .iterator_cf(
+        index.db.cf_handle(column_family).unwrap(),
+        IteratorMode::Start,
+      )
+      .map(Result::unwrap)
+      .collect()
+  }
+
+  #[test]
+  fn replaying_a_recording_indexes_like_bitcoin_core() {
+    let mut context = Context::new(Options::default());
+
+    context.mine_blocks(1);
+
+    let inscription = Inscription {
+      content_type: Some("text/plain".into()),
+      body: Some("foo".into()),
+      ..default()
+    };
+
+    context.core.broadcast_tx(TransactionTemplate {
+      inputs: &[(
+        1,
+        0,
+        0,
+        Witness::from_slice(&[
+          inscription
+            .append_reveal_script_to_builder(script::Builder::new())
+            .into_script()
+            .into_bytes(),
+          Vec::new(),
+        ]),
+      )],
+      ..default()
+    });
+    context.mine_blocks(1);
+
+    context.core.broadcast_tx(TransactionTemplate {
+      inputs: &[(2, 1, 0, Witness::new()), (2, 0, 0, Witness::new())],
+      outputs: 2,
+      ..default()
+    });
+    context.mine_blocks(1);
+
+    let path = context.tempdir.path().join("blocks.rec");
+
+    let info = Recording::record(
+      &Context::core_settings(&context.core, &context.tempdir, Options::default()),
+      0,
+      3,
+      &path,
+    )
+    .unwrap();
+
+    assert_eq!(info.blocks, 4);
+    assert!(path.exists());
+    assert!(!path.with_extension("partial").exists());
+
+    let tempdir = TempDir::new().unwrap();
+
+    let recording =
+      Recording::open(&Context::settings(&tempdir, Options::default()), &path).unwrap();
+
+    let mut replayed =
+      Context::open_with_block_source(&tempdir, Options::default(), Box::new(recording)).unwrap();
+
+    replayed.update().unwrap();
+
+    assert_eq!(replayed.block_count().unwrap(), 4);
+
+    for column_family in COLUMN_FAMILIES {
+      // Index times differ between runs
+      if *column_family == "ord_index_stats" {
+        continue;
+      }
+
+      assert_eq!(
+        entries(&replayed, column_family),
+        entries(&context.index, column_family),
+        "{column_family} differs",
+      );
+    }
+
+    assert!(!entries(&replayed, "ord_transfers").is_empty());
+  }
+}
@@ -1,4 +1,4 @@
-use super::*;
+use {super::*, db_reader::InscriptionEvent};
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum Event {
@@ -42,3 +42,46 @@ pub enum Event {
     txid: Txid,
   },
 }
+
+impl Event {
+  /// The event as pushed to db_reader subscribers, `None` for rune events.
+  pub(crate) fn to_inscription_event(&self) -> Option<InscriptionEvent> {
+    match self {
+      Self::InscriptionCreated {
+        block_height,
+        charms,
+        inscription_id,
+        location,
+        parent_inscription_ids,
+        sequence_number,
+      } => Some(InscriptionEvent::InscriptionCreated {
+        block_height: *block_height,
+        charms: *charms,
+        inscription_id: inscription_id.to_string(),
+        location: location.map(|location| location.to_string()),
+        parent_inscription_ids: parent_inscription_ids
+          .iter()
+          .map(|parent| parent.to_string())
+          .collect(),
+        sequence_number: *sequence_number,
+      }),
+      Self::InscriptionTransferred {
+        block_height,
+        inscription_id,
+        new_location,
+        old_location,
+        sequence_number,
+      } => Some(InscriptionEvent::InscriptionTransferred {
+        block_height: *block_height,
+        inscription_id: inscription_id.to_string(),
+        new_location: new_location.to_string(),
+        old_location: old_location.to_string(),
+        sequence_number: *sequence_number,
+      }),
+      Self::RuneBurned { .. }
+      | Self::RuneEtched { .. }
+      | Self::RuneMinted { .. }
+      | Self::RuneTransferred { .. } => None,
+    }
+  }
+}
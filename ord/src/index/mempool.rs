@@ -0,0 +1,420 @@
+use {
+  super::*,
+  db_reader::{PendingTransfer, PendingTransfers},
+};
+
+/// At most this many new mempool transactions are fetched per refresh, so a
+/// large mempool doesn't hold up indexing the next block.
+const MAX_NEW_TRANSACTIONS: usize = 10_000;
+
+/// Simulates the transfers of tracked inscriptions by transactions in
+/// bitcoind's mempool against the committed UTXO index, and publishes them to
+/// db_reader.
+///
+/// Transactions are simulated once, when first seen, and dropped once they
+/// confirm or leave the mempool. Transactions spending outputs of other
+/// mempool transactions are deferred, without being fetched again, until
+/// those parents confirm.
+pub(crate) struct Mempool {
+  client: Client,
+  deferred: HashMap<Txid, HashSet<Txid>>,
+  pending_transfers: PendingTransfers,
+  transfers: HashMap<Txid, Vec<PendingTransfer>>,
+}
+
+impl Mempool {
+  pub(crate) fn new(settings: &Settings) -> Result<Self> {
+    Ok(Self {
+      client: settings.bitcoin_rpc_client(None)?,
+      deferred: HashMap::new(),
+      pending_transfers: PendingTransfers::default(),
+      transfers: HashMap::new(),
+    })
+  }
+
+  pub(crate) fn pending_transfers(&self) -> PendingTransfers {
+    self.pending_transfers.clone()
+  }
+
+  /// Forgets every simulated transaction. Their inputs may have been spent
+  /// differently in blocks that were rolled back.
+  pub(crate) fn clear(&mut self) {
+    self.deferred.clear();
+    self.transfers.clear();
+    self.pending_transfers.replace(Vec::new());
+  }
+
+  pub(crate) fn refresh(&mut self, index: &Index) -> Result {
+    let txids = self
+      .client
+      .get_raw_mempool()?
+      .into_iter()
+      .collect::<HashSet<Txid>>();
+
+    self.transfers.retain(|txid, _| txids.contains(txid));
+
+    // Deferred transactions are simulated again once none of their parents
+    // are left in the mempool
+    self.deferred.retain(|txid, parents| {
+      txids.contains(txid) && parents.iter().any(|parent| txids.contains(parent))
+    });
+
+    let new = txids
+      .iter()
+      .filter(|txid| !self.transfers.contains_key(*txid) && !self.deferred.contains_key(*txid))
+      .take(MAX_NEW_TRANSACTIONS)
+      .copied()
+      .collect::<Vec<Txid>>();
+
+    for txid in new {
+      // The transaction may have confirmed or been evicted since
+      let Some(tx) = self.client.get_raw_transaction(&txid, None).into_option()? else {
+        continue;
+      };
+
+      let parents = tx
+        .input
+        .iter()
+        .map(|input| input.previous_output.txid)
+        .filter(|parent| txids.contains(parent))
+        .collect::<HashSet<Txid>>();
+
+      if !parents.is_empty() {
+        self.deferred.insert(txid, parents);
+        continue;
+      }
+
+      if let Some(transfers) = self.simulate(index, &tx, txid)? {
+        self.transfers.insert(txid, transfers);
+      }
+    }
+
+    self
+      .pending_transfers
+      .replace(self.transfers.values().flatten().cloned().collect());
+
+    Ok(())
+  }
+
+  /// The transfers of tracked inscriptions by `tx`, found the way
+  /// `InscriptionUpdater` locates inscriptions in a transaction's outputs, or
+  /// `None` if it spends unconfirmed outputs.
+  fn simulate(
+    &self,
+    index: &Index,
+    tx: &Transaction,
+    txid: Txid,
+  ) -> Result<Option<Vec<PendingTransfer>>> {
+    let outpoint_to_utxo_entry = index
+      .db
+      .cf_handle("outpoint_to_utxo_entry")
+      .ok_or_else(|| anyhow!("Failed to open column family 'outpoint_to_utxo_entry'"))?;
+    let sequence_number_to_inscription_entry = index
+      .db
+      .cf_handle("sequence_number_to_inscription_entry")
+      .ok_or_else(|| {
+        anyhow!("Failed to open column family 'sequence_number_to_inscription_entry'")
+      })?;
+    let inscription_id_to_txcnt = index
+      .db
+      .cf_handle("inscription_id_to_txcnt")
+      .ok_or_else(|| anyhow!("Failed to open column family 'inscription_id_to_txcnt'"))?;
+
+    let mut inscriptions = Vec::new();
+    let mut input_value = 0;
+
+    for input in &tx.input {
+      let entry = index
+        .db
+        .get_cf(outpoint_to_utxo_entry, input.previous_output.store())?
+        .map(UtxoEntryBuf::new_with_values);
+
+      let value = match entry {
+        Some(entry) => {
+          let entry = entry.parse();
+
+          for (sequence_number, offset) in entry.parse_inscriptions() {
+            inscriptions.push((
+              sequence_number,
+              SatPoint {
+                outpoint: input.previous_output,
+                offset,
+              },
+              input_value + offset,
+            ));
+          }
+
+          entry.total_value()
+        }
+        // Outputs created before the first indexed height aren't in the
+        // index and hold no inscriptions, but bitcoind knows them as long as
+        // they are unspent. Anything else is unconfirmed.
+        None => match self.client.get_tx_out(
+          &input.previous_output.txid,
+          input.previous_output.vout,
+          Some(false),
+        )? {
+          Some(txout) => txout.value.to_sat(),
+          None => return Ok(None),
+        },
+      };
+
+      input_value += value;
+    }
+
+    let mut transfers = Vec::new();
+
+    for (sequence_number, old_satpoint, offset) in inscriptions {
+      let Some(entry) = index
+        .db
+        .get_cf(
+          sequence_number_to_inscription_entry,
+          sequence_number.to_be_bytes(),
+        )?
+        .map(InscriptionEntry::load)
+      else {
+        continue;
+      };
+
+      let txcnt = index
+        .db
+        .get_cf(inscription_id_to_txcnt, entry.id.store())?
+        .map(|txcnt| i64::from_be_bytes(txcnt.try_into().unwrap()))
+        .unwrap_or(0);
+
+      if !entry.is_json_or_text || txcnt + 1 > i64::from(entry.txcnt_limit) {
+        continue;
+      }
+
+      let mut output_value = 0;
+      let mut new_location = None;
+
+      for (vout, txout) in tx.output.iter().enumerate() {
+        let end = output_value + txout.value.to_sat();
+
+        if offset < end {
+          new_location = Some((
+            SatPoint {
+              outpoint: OutPoint {
+                txid,
+                vout: vout.try_into().unwrap(),
+              },
+              offset: offset - output_value,
+            },
+            &txout.script_pubkey,
+          ));
+          break;
+        }
+
+        output_value = end;
+      }
+
+      transfers.push(PendingTransfer {
+        inscription_id: entry.id.to_string(),
+        sequence_number,
+        txid: txid.to_string(),
+        old_satpoint: old_satpoint.to_string(),
+        new_satpoint: new_location.map(|(satpoint, _)| satpoint.to_string()),
+        new_pkscript: new_location
+          .map(|(_, script_pubkey)| hex::encode(script_pubkey.as_bytes()))
+          .unwrap_or_default(),
+        new_wallet: new_location
+          .and_then(|(_, script_pubkey)| {
+            Address::from_script(script_pubkey, Network::from(index.settings.chain())).ok()
+          })
+          .map(|address| address.to_string())
+          .unwrap_or_default(),
+        sent_as_fee: new_location.is_none(),
+      });
+    }
+
+    Ok(Some(transfers))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use {
+    super::*,
+    crate::{index::testing::Context, options::Options},
+    bitcoin::{absolute::LockTime, transaction::Version, TxIn},
+    mockcore::TransactionTemplate,
+    std::sync::MutexGuard,
+  };
+
+  fn context() -> Context {
+    Context::new(Options {
+      undo_log_depth: Some(6),
+      watch_mempool: true,
+      ..default()
+    })
+  }
+
+  /// Mines a block revealing a text inscription from the coinbase of block 1.
+  fn inscribe(context: &mut Context) -> InscriptionId {
+    context.mine_blocks(1);
+
+    let inscription = Inscription {
+      content_type: Some("text/plain".into()),
+      body: Some("foo".into()),
+      ..default()
+    };
+
+    let txid = context.core.broadcast_tx(TransactionTemplate {
+      inputs: &[(
+        1,
+        0,
+        0,
+        Witness::from_slice(&[
+          inscription
+            .append_reveal_script_to_builder(script::Builder::new())
+            .into_script()
+            .into_bytes(),
+          Vec::new(),
+        ]),
+      )],
+      ..default()
+    });
+
+    context.mine_blocks(1);
+
+    InscriptionId { txid, index: 0 }
+  }
+
+  fn mempool(context: &Context) -> MutexGuard<'_, Mempool> {
+    context.index.mempool.as_ref().unwrap().lock().unwrap()
+  }
+
+  fn refresh(context: &Context) -> Vec<PendingTransfer> {
+    let mut mempool = mempool(context);
+    mempool.refresh(&context.index).unwrap();
+    mempool.pending_transfers().get().to_vec()
+  }
+
+  #[test]
+  fn transfers_are_pending_until_they_confirm() {
+    let mut context = context();
+
+    let inscription_id = inscribe(&mut context);
+
+    let txid = context.core.broadcast_tx(TransactionTemplate {
+      inputs: &[(2, 1, 0, Witness::new())],
+      ..default()
+    });
+
+    let transfers = refresh(&context);
+
+    assert_eq!(transfers.len(), 1);
+    assert_eq!(transfers[0].inscription_id, inscription_id.to_string());
+    assert_eq!(transfers[0].txid, txid.to_string());
+    assert_eq!(
+      transfers[0].old_satpoint,
+      format!("{}:0:0", inscription_id.txid)
+    );
+    assert_eq!(transfers[0].new_satpoint, Some(format!("{txid}:0:0")));
+    assert!(!transfers[0].sent_as_fee);
+
+    context.mine_blocks(1);
+
+    assert_eq!(refresh(&context), []);
+  }
+
+  #[test]
+  fn transfers_without_outputs_are_sent_as_fee() {
+    let mut context = context();
+
+    inscribe(&mut context);
+
+    context.core.broadcast_tx(TransactionTemplate {
+      inputs: &[(2, 1, 0, Witness::new())],
+      outputs: 0,
+      ..default()
+    });
+
+    let transfers = refresh(&context);
+
+    assert_eq!(transfers.len(), 1);
+    assert_eq!(transfers[0].new_satpoint, None);
+    assert_eq!(transfers[0].new_pkscript, "");
+    assert!(transfers[0].sent_as_fee);
+  }
+
+  #[test]
+  fn transactions_spending_unconfirmed_outputs_are_deferred_until_their_parent_confirms() {
+    let mut context = context();
+
+    inscribe(&mut context);
+
+    let parent = context.core.broadcast_tx(TransactionTemplate {
+      inputs: &[(2, 1, 0, Witness::new())],
+      ..default()
+    });
+
+    let child = Transaction {
+      version: Version(2),
+      lock_time: LockTime::ZERO,
+      input: vec![TxIn {
+        previous_output: OutPoint {
+          txid: parent,
+          vout: 0,
+        },
+        ..default()
+      }],
+      output: vec![TxOut {
+        value: context.core.tx(2, 1).output[0].value,
+        script_pubkey: ScriptBuf::new(),
+      }],
+    };
+
+    let child_txid = child.compute_txid();
+
+    context.core.state().mempool.push(child);
+
+    refresh(&context);
+    refresh(&context);
+
+    {
+      let mempool = mempool(&context);
+      assert_eq!(mempool.deferred, [(child_txid, [parent].into())].into());
+      assert_eq!(mempool.transfers.keys().collect::<Vec<&Txid>>(), [&parent]);
+    }
+
+    // Confirm the parent without the child
+    let child = context.core.state().mempool.pop().unwrap();
+
+    context.mine_blocks(1);
+
+    context.core.state().mempool.push(child);
+
+    refresh(&context);
+
+    let mempool = mempool(&context);
+    assert!(mempool.deferred.is_empty());
+    assert_eq!(
+      mempool.transfers.keys().collect::<Vec<&Txid>>(),
+      [&child_txid]
+    );
+  }
+
+  #[test]
+  fn reorgs_clear_pending_transfers() {
+    let mut context = context();
+
+    inscribe(&mut context);
+
+    context.core.broadcast_tx(TransactionTemplate {
+      inputs: &[(2, 1, 0, Witness::new())],
+      ..default()
+    });
+
+    assert_eq!(refresh(&context).len(), 1);
+
+    context.core.state().mempool.clear();
+    context.core.invalidate_tip();
+    context.mine_blocks(2);
+
+    let mempool = mempool(&context);
+    assert!(mempool.transfers.is_empty());
+    assert!(mempool.pending_transfers().get().is_empty());
+  }
+}
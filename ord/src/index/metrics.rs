@@ -0,0 +1,337 @@
+use {
+  super::*,
+  axum::{extract::State, http::header, response::IntoResponse, routing::get, Router},
+  std::{
+    fmt::Write,
+    net::SocketAddr,
+    sync::{atomic::AtomicU64, Arc},
+  },
+};
+
+const DURATION_BUCKETS: &[f64] = &[
+  0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0, 60.0, 300.0,
+];
+
+const INSCRIPTION_BUCKETS: &[f64] = &[
+  0.0, 1.0, 10.0, 100.0, 1000.0, 2500.0, 5000.0, 10000.0, 25000.0, 50000.0,
+];
+
+#[derive(Default)]
+pub(crate) struct Counter(AtomicU64);
+
+impl Counter {
+  pub(crate) fn inc(&self) {
+    self.0.fetch_add(1, atomic::Ordering::Relaxed);
+  }
+
+  fn get(&self) -> u64 {
+    self.0.load(atomic::Ordering::Relaxed)
+  }
+}
+
+/// Stores the bits of an `f64`.
+#[derive(Default)]
+pub(crate) struct Gauge(AtomicU64);
+
+impl Gauge {
+  pub(crate) fn set(&self, value: f64) {
+    self.0.store(value.to_bits(), atomic::Ordering::Relaxed);
+  }
+
+  fn get(&self) -> f64 {
+    f64::from_bits(self.0.load(atomic::Ordering::Relaxed))
+  }
+}
+
+pub(crate) struct Histogram {
+  buckets: &'static [f64],
+  state: Mutex<HistogramState>,
+}
+
+#[derive(Default)]
+struct HistogramState {
+  count: u64,
+  counts: Vec<u64>,
+  sum: f64,
+}
+
+impl Histogram {
+  fn new(buckets: &'static [f64]) -> Self {
+    Self {
+      buckets,
+      state: Mutex::new(HistogramState {
+        counts: vec![0; buckets.len()],
+        ..default()
+      }),
+    }
+  }
+
+  pub(crate) fn observe(&self, value: f64) {
+    let mut state = self.state.lock().unwrap();
+
+    if let Some(i) = self.buckets.iter().position(|bucket| value <= *bucket) {
+      state.counts[i] += 1;
+    }
+
+    state.count += 1;
+    state.sum += value;
+  }
+
+  pub(crate) fn observe_duration(&self, duration: Duration) {
+    self.observe(duration.as_secs_f64());
+  }
+}
+
+/// Indexer metrics, served in the Prometheus text format at `/metrics` if a
+/// metrics port is configured.
+pub(crate) struct Metrics {
+  pub(crate) bitcoind_height: Gauge,
+  pub(crate) block_commit_duration: Histogram,
+  pub(crate) block_fetch_duration: Histogram,
+  pub(crate) block_index_duration: Histogram,
+  pub(crate) commit_duration: Histogram,
+  pub(crate) flush_write_bytes: Gauge,
+  pub(crate) indexed_height: Gauge,
+  pub(crate) inscriptions_per_block: Histogram,
+  pub(crate) memtable_bytes: Gauge,
+  pub(crate) reorgs: Counter,
+  pub(crate) savepoint_duration: Histogram,
+  pub(crate) transfers_written: Counter,
+  pub(crate) wal_bytes: Gauge,
+}
+
+impl Default for Metrics {
+  fn default() -> Self {
+    Self {
+      bitcoind_height: Gauge::default(),
+      block_commit_duration: Histogram::new(DURATION_BUCKETS),
+      block_fetch_duration: Histogram::new(DURATION_BUCKETS),
+      block_index_duration: Histogram::new(DURATION_BUCKETS),
+      commit_duration: Histogram::new(DURATION_BUCKETS),
+      flush_write_bytes: Gauge::default(),
+      indexed_height: Gauge::default(),
+      inscriptions_per_block: Histogram::new(INSCRIPTION_BUCKETS),
+      memtable_bytes: Gauge::default(),
+      reorgs: Counter::default(),
+      savepoint_duration: Histogram::new(DURATION_BUCKETS),
+      transfers_written: Counter::default(),
+      wal_bytes: Gauge::default(),
+    }
+  }
+}
+
+impl Metrics {
+  pub(crate) async fn serve(self: Arc<Self>, address: SocketAddr) -> Result {
+    let router = Router::new()
+      .route("/metrics", get(Self::metrics))
+      .with_state(self);
+
+    let handle = axum_server::Handle::new();
+
+    LISTENERS.lock().unwrap().push(handle.clone());
+
+    log::info!("Serving metrics on {address}");
+
+    axum_server::bind(address)
+      .handle(handle)
+      .serve(router.into_make_service())
+      .await?;
+
+    Ok(())
+  }
+
+  async fn metrics(State(metrics): State<Arc<Self>>) -> impl IntoResponse {
+    (
+      [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+      metrics.render(),
+    )
+  }
+
+  fn render(&self) -> String {
+    let mut out = String::new();
+
+    Self::gauge(
+      &mut out,
+      "ord_indexed_height",
+      "Height of the last indexed block.",
+      self.indexed_height.get(),
+    );
+    Self::gauge(
+      &mut out,
+      "ord_bitcoind_height",
+      "Height of bitcoind's best block when last checked.",
+      self.bitcoind_height.get(),
+    );
+    Self::gauge(
+      &mut out,
+      "ord_blocks_behind",
+      "Blocks bitcoind is ahead of the index.",
+      (self.bitcoind_height.get() - self.indexed_height.get()).max(0.0),
+    );
+    Self::histogram(
+      &mut out,
+      "ord_block_fetch_duration_seconds",
+      "Time spent waiting for a block to be fetched.",
+      &self.block_fetch_duration,
+    );
+    Self::histogram(
+      &mut out,
+      "ord_block_index_duration_seconds",
+      "Time spent indexing a block.",
+      &self.block_index_duration,
+    );
+    Self::histogram(
+      &mut out,
+      "ord_block_commit_duration_seconds",
+      "Time spent committing and creating savepoints after a block.",
+      &self.block_commit_duration,
+    );
+    Self::histogram(
+      &mut out,
+      "ord_commit_duration_seconds",
+      "Time spent writing cached outputs and flushing the index.",
+      &self.commit_duration,
+    );
+    Self::histogram(
+      &mut out,
+      "ord_savepoint_duration_seconds",
+      "Time spent creating a savepoint.",
+      &self.savepoint_duration,
+    );
+    Self::histogram(
+      &mut out,
+      "ord_inscriptions_per_block",
+      "Inscriptions created per indexed block.",
+      &self.inscriptions_per_block,
+    );
+    Self::counter(
+      &mut out,
+      "ord_transfers_written_total",
+      "Inscription transfers written to the index.",
+      self.transfers_written.get(),
+    );
+    Self::counter(
+      &mut out,
+      "ord_reorgs_total",
+      "Reorgs rolled back.",
+      self.reorgs.get(),
+    );
+    Self::gauge(
+      &mut out,
+      "ord_rocksdb_memtable_bytes",
+      "Size of all RocksDB memtables.",
+      self.memtable_bytes.get(),
+    );
+    Self::gauge(
+      &mut out,
+      "ord_rocksdb_flush_write_bytes",
+      "Bytes written by RocksDB flushes since the index was opened.",
+      self.flush_write_bytes.get(),
+    );
+    Self::gauge(
+      &mut out,
+      "ord_rocksdb_wal_bytes",
+      "Bytes written to the RocksDB WAL since the index was opened.",
+      self.wal_bytes.get(),
+    );
+
+    out
+  }
+
+  fn header(out: &mut String, name: &str, help: &str, kind: &str) {
+    writeln!(out, "# HELP {name} {help}").unwrap();
+    writeln!(out, "# TYPE {name} {kind}").unwrap();
+  }
+
+  fn counter(out: &mut String, name: &str, help: &str, value: u64) {
+    Self::header(out, name, help, "counter");
+    writeln!(out, "{name} {value}").unwrap();
+  }
+
+  fn gauge(out: &mut String, name: &str, help: &str, value: f64) {
+    Self::header(out, name, help, "gauge");
+    writeln!(out, "{name} {value}").unwrap();
+  }
+
+  fn histogram(out: &mut String, name: &str, help: &str, histogram: &Histogram) {
+    Self::header(out, name, help, "histogram");
+
+    let state = histogram.state.lock().unwrap();
+
+    let mut cumulative = 0;
+    for (bucket, count) in histogram.buckets.iter().zip(&state.counts) {
+      cumulative += count;
+      writeln!(out, "{name}_bucket{{le=\"{bucket}\"}} {cumulative}").unwrap();
+    }
+
+    writeln!(out, "{name}_bucket{{le=\"+Inf\"}} {}", state.count).unwrap();
+    writeln!(out, "{name}_sum {}", state.sum).unwrap();
+    writeln!(out, "{name}_count {}", state.count).unwrap();
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn metrics_are_rendered_in_the_prometheus_text_format() {
+    let metrics = Metrics::default();
+
+    metrics.indexed_height.set(5.0);
+    metrics.bitcoind_height.set(7.0);
+    metrics.reorgs.inc();
+
+    for value in [0.0, 0.25, 0.25, 1000.0] {
+      metrics.savepoint_duration.observe(value);
+    }
+
+    let out = metrics.render();
+
+    assert!(out.starts_with(
+      "# HELP ord_indexed_height Height of the last indexed block.
+# TYPE ord_indexed_height gauge
+ord_indexed_height 5
+# HELP ord_bitcoind_height Height of bitcoind's best block when last checked.
+# TYPE ord_bitcoind_height gauge
+ord_bitcoind_height 7
+# HELP ord_blocks_behind Blocks bitcoind is ahead of the index.
+# TYPE ord_blocks_behind gauge
+ord_blocks_behind 2
+"
+    ));
+
+    assert!(
+      out.contains(
+        "# HELP ord_savepoint_duration_seconds Time spent creating a savepoint.
+# TYPE ord_savepoint_duration_seconds histogram
+ord_savepoint_duration_seconds_bucket{le=\"0.005\"} 1
+ord_savepoint_duration_seconds_bucket{le=\"0.01\"} 1
+ord_savepoint_duration_seconds_bucket{le=\"0.025\"} 1
+ord_savepoint_duration_seconds_bucket{le=\"0.05\"} 1
+ord_savepoint_duration_seconds_bucket{le=\"0.1\"} 1
+ord_savepoint_duration_seconds_bucket{le=\"0.25\"} 3
+ord_savepoint_duration_seconds_bucket{le=\"0.5\"} 3
+ord_savepoint_duration_seconds_bucket{le=\"1\"} 3
+ord_savepoint_duration_seconds_bucket{le=\"2.5\"} 3
+ord_savepoint_duration_seconds_bucket{le=\"5\"} 3
+ord_savepoint_duration_seconds_bucket{le=\"10\"} 3
+ord_savepoint_duration_seconds_bucket{le=\"30\"} 3
+ord_savepoint_duration_seconds_bucket{le=\"60\"} 3
+ord_savepoint_duration_seconds_bucket{le=\"300\"} 3
+ord_savepoint_duration_seconds_bucket{le=\"+Inf\"} 4
+ord_savepoint_duration_seconds_sum 1000.5
+ord_savepoint_duration_seconds_count 4
+"
+      ),
+      "{out}",
+    );
+
+    assert!(out.contains(
+      "# HELP ord_reorgs_total Reorgs rolled back.
+# TYPE ord_reorgs_total counter
+ord_reorgs_total 1
+"
+    ));
+  }
+}
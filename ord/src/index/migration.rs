@@ -0,0 +1,654 @@
+use {
+  super::*,
+  rocksdb::{ColumnFamily, WriteBatch},
+};
+
+/// Writes are applied in batches of about this many bytes, so a migration may
+/// be interrupted halfway and must be safe to run again.
+const BATCH_SIZE: usize = 64 * 1024 * 1024;
+
+/// Upgrades an index from schema version `from` to `to`.
+pub(crate) struct Migration {
+  description: &'static str,
+  from: u64,
+  run: fn(&mut MigrationContext) -> Result,
+  to: u64,
+}
+
+/// Every migration, in order. Bump `SCHEMA_VERSION` and add a migration here
+/// when changing the format of a column family.
+const MIGRATIONS: &[Migration] = &[
+  Migration {
+    description: "record the transfer limits the index was built with",
+    from: 99100030,
+    run: record_default_tx_limits,
+    to: 99100031,
+  },
+  Migration {
+    description: "record the height the index was last committed at",
+    from: 99100031,
+    run: record_committed_height,
+    to: 99100032,
+  },
+  Migration {
+    description: "prefix inscription info records with their format version",
+    from: 99100032,
+    run: version_inscription_info,
+    to: 99100033,
+  },
+  Migration {
+    description: "index transfers by the sequence number of their inscription",
+    from: 99100033,
+    run: index_transfers_by_inscription,
+    to: 99100034,
+  },
+];
+
+fn record_default_tx_limits(context: &mut MigrationContext) -> Result {
+  context.put(
+    "statistic_to_count",
+    &Statistic::TxLimits.key().to_be_bytes(),
+    &serde_json::to_vec(&BTreeMap::from([("default".to_string(), 2i16)]))?,
+  )
+}
+
+/// Older versions recorded no committed height, so assume the index was shut
+/// down cleanly and every block is committed.
+fn record_committed_height(context: &mut MigrationContext) -> Result {
+  let height_to_block_header = context.cf("height_to_block_header")?;
+
+  let block_count = context
+    .db
+    .iterator_cf(height_to_block_header, IteratorMode::End)
+    .next()
+    .transpose()?
+    .map(|(height, _header)| u32::from_be_bytes((*height).try_into().unwrap()) + 1)
+    .unwrap_or(0);
+
+  context.put(
+    "statistic_to_count",
+    &Statistic::CommittedHeight.key().to_be_bytes(),
+    &block_count.to_be_bytes(),
+  )
+}
+
+/// Marks existing `ord_inscription_info` records as the legacy format, which
+/// lacks most envelope fields. The last migrated key is recorded with every
+/// record, so an interrupted run resumes after it instead of prefixing records
+/// twice.
+fn version_inscription_info(context: &mut MigrationContext) -> Result {
+  let db = context.db;
+  let ord_inscription_info = context.cf("ord_inscription_info")?;
+  let statistic_to_count = context.cf("statistic_to_count")?;
+  let progress_key = Statistic::MigrationProgress.key().to_be_bytes();
+
+  let resume = db.get_cf(statistic_to_count, progress_key)?;
+
+  let mut iterator = db.raw_iterator_cf(ord_inscription_info);
+
+  match &resume {
+    Some(last) => {
+      iterator.seek(last);
+      if iterator.key() == Some(last.as_slice()) {
+        iterator.next();
+      }
+    }
+    None => iterator.seek_to_first(),
+  }
+
+  while let (Some(key), Some(value)) = (iterator.key(), iterator.value()) {
+    context.put(
+      "ord_inscription_info",
+      key,
+      &[&[InscriptionInfoEntry::LEGACY_VERSION], value].concat(),
+    )?;
+    context.put("statistic_to_count", &progress_key, key)?;
+    iterator.next();
+  }
+
+  iterator.status()?;
+
+  context.delete("statistic_to_count", &progress_key)
+}
+
+/// Adds a `sequence_number_to_transfers` key for every `ord_transfers` row.
+/// Running it again rewrites the same keys.
+fn index_transfers_by_inscription(context: &mut MigrationContext) -> Result {
+  let inscription_id_to_sequence_number = context.cf("inscription_id_to_sequence_number")?;
+
+  context.for_each("ord_transfers", |context, key, value| {
+    let sequence_number = context
+      .db
+      .get_cf(inscription_id_to_sequence_number, &value[..36])?
+      .ok_or_else(|| anyhow!("transfer {} is of an unknown inscription", hex::encode(key)))?;
+
+    context.put(
+      "sequence_number_to_transfers",
+      &[sequence_number.as_slice(), key].concat(),
+      &[],
+    )
+  })
+}
+
+/// In a dry run, `puts` and `deletes` are per-step estimates, since every step
+/// runs against the unmigrated index.
+#[derive(Serialize, Debug)]
+pub struct MigrationReport {
+  pub deletes: u64,
+  pub description: &'static str,
+  pub from: u64,
+  pub puts: u64,
+  pub to: u64,
+}
+
+#[derive(Serialize, Debug)]
+pub struct Migrations {
+  pub dry_run: bool,
+  pub migrations: Vec<MigrationReport>,
+  pub schema_version: u64,
+}
+
+/// Collects the writes of a migration. In a dry run they are counted and
+/// discarded.
+pub(crate) struct MigrationContext<'a> {
+  batch: WriteBatch,
+  db: &'a Database,
+  deletes: u64,
+  dry_run: bool,
+  puts: u64,
+  write_options: rocksdb::WriteOptions,
+}
+
+impl<'a> MigrationContext<'a> {
+  fn cf(&self, name: &str) -> Result<&'a ColumnFamily> {
+    self
+      .db
+      .cf_handle(name)
+      .ok_or_else(|| anyhow!("Failed to open column family '{name}'"))
+  }
+
+  pub(crate) fn put(&mut self, cf: &str, key: &[u8], value: &[u8]) -> Result {
+    if !self.dry_run {
+      self.batch.put_cf(self.cf(cf)?, key, value);
+    }
+    self.puts += 1;
+    self.write_if_full()
+  }
+
+  pub(crate) fn delete(&mut self, cf: &str, key: &[u8]) -> Result {
+    if !self.dry_run {
+      self.batch.delete_cf(self.cf(cf)?, key);
+    }
+    self.deletes += 1;
+    self.write_if_full()
+  }
+
+  /// Calls `f` with every entry of `cf`, showing a progress bar.
+  pub(crate) fn for_each(
+    &mut self,
+    cf: &str,
+    mut f: impl FnMut(&mut Self, &[u8], &[u8]) -> Result,
+  ) -> Result {
+    let db = self.db;
+    let handle = self.cf(cf)?;
+
+    let estimated_keys = db
+      .property_int_value_cf(handle, "rocksdb.estimate-num-keys")?
+      .unwrap_or_default();
+
+    let progress_bar = if log_enabled!(log::Level::Info) {
+      None
+    } else {
+      let progress_bar = ProgressBar::new(estimated_keys);
+      progress_bar.set_style(
+        ProgressStyle::with_template(&format!("[migrating {cf}] {{wide_bar}} {{pos}}/~{{len}}"))
+          .unwrap(),
+      );
+      Some(progress_bar)
+    };
+
+    for (i, entry) in db.iterator_cf(handle, IteratorMode::Start).enumerate() {
+      let (key, value) = entry?;
+      f(self, &key, &value)?;
+
+      if let Some(progress_bar) = &progress_bar {
+        progress_bar.inc(1);
+      } else if i % 1_000_000 == 0 {
+        log::info!("Migrated {i} of about {estimated_keys} entries of {cf}");
+      }
+    }
+
+    if let Some(progress_bar) = progress_bar {
+      progress_bar.finish_and_clear();
+    }
+
+    Ok(())
+  }
+
+  fn write_if_full(&mut self) -> Result {
+    if self.batch.size_in_bytes() >= BATCH_SIZE {
+      self.write()?;
+    }
+    Ok(())
+  }
+
+  fn write(&mut self) -> Result {
+    if !self.dry_run {
+      self
+        .db
+        .write_opt(std::mem::take(&mut self.batch), &self.write_options)?;
+    }
+    Ok(())
+  }
+}
+
+/// Returns the migrations that upgrade an index at `schema_version` to
+/// `SCHEMA_VERSION`.
+fn plan(schema_version: u64) -> Result<Vec<&'static Migration>> {
+  ensure!(
+    schema_version <= SCHEMA_VERSION,
+    "Index schema version {schema_version} is newer than this version's {SCHEMA_VERSION}"
+  );
+
+  let mut version = schema_version;
+  let mut plan = Vec::new();
+
+  while version != SCHEMA_VERSION {
+    let Some(migration) = MIGRATIONS.iter().find(|migration| migration.from == version) else {
+      bail!(
+        "Incompatible index schema version: expected {SCHEMA_VERSION}, found {schema_version}, and there is no migration from {version}"
+      );
+    };
+
+    plan.push(migration);
+    version = migration.to;
+  }
+
+  Ok(plan)
+}
+
+/// Upgrades the index in `db` from `schema_version` to `SCHEMA_VERSION`. The
+/// undo log and savepoints hold data in the old format, so they are dropped
+/// once any migration has run.
+pub(crate) fn migrate(
+  db: &Database,
+  index_path: &Path,
+  schema_version: u64,
+  dry_run: bool,
+) -> Result<Migrations> {
+  let plan = plan(schema_version)?;
+
+  let mut migrations = Vec::new();
+
+  for (i, migration) in plan.iter().enumerate() {
+    println!(
+      "{}[{}/{}] Migrating index schema from {} to {}: {}",
+      if dry_run { "(dry run) " } else { "" },
+      i + 1,
+      plan.len(),
+      migration.from,
+      migration.to,
+      migration.description,
+    );
+
+    let mut context = MigrationContext {
+      batch: WriteBatch::default(),
+      db,
+      deletes: 0,
+      dry_run,
+      puts: 0,
+      write_options: {
+        let mut write_options = rocksdb::WriteOptions::default();
+        write_options.disable_wal(true);
+        write_options
+      },
+    };
+
+    (migration.run)(&mut context)
+      .with_context(|| format!("failed to migrate index schema from {}", migration.from))?;
+
+    let report = MigrationReport {
+      deletes: context.deletes,
+      description: migration.description,
+      from: migration.from,
+      puts: context.puts,
+      to: migration.to,
+    };
+
+    if !dry_run {
+      let statistic_to_count = context.cf("statistic_to_count")?;
+      context.batch.put_cf(
+        statistic_to_count,
+        Statistic::Schema.key().to_be_bytes(),
+        migration.to.to_be_bytes(),
+      );
+      context.write()?;
+      db.flush_all()?;
+    }
+
+    if dry_run {
+      // earlier steps aren't written, so each step sees the unmigrated index
+      println!(
+        "Migration to {} would write an estimated {} puts and {} deletes, counted against the unmigrated index",
+        report.to, report.puts, report.deletes,
+      );
+    } else {
+      println!(
+        "Migration to {} wrote {} puts and {} deletes",
+        report.to, report.puts, report.deletes,
+      );
+    }
+
+    migrations.push(report);
+  }
+
+  if !dry_run && !plan.is_empty() {
+    let undo_log = db
+      .cf_handle("undo_log")
+      .ok_or_else(|| anyhow!("Failed to open column family 'undo_log'"))?;
+    db.delete_range_cf(undo_log, vec![0; 4], vec![u8::MAX; 5])?;
+    db.flush_all()?;
+
+    let backup = index_path.join("backup");
+    if backup.exists() {
+      fs::remove_dir_all(&backup)?;
+    }
+
+    println!("Dropped undo logs and savepoints taken before the migration");
+  }
+
+  Ok(Migrations {
+    dry_run,
+    migrations,
+    schema_version,
+  })
+}
+
+/// Opens the index at `settings.index()` and migrates it to `SCHEMA_VERSION`.
+pub(crate) fn run(settings: &Settings, dry_run: bool) -> Result<Migrations> {
+  let path = settings.index();
+
+  ensure!(
+    path.join("index.db").exists(),
+    "no index found at {}",
+    path.display()
+  );
+
+  let db = Database::open(path.join("index.db"), settings.rocksdb())?;
+
+  let statistic_to_count = db
+    .cf_handle("statistic_to_count")
+    .ok_or_else(|| anyhow!("Failed to open column family 'statistic_to_count'"))?;
+
+  let schema_version = db
+    .get_cf(statistic_to_count, Statistic::Schema.key().to_be_bytes())?
+    .map(|version| u64::from_be_bytes(version.try_into().unwrap()))
+    .ok_or_else(|| anyhow!("index has no schema version"))?;
+
+  migrate(&db, path, schema_version, dry_run)
+}
+
+#[cfg(test)]
+mod tests {
+  use {
+    super::*,
+    crate::{index::testing::Context, options::Options},
+  };
+
+  const FIRST_SCHEMA_VERSION: u64 = 99100030;
+
+  fn inscription_id(n: u8) -> InscriptionId {
+    InscriptionId {
+      txid: Txid::from_byte_array([n; 32]),
+      index: 0,
+    }
+  }
+
+  /// An `ord_inscription_info` record as written before records were
+  /// versioned
+  fn unversioned_inscription_info(number: i32) -> Vec<u8> {
+    [
+      number.to_be_bytes().to_vec(),
+      vec![0],
+      vec![0; 36],
+      vec![1],
+      2u32.to_be_bytes().to_vec(),
+      b"{}".to_vec(),
+      0u32.to_be_bytes().to_vec(),
+      0u32.to_be_bytes().to_vec(),
+    ]
+    .concat()
+  }
+
+  fn schema_version(db: &Database) -> u64 {
+    u64::from_be_bytes(
+      db.get_cf(
+        db.cf_handle("statistic_to_count").unwrap(),
+        Statistic::Schema.key().to_be_bytes(),
+      )
+      .unwrap()
+      .unwrap()
+      .try_into()
+      .unwrap(),
+    )
+  }
+
+  /// Rewrites the statistics and records of a new index to how version
+  /// `FIRST_SCHEMA_VERSION` left them, with one tracked inscription that was
+  /// transferred once, an undo log entry and a savepoint.
+  fn downgrade(settings: &Settings) {
+    let db = Database::open(settings.index().join("index.db"), settings.rocksdb()).unwrap();
+    let cf = |name| db.cf_handle(name).unwrap();
+
+    let statistic_to_count = cf("statistic_to_count");
+    db.put_cf(
+      statistic_to_count,
+      Statistic::Schema.key().to_be_bytes(),
+      FIRST_SCHEMA_VERSION.to_be_bytes(),
+    )
+    .unwrap();
+    for statistic in [Statistic::TxLimits, Statistic::CommittedHeight] {
+      db.delete_cf(statistic_to_count, statistic.key().to_be_bytes())
+        .unwrap();
+    }
+
+    let id = inscription_id(1).store();
+
+    db.put_cf(
+      cf("ord_inscription_info"),
+      &id,
+      unversioned_inscription_info(0),
+    )
+    .unwrap();
+
+    db.put_cf(
+      cf("inscription_id_to_sequence_number"),
+      &id,
+      7u32.to_be_bytes(),
+    )
+    .unwrap();
+    db.put_cf(
+      cf("ord_transfers"),
+      [1u32.to_be_bytes(), 0u32.to_be_bytes()].concat(),
+      [id.as_slice(), &[0; 80]].concat(),
+    )
+    .unwrap();
+
+    db.put_cf(cf("undo_log"), [0, 0, 0, 1], [0]).unwrap();
+    fs::create_dir_all(settings.index().join("backup")).unwrap();
+
+    db.flush_all().unwrap();
+  }
+
+  #[test]
+  fn migrations_form_a_chain_to_the_current_version() {
+    let migrations = plan(FIRST_SCHEMA_VERSION).unwrap();
+
+    assert_eq!(migrations.len(), MIGRATIONS.len());
+    assert_eq!(migrations[0].from, FIRST_SCHEMA_VERSION);
+    assert_eq!(migrations.last().unwrap().to, SCHEMA_VERSION);
+
+    for pair in migrations.windows(2) {
+      assert_eq!(pair[0].to, pair[1].from);
+    }
+
+    assert!(plan(SCHEMA_VERSION).unwrap().is_empty());
+  }
+
+  #[test]
+  fn unknown_schema_versions_are_not_migrated() {
+    assert_eq!(
+      plan(SCHEMA_VERSION + 1).err().unwrap().to_string(),
+      format!(
+        "Index schema version {} is newer than this version's {SCHEMA_VERSION}",
+        SCHEMA_VERSION + 1
+      ),
+    );
+
+    assert_eq!(
+      plan(FIRST_SCHEMA_VERSION - 1).err().unwrap().to_string(),
+      format!(
+        "Incompatible index schema version: expected {SCHEMA_VERSION}, found {0}, and there is no migration from {0}",
+        FIRST_SCHEMA_VERSION - 1
+      ),
+    );
+  }
+
+  #[test]
+  fn indexes_are_migrated_from_the_first_schema_version() {
+    let mut context = Context::new(Options::default());
+
+    context.mine_blocks(3);
+
+    let Context {
+      core,
+      index,
+      tempdir,
+    } = context;
+
+    index.shut_down().unwrap();
+
+    let settings = Context::settings(&tempdir, Options::default());
+
+    downgrade(&settings);
+
+    let dry_run = run(&settings, true).unwrap();
+
+    assert_eq!(
+      dry_run
+        .migrations
+        .iter()
+        .map(|report| (report.from, report.puts, report.deletes))
+        .collect::<Vec<(u64, u64, u64)>>(),
+      [
+        (99100030, 1, 0),
+        (99100031, 1, 0),
+        (99100032, 2, 1),
+        (99100033, 1, 0),
+      ],
+    );
+
+    assert_eq!(
+      schema_version(
+        &Database::open(settings.index().join("index.db"), settings.rocksdb()).unwrap()
+      ),
+      FIRST_SCHEMA_VERSION,
+    );
+
+    let index = Context::open(&core, &tempdir, Options::default()).unwrap();
+
+    let context = Context {
+      core,
+      index,
+      tempdir,
+    };
+
+    assert_eq!(schema_version(&context.index.db), SCHEMA_VERSION);
+
+    assert_eq!(context.index.committed_height().unwrap(), 4);
+
+    assert_eq!(
+      context.get(
+        "statistic_to_count",
+        &Statistic::TxLimits.key().to_be_bytes()
+      ),
+      Some(br#"{"default":2}"#.to_vec()),
+    );
+
+    let info = context
+      .get("ord_inscription_info", &inscription_id(1).store())
+      .unwrap();
+
+    assert_eq!(info[0], InscriptionInfoEntry::LEGACY_VERSION);
+    assert_eq!(info[1..], unversioned_inscription_info(0));
+    assert!(InscriptionInfoEntry::load(info).unwrap().is_json);
+
+    assert_eq!(
+      context.get(
+        "sequence_number_to_transfers",
+        &[7u32.to_be_bytes(), 1u32.to_be_bytes(), 0u32.to_be_bytes()].concat()
+      ),
+      Some(Vec::new()),
+    );
+
+    assert_eq!(context.get("undo_log", &[0, 0, 0, 1]), None);
+    assert!(!context.tempdir.path().join("regtest/dbs/backup").exists());
+  }
+
+  #[test]
+  fn interrupted_inscription_info_migrations_resume() {
+    let tempdir = tempfile::TempDir::new().unwrap();
+    let settings = Context::settings(&tempdir, Options::default());
+    let path = settings.index().join("index.db");
+
+    let db = Database::open(path, settings.rocksdb()).unwrap();
+    let ord_inscription_info = db.cf_handle("ord_inscription_info").unwrap();
+
+    // The first record was migrated before the interruption
+    let migrated = [
+      vec![InscriptionInfoEntry::LEGACY_VERSION],
+      unversioned_inscription_info(1),
+    ]
+    .concat();
+
+    db.put_cf(ord_inscription_info, inscription_id(1).store(), &migrated)
+      .unwrap();
+    db.put_cf(
+      ord_inscription_info,
+      inscription_id(2).store(),
+      unversioned_inscription_info(2),
+    )
+    .unwrap();
+    db.put_cf(
+      db.cf_handle("statistic_to_count").unwrap(),
+      Statistic::MigrationProgress.key().to_be_bytes(),
+      inscription_id(1).store(),
+    )
+    .unwrap();
+
+    let migrations = migrate(&db, settings.index(), 99100032, false).unwrap();
+
+    assert_eq!(migrations.migrations[0].puts, 2);
+
+    for (n, number) in [(1, 1), (2, 2)] {
+      let info = db
+        .get_cf(ord_inscription_info, inscription_id(n).store())
+        .unwrap()
+        .unwrap();
+
+      assert_eq!(
+        InscriptionInfoEntry::load(info).unwrap().inscription_number,
+        number
+      );
+    }
+
+    assert_eq!(
+      db.get_cf(
+        db.cf_handle("statistic_to_count").unwrap(),
+        Statistic::MigrationProgress.key().to_be_bytes(),
+      )
+      .unwrap(),
+      None,
+    );
+  }
+}
@@ -28,17 +28,21 @@ impl Reorg {
     match index.block_hash(height.checked_sub(1))? {
       Some(index_prev_blockhash) if index_prev_blockhash == bitcoind_prev_blockhash => Ok(()),
       Some(index_prev_blockhash) if index_prev_blockhash != bitcoind_prev_blockhash => {
-        let savepoint_interval = u32::try_from(index.settings.savepoint_interval()).unwrap();
-        let max_savepoints = u32::try_from(index.settings.max_savepoints()).unwrap();
-        let max_recoverable_reorg_depth =
-          (max_savepoints - 1) * savepoint_interval + height % savepoint_interval;
+        let max_recoverable_reorg_depth = match index.settings.undo_log_depth() {
+          // A reorg of depth `depth` rolls back `depth - 1` blocks
+          Some(undo_log_depth) => u32::try_from(undo_log_depth).unwrap() + 2,
+          None => {
+            let savepoint_interval = u32::try_from(index.settings.savepoint_interval()).unwrap();
+            let max_savepoints = u32::try_from(index.settings.max_savepoints()).unwrap();
+            (max_savepoints - 1) * savepoint_interval + height % savepoint_interval
+          }
+        };
 
         for depth in 1..max_recoverable_reorg_depth {
           let index_block_hash = index.block_hash(height.checked_sub(depth))?;
           let bitcoind_block_hash = index
-            .client
-            .get_block_hash(u64::from(height.saturating_sub(depth)))
-            .into_option()?;
+            .block_source
+            .block_hash(height.saturating_sub(depth))?;
 
           if index_block_hash == bitcoind_block_hash {
             return Err(anyhow!(reorg::Error::Recoverable { height, depth }));
@@ -52,17 +56,21 @@ impl Reorg {
   }
 
   pub(crate) fn is_savepoint_required(index: &Index, height: u32) -> Result<bool> {
+    if index.settings.undo_log_depth().is_some() {
+      return Ok(false);
+    }
+
     let height = u64::from(height);
 
     let statistic_to_count = index.db.cf_handle("statistic_to_count")
       .ok_or_else(|| anyhow!("Failed to open column family 'statistic_to_count'"))?;
 
     let last_savepoint_height = index.db
-      .get_cf(statistic_to_count, &Statistic::LastSavepointHeight.key().to_be_bytes())?
+      .get_cf(statistic_to_count, Statistic::LastSavepointHeight.key().to_be_bytes())?
       .map(|last_savepoint_height| u64::from(u32::from_be_bytes(last_savepoint_height.try_into().unwrap())))
       .unwrap_or(0);
 
-    let blocks = index.client.get_blockchain_info()?.headers;
+    let blocks = index.block_source.header_height()?;
 
     let savepoint_interval = u64::try_from(index.settings.savepoint_interval()).unwrap();
     let max_savepoints = u64::try_from(index.settings.max_savepoints()).unwrap();
@@ -82,52 +90,202 @@ impl Reorg {
     Ok(result)
   }
 
-  pub(crate) fn handle_reorg(path: &PathBuf, height: u32, depth: u32) -> Result {
+  pub(crate) fn handle_reorg(index: &mut Index, height: u32, depth: u32) -> Result {
     println!("rolling back database after reorg of depth {depth} at height {height}");
 
-    let backup_opts = BackupEngineOptions::new(&path.join("backup"))?;
+    index.metrics.reorgs.inc();
+
+    if index.settings.undo_log_depth().is_some() {
+      UndoLog::rollback(index, height.saturating_sub(depth) + 1..height)?;
+
+      index.emit_rollback()?;
+
+      println!("successfully rolled back database using undo logs");
+
+      return Ok(());
+    }
+
+    // Hold off db_reader catching up until the restored database has been reopened
+    let _pause = index.reader_gate.pause();
+
+    index.db.close();
+
+    let result = Self::restore_savepoint(&index.path, height.saturating_sub(depth));
+
+    index.db.reopen()?;
+
+    result?;
+
+    index.emit_rollback()?;
+
+    println!(
+      "successfully rolled back database"
+    );
+
+    Ok(())
+  }
+
+  /// Restores the newest savepoint taken at or below `target_height`. Backups
+  /// created before savepoint heights were recorded fall back to the oldest
+  /// backup, otherwise the reorg is unrecoverable if every savepoint is newer.
+  fn restore_savepoint(path: &Path, target_height: u32) -> Result {
+    let backup_opts = BackupEngineOptions::new(path.join("backup"))?;
     let mut backup_engine = BackupEngine::open(&backup_opts, &rocksdb::Env::new()?)?;
 
+    let mut savepoint_heights = Self::load_savepoint_heights(path)?;
+
     let backups = backup_engine.get_backup_info();
-    // get the oldest backup
-    let oldest_backup = backups
+
+    let backup_id = match backups
       .iter()
-      .min_by_key(|backup| backup.backup_id)
-      .ok_or_else(|| anyhow!("No backups found"))?;
-    let backup_id = oldest_backup.backup_id;
+      .filter(|backup| {
+        savepoint_heights
+          .get(&backup.backup_id)
+          .is_some_and(|height| *height <= target_height)
+      })
+      .max_by_key(|backup| backup.backup_id)
+    {
+      Some(backup) => backup.backup_id,
+      None if backups
+        .iter()
+        .all(|backup| !savepoint_heights.contains_key(&backup.backup_id)) =>
+      {
+        log::warn!("No savepoint heights recorded, using oldest backup");
+
+        backups
+          .iter()
+          .min_by_key(|backup| backup.backup_id)
+          .ok_or_else(|| anyhow!("No backups found"))?
+          .backup_id
+      }
+      None => {
+        log::error!("No savepoint recorded at or below height {target_height}");
+        return Err(anyhow!(reorg::Error::Unrecoverable));
+      }
+    };
 
-    println!("restoring backup with id {}", backup_id);
+    println!(
+      "restoring backup with id {} at height {}",
+      backup_id,
+      savepoint_heights
+        .get(&backup_id)
+        .map(|height| height.to_string())
+        .unwrap_or_else(|| "unknown".into())
+    );
     let db_dir = path.join("index.db");
     let wal_dir = path.join("index.db");
     let opts = rocksdb::backup::RestoreOptions::default();
 
     backup_engine.restore_from_backup(db_dir, wal_dir, &opts, backup_id)?;
 
-    println!(
-      "successfully rolled back database"
-    );
+    // Newer backups were taken on the abandoned chain and must never be restored
+    let abandoned = backups
+      .iter()
+      .map(|backup| backup.backup_id)
+      .filter(|id| *id > backup_id)
+      .collect::<Vec<u32>>();
+
+    drop(backup_engine);
+
+    Self::delete_backups(path, &backup_opts, &abandoned, backups.len() - abandoned.len())?;
+
+    savepoint_heights.retain(|id, _| *id <= backup_id);
+    Self::store_savepoint_heights(path, &savepoint_heights)?;
+
+    Ok(())
+  }
+
+  /// Deletes the backups in `backup_ids`, keeping `remaining` backups.
+  /// rust-rocksdb has no binding for `DeleteBackup`, so their metadata files
+  /// are removed while the engine is closed, like `DeleteBackup` does, and
+  /// their files are garbage collected by purging the reopened engine.
+  fn delete_backups(
+    path: &Path,
+    backup_opts: &BackupEngineOptions,
+    backup_ids: &[u32],
+    remaining: usize,
+  ) -> Result {
+    if backup_ids.is_empty() {
+      return Ok(());
+    }
+
+    for backup_id in backup_ids {
+      let meta = path.join("backup").join("meta").join(backup_id.to_string());
+
+      fs::remove_file(&meta)
+        .with_context(|| format!("failed to delete backup {backup_id} at {}", meta.display()))?;
+    }
+
+    let mut backup_engine = BackupEngine::open(backup_opts, &rocksdb::Env::new()?)?;
+
+    backup_engine.purge_old_backups(remaining)?;
+
+    Ok(())
+  }
+
+  fn savepoint_heights_path(path: &Path) -> PathBuf {
+    path.join("backup").join("savepoint_heights.json")
+  }
+
+  /// Heights of the savepoints, keyed by the id of their `BackupEngine` backup.
+  fn load_savepoint_heights(path: &Path) -> Result<BTreeMap<u32, u32>> {
+    let savepoint_heights_path = Self::savepoint_heights_path(path);
+
+    if !savepoint_heights_path.exists() {
+      return Ok(BTreeMap::new());
+    }
+
+    serde_json::from_reader(File::open(&savepoint_heights_path)?).context(anyhow!(
+      "failed to read savepoint heights from `{}`",
+      savepoint_heights_path.display()
+    ))
+  }
+
+  fn store_savepoint_heights(path: &Path, savepoint_heights: &BTreeMap<u32, u32>) -> Result {
+    let savepoint_heights_path = Self::savepoint_heights_path(path);
+    let tmp_path = savepoint_heights_path.with_extension("json.tmp");
+
+    fs::write(&tmp_path, serde_json::to_vec(savepoint_heights)?)?;
+    fs::rename(&tmp_path, &savepoint_heights_path)?;
 
     Ok(())
   }
 
   pub(crate) fn update_savepoints(index: &Index, height: u32) -> Result {
     if Self::is_savepoint_required(index, height)? {
-      let backup_opts = BackupEngineOptions::new(&index.path.join("backup"))?;
+      let backup_opts = BackupEngineOptions::new(index.path.join("backup"))?;
       let mut backup_engine = BackupEngine::open(&backup_opts, &rocksdb::Env::new()?)?;
 
       backup_engine.purge_old_backups(index.settings.max_savepoints() - 1)?;
 
       println!("Creating savepoint at height {}", height);
 
+      let start = Instant::now();
+
       backup_engine.create_new_backup(&index.db)?;
 
+      index.metrics.savepoint_duration.observe_duration(start.elapsed());
+
+      let backups = backup_engine.get_backup_info();
+
+      let backup_id = backups
+        .iter()
+        .map(|backup| backup.backup_id)
+        .max()
+        .ok_or_else(|| anyhow!("Savepoint backup not found after creating it"))?;
+
+      let mut savepoint_heights = Self::load_savepoint_heights(&index.path)?;
+      savepoint_heights.insert(backup_id, height);
+      savepoint_heights.retain(|id, _| backups.iter().any(|backup| backup.backup_id == *id));
+      Self::store_savepoint_heights(&index.path, &savepoint_heights)?;
+
       println!("Savepoint created successfully");
 
       let statistic_to_count = index.db.cf_handle("statistic_to_count")
         .ok_or_else(|| anyhow!("Failed to open column family 'statistic_to_count'"))?;
 
       index.db
-        .put_cf_opt(statistic_to_count, &Statistic::LastSavepointHeight.key().to_be_bytes(), height.to_be_bytes(), &index.write_options)?;
+        .put_cf_opt(statistic_to_count, Statistic::LastSavepointHeight.key().to_be_bytes(), height.to_be_bytes(), &index.write_options)?;
 
       let mut flush_opts = rocksdb::FlushOptions::default();
       flush_opts.set_wait(true);
@@ -138,3 +296,190 @@ impl Reorg {
     Ok(())
   }
 }
+
+#[cfg(test)]
+mod tests {
+  use {
+    super::*,
+    crate::{index::testing::Context, options::Options},
+  };
+
+  fn backup_ids(path: &Path) -> Vec<u32> {
+    BackupEngine::open(
+      &BackupEngineOptions::new(path.join("backup")).unwrap(),
+      &rocksdb::Env::new().unwrap(),
+    )
+    .unwrap()
+    .get_backup_info()
+    .iter()
+    .map(|backup| backup.backup_id)
+    .collect()
+  }
+
+  #[test]
+  fn restoring_a_savepoint_deletes_newer_backups() {
+    let mut context = Context::new(Options::default());
+
+    let path = context.index.path.clone();
+
+    let mut backup_engine = BackupEngine::open(
+      &BackupEngineOptions::new(path.join("backup")).unwrap(),
+      &rocksdb::Env::new().unwrap(),
+    )
+    .unwrap();
+
+    let statistic_to_count = context.index.db.cf_handle("statistic_to_count").unwrap();
+
+    for height in [10u32, 20, 30] {
+      context
+        .index
+        .db
+        .put_cf(
+          statistic_to_count,
+          Statistic::LastSavepointHeight.key().to_be_bytes(),
+          height.to_be_bytes(),
+        )
+        .unwrap();
+
+      backup_engine.create_new_backup(&context.index.db).unwrap();
+    }
+
+    drop(backup_engine);
+
+    Reorg::store_savepoint_heights(&path, &[(1, 10), (2, 20), (3, 30)].into()).unwrap();
+
+    context.index.db.close();
+    Reorg::restore_savepoint(&path, 25).unwrap();
+    context.index.db.reopen().unwrap();
+
+    assert_eq!(
+      context.get(
+        "statistic_to_count",
+        &Statistic::LastSavepointHeight.key().to_be_bytes()
+      ),
+      Some(20u32.to_be_bytes().to_vec()),
+    );
+
+    assert_eq!(backup_ids(&path), [1, 2]);
+
+    assert_eq!(
+      Reorg::load_savepoint_heights(&path).unwrap(),
+      [(1, 10), (2, 20)].into(),
+    );
+  }
+
+  #[test]
+  fn restoring_the_newest_savepoint_keeps_all_backups() {
+    let mut context = Context::new(Options::default());
+
+    let path = context.index.path.clone();
+
+    let mut backup_engine = BackupEngine::open(
+      &BackupEngineOptions::new(path.join("backup")).unwrap(),
+      &rocksdb::Env::new().unwrap(),
+    )
+    .unwrap();
+
+    backup_engine.create_new_backup(&context.index.db).unwrap();
+    backup_engine.create_new_backup(&context.index.db).unwrap();
+
+    drop(backup_engine);
+
+    Reorg::store_savepoint_heights(&path, &[(1, 10), (2, 20)].into()).unwrap();
+
+    context.index.db.close();
+    Reorg::restore_savepoint(&path, 20).unwrap();
+    context.index.db.reopen().unwrap();
+
+    assert_eq!(backup_ids(&path), [1, 2]);
+  }
+
+  #[test]
+  fn restoring_below_every_savepoint_is_unrecoverable() {
+    let mut context = Context::new(Options::default());
+
+    let path = context.index.path.clone();
+
+    let mut backup_engine = BackupEngine::open(
+      &BackupEngineOptions::new(path.join("backup")).unwrap(),
+      &rocksdb::Env::new().unwrap(),
+    )
+    .unwrap();
+
+    backup_engine.create_new_backup(&context.index.db).unwrap();
+    backup_engine.create_new_backup(&context.index.db).unwrap();
+
+    drop(backup_engine);
+
+    Reorg::store_savepoint_heights(&path, &[(1, 10), (2, 20)].into()).unwrap();
+
+    context.index.db.close();
+    let err = Reorg::restore_savepoint(&path, 5).unwrap_err();
+    context.index.db.reopen().unwrap();
+
+    assert_eq!(
+      err.downcast_ref::<reorg::Error>(),
+      Some(&reorg::Error::Unrecoverable),
+    );
+
+    assert_eq!(backup_ids(&path), [1, 2]);
+
+    assert_eq!(
+      Reorg::load_savepoint_heights(&path).unwrap(),
+      [(1, 10), (2, 20)].into(),
+    );
+  }
+
+  #[test]
+  fn restoring_legacy_backups_falls_back_to_the_oldest() {
+    let mut context = Context::new(Options::default());
+
+    let path = context.index.path.clone();
+
+    let mut backup_engine = BackupEngine::open(
+      &BackupEngineOptions::new(path.join("backup")).unwrap(),
+      &rocksdb::Env::new().unwrap(),
+    )
+    .unwrap();
+
+    backup_engine.create_new_backup(&context.index.db).unwrap();
+    backup_engine.create_new_backup(&context.index.db).unwrap();
+
+    drop(backup_engine);
+
+    context.index.db.close();
+    Reorg::restore_savepoint(&path, 5).unwrap();
+    context.index.db.reopen().unwrap();
+
+    assert_eq!(backup_ids(&path), [1]);
+  }
+
+  #[test]
+  fn reorgs_are_rolled_back_to_the_savepoint_below_the_fork() {
+    let options = || Options {
+      savepoint_interval: Some(2),
+      max_savepoints: Some(3),
+      ..default()
+    };
+
+    let mut context = Context::new(options());
+
+    context.mine_blocks(6);
+
+    context.core.invalidate_tip();
+    context.core.invalidate_tip();
+
+    let blocks = context.mine_blocks(3);
+
+    let context = context.reopen(options()).unwrap();
+
+    assert_eq!(context.index.block_count().unwrap(), 8);
+
+    for (height, block) in (5..).zip(&blocks) {
+      assert_eq!(
+        context.index.block_hash(Some(height)).unwrap(),
+        Some(block.block_hash()),
+      );
+    }
+  }
+}
@@ -0,0 +1,507 @@
+use {
+  super::*,
+  bitcoin::hashes::{sha256, Hash, HashEngine},
+  rocksdb::WriteBatch,
+  std::io::{BufReader, BufWriter, Read, Write},
+};
+
+/// Snapshots are a brotli compressed stream of
+/// `MAGIC ‖ header_len(4) ‖ header ‖ entry* ‖ END_OF_ENTRIES ‖ sha256`, where
+/// the header is JSON, every entry is
+/// `cf(1) ‖ key_len(4) ‖ key ‖ value_len(4) ‖ value` and `cf` indexes the
+/// header's column families. The checksum covers everything before it.
+const MAGIC: &[u8; 8] = b"OPISNAP1";
+
+const END_OF_ENTRIES: u8 = u8::MAX;
+
+/// Entries are imported in batches of about this many bytes.
+const IMPORT_BATCH_SIZE: usize = 64 * 1024 * 1024;
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct SnapshotHeader {
+  pub block_hash: BlockHash,
+  pub chain: Chain,
+  pub column_families: Vec<String>,
+  pub height: u32,
+  pub schema_version: u64,
+}
+
+#[derive(Serialize, Debug)]
+pub struct SnapshotInfo {
+  pub checksum: sha256::Hash,
+  pub entries: u64,
+  pub header: SnapshotHeader,
+  pub path: PathBuf,
+}
+
+/// Writes the index at `settings.index()` to `path`. The index must have been
+/// committed at exactly `height`.
+pub(crate) fn export(settings: &Settings, height: u32, path: &Path) -> Result<SnapshotInfo> {
+  let index_path = settings.index().join("index.db");
+
+  ensure!(
+    index_path.exists(),
+    "no index found at {}",
+    index_path.display()
+  );
+
+  let db = Database::open(index_path, settings.rocksdb())?;
+
+  let statistic_to_count = db
+    .cf_handle("statistic_to_count")
+    .ok_or_else(|| anyhow!("Failed to open column family 'statistic_to_count'"))?;
+  let height_to_block_header = db
+    .cf_handle("height_to_block_header")
+    .ok_or_else(|| anyhow!("Failed to open column family 'height_to_block_header'"))?;
+
+  let schema_version = db
+    .get_cf(statistic_to_count, Statistic::Schema.key().to_be_bytes())?
+    .map(|version| u64::from_be_bytes(version.try_into().unwrap()))
+    .ok_or_else(|| anyhow!("index has no schema version"))?;
+
+  ensure!(
+    schema_version == SCHEMA_VERSION,
+    "Incompatible index schema version: expected {SCHEMA_VERSION}, found {schema_version}"
+  );
+
+  let snapshot = db.snapshot();
+
+  let Some((tip_height, tip_header)) = snapshot
+    .iterator_cf(height_to_block_header, IteratorMode::End)
+    .next()
+    .transpose()?
+    .map(|(tip_height, tip_header)| {
+      (
+        u32::from_be_bytes((*tip_height).try_into().unwrap()),
+        Header::load((*tip_header).try_into().unwrap()),
+      )
+    })
+  else {
+    bail!("index has no blocks");
+  };
+
+  ensure!(
+    tip_height == height,
+    "index is committed at height {tip_height}, not {height}; snapshots can only be exported at the committed height"
+  );
+
+  let committed_height = snapshot
+    .get_cf(
+      statistic_to_count,
+      Statistic::CommittedHeight.key().to_be_bytes(),
+    )?
+    .map(|height| u32::from_be_bytes(height.try_into().unwrap()))
+    .unwrap_or(0);
+
+  ensure!(
+    committed_height == height + 1,
+    "index has uncommitted blocks above height {}, open it with `ord index update` to roll them back",
+    committed_height.saturating_sub(1),
+  );
+
+  let header = SnapshotHeader {
+    block_hash: tip_header.block_hash(),
+    chain: settings.chain(),
+    column_families: COLUMN_FAMILIES
+      .iter()
+      .map(|name| name.to_string())
+      .collect(),
+    height,
+    schema_version,
+  };
+
+  let partial = path.with_extension("partial");
+
+  let mut writer = HashingWriter::new(brotli::CompressorWriter::new(
+    BufWriter::new(File::create(&partial)?),
+    4096,
+    4,
+    22,
+  ));
+
+  writer.write_all(MAGIC)?;
+  let header_json = serde_json::to_vec(&header)?;
+  writer.write_all(&u32::try_from(header_json.len())?.to_be_bytes())?;
+  writer.write_all(&header_json)?;
+
+  let mut entries = 0;
+
+  for (i, name) in COLUMN_FAMILIES.iter().enumerate() {
+    let cf = db
+      .cf_handle(name)
+      .ok_or_else(|| anyhow!("Failed to open column family '{name}'"))?;
+
+    for entry in snapshot.iterator_cf(cf, IteratorMode::Start) {
+      let (key, value) = entry?;
+      writer.write_all(&[u8::try_from(i).unwrap()])?;
+      writer.write_all(&u32::try_from(key.len())?.to_be_bytes())?;
+      writer.write_all(&key)?;
+      writer.write_all(&u32::try_from(value.len())?.to_be_bytes())?;
+      writer.write_all(&value)?;
+      entries += 1;
+    }
+  }
+
+  writer.write_all(&[END_OF_ENTRIES])?;
+
+  let (mut inner, checksum) = writer.finish();
+  inner.write_all(checksum.as_byte_array())?;
+  inner.flush()?;
+  inner
+    .into_inner()
+    .into_inner()
+    .map_err(|err| err.into_error())?
+    .sync_all()?;
+
+  fs::rename(&partial, path)?;
+
+  Ok(SnapshotInfo {
+    checksum,
+    entries,
+    header,
+    path: path.into(),
+  })
+}
+
+/// Creates the index at `settings.index()` from the snapshot at `path`, after
+/// checking that bitcoind agrees with the snapshot's tip block hash.
+pub(crate) fn import(settings: &Settings, path: &Path) -> Result<SnapshotInfo> {
+  let index_path = settings.index().join("index.db");
+
+  ensure!(
+    !index_path.exists(),
+    "refusing to overwrite existing index at {}",
+    index_path.display()
+  );
+
+  let (reader, header) = read_header(path)?;
+
+  ensure!(
+    header.schema_version == SCHEMA_VERSION,
+    "snapshot has schema version {}, expected {SCHEMA_VERSION}",
+    header.schema_version,
+  );
+
+  ensure!(
+    header.chain == settings.chain(),
+    "snapshot is for {}, not {}",
+    header.chain,
+    settings.chain(),
+  );
+
+  if let Some(name) = header
+    .column_families
+    .iter()
+    .find(|name| !COLUMN_FAMILIES.contains(&name.as_str()))
+  {
+    bail!("snapshot contains unknown column family '{name}'");
+  }
+
+  let client = settings.bitcoin_rpc_client(None)?;
+
+  let block_hash = client
+    .get_block_hash(header.height.into())
+    .into_option()?
+    .ok_or_else(|| anyhow!("bitcoind has no block at snapshot height {}", header.height))?;
+
+  ensure!(
+    block_hash == header.block_hash,
+    "snapshot tip {} at height {} is not in bitcoind's best chain, which has {block_hash}",
+    header.block_hash,
+    header.height,
+  );
+
+  let partial = settings.index().join("index.db.partial");
+
+  if partial.exists() {
+    fs::remove_dir_all(&partial)?;
+  }
+
+  let result = import_entries(reader, &header, &partial, settings.rocksdb());
+
+  match result {
+    Ok((entries, checksum)) => {
+      fs::rename(&partial, &index_path)?;
+      Ok(SnapshotInfo {
+        checksum,
+        entries,
+        header,
+        path: path.into(),
+      })
+    }
+    Err(err) => {
+      fs::remove_dir_all(&partial).ok();
+      Err(err)
+    }
+  }
+}
+
+/// Opens the snapshot at `path` and reads its header, leaving the reader at
+/// the first entry.
+fn read_header(path: &Path) -> Result<(HashingReader<impl Read>, SnapshotHeader)> {
+  let mut reader = HashingReader::new(brotli::Decompressor::new(
+    BufReader::new(File::open(path)?),
+    4096,
+  ));
+
+  let mut magic = [0; 8];
+  reader.read_exact(&mut magic)?;
+  ensure!(&magic == MAGIC, "{} is not an OPI snapshot", path.display());
+
+  let header_len = reader.read_u32()?;
+  let mut header_json = vec![0; header_len.try_into()?];
+  reader.read_exact(&mut header_json)?;
+  let header = serde_json::from_slice::<SnapshotHeader>(&header_json)?;
+
+  Ok((reader, header))
+}
+
+fn import_entries<R: Read>(
+  mut reader: HashingReader<R>,
+  header: &SnapshotHeader,
+  path: &Path,
+  rocksdb: &RocksDbSettings,
+) -> Result<(u64, sha256::Hash)> {
+  let db = Database::open(path.into(), rocksdb)?;
+
+  let write_options = {
+    let mut write_options = rocksdb::WriteOptions::default();
+    write_options.disable_wal(true);
+    write_options
+  };
+
+  let cfs = header
+    .column_families
+    .iter()
+    .map(|name| {
+      db.cf_handle(name)
+        .ok_or_else(|| anyhow!("Failed to open column family '{name}'"))
+    })
+    .collect::<Result<Vec<_>>>()?;
+
+  let mut batch = WriteBatch::default();
+  let mut entries = 0;
+
+  loop {
+    let mut cf = [0];
+    reader.read_exact(&mut cf)?;
+
+    if cf[0] == END_OF_ENTRIES {
+      break;
+    }
+
+    let cf = cfs
+      .get(usize::from(cf[0]))
+      .ok_or_else(|| anyhow!("snapshot entry has invalid column family {}", cf[0]))?;
+
+    let key = reader.read_bytes()?;
+    let value = reader.read_bytes()?;
+
+    batch.put_cf(cf, key, value);
+    entries += 1;
+
+    if batch.size_in_bytes() >= IMPORT_BATCH_SIZE {
+      db.write_opt(std::mem::take(&mut batch), &write_options)?;
+    }
+  }
+
+  let (mut inner, checksum) = reader.finish();
+
+  let mut expected = [0; 32];
+  inner.read_exact(&mut expected)?;
+
+  ensure!(
+    checksum.as_byte_array() == &expected,
+    "snapshot checksum mismatch, the file is corrupt"
+  );
+
+  db.write_opt(batch, &write_options)?;
+  db.flush_all()?;
+
+  Ok((entries, checksum))
+}
+
+struct HashingWriter<W> {
+  engine: sha256::HashEngine,
+  inner: W,
+}
+
+impl<W: Write> HashingWriter<W> {
+  fn new(inner: W) -> Self {
+    Self {
+      engine: sha256::Hash::engine(),
+      inner,
+    }
+  }
+
+  fn finish(self) -> (W, sha256::Hash) {
+    (self.inner, sha256::Hash::from_engine(self.engine))
+  }
+}
+
+impl<W: Write> Write for HashingWriter<W> {
+  fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+    let n = self.inner.write(buf)?;
+    self.engine.input(&buf[..n]);
+    Ok(n)
+  }
+
+  fn flush(&mut self) -> io::Result<()> {
+    self.inner.flush()
+  }
+}
+
+struct HashingReader<R> {
+  engine: sha256::HashEngine,
+  inner: R,
+}
+
+impl<R: Read> HashingReader<R> {
+  fn new(inner: R) -> Self {
+    Self {
+      engine: sha256::Hash::engine(),
+      inner,
+    }
+  }
+
+  fn finish(self) -> (R, sha256::Hash) {
+    (self.inner, sha256::Hash::from_engine(self.engine))
+  }
+
+  fn read_u32(&mut self) -> io::Result<u32> {
+    let mut buffer = [0; 4];
+    self.read_exact(&mut buffer)?;
+    Ok(u32::from_be_bytes(buffer))
+  }
+
+  fn read_bytes(&mut self) -> Result<Vec<u8>> {
+    let mut buffer = vec![0; self.read_u32()?.try_into()?];
+    self.read_exact(&mut buffer)?;
+    Ok(buffer)
+  }
+}
+
+impl<R: Read> Read for HashingReader<R> {
+  fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+    let n = self.inner.read(buf)?;
+    self.engine.input(&buf[..n]);
+    Ok(n)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use {
+    super::*,
+    crate::{index::testing::Context, options::Options},
+  };
+
+  fn entries(path: PathBuf, rocksdb: &RocksDbSettings) -> Vec<(&'static str, Vec<u8>, Vec<u8>)> {
+    let db = Database::open(path, rocksdb).unwrap();
+
+    COLUMN_FAMILIES
+      .iter()
+      .flat_map(|name| {
+        db.iterator_cf(db.cf_handle(name).unwrap(), IteratorMode::Start)
+          .map(|entry| {
+            let (key, value) = entry.unwrap();
+            (*name, key.into(), value.into())
+          })
+          .collect::<Vec<_>>()
+      })
+      .collect()
+  }
+
+  /// An index committed at height 2
+  fn committed_index() -> (Context, BlockHash) {
+    let mut context = Context::new(Options::default());
+
+    context.mine_blocks(1);
+    context.spend_coinbases(&[1], 2);
+    let tip = context.mine_blocks(1)[0].block_hash();
+
+    (context, tip)
+  }
+
+  #[test]
+  fn snapshots_round_trip() {
+    let (context, tip) = committed_index();
+    let Context { index, tempdir, .. } = context;
+    index.shut_down().unwrap();
+
+    let settings = Context::settings(&tempdir, Options::default());
+    let path = tempdir.path().join("snapshot.opi");
+
+    let exported = export(&settings, 2, &path).unwrap();
+
+    assert_eq!(exported.header.block_hash, tip);
+    assert_eq!(exported.header.chain, Chain::Regtest);
+    assert_eq!(exported.header.height, 2);
+    assert_eq!(exported.header.schema_version, SCHEMA_VERSION);
+    assert!(!path.with_extension("partial").exists());
+
+    let (reader, header) = read_header(&path).unwrap();
+    let imported = tempdir.path().join("imported");
+
+    let (entries_imported, checksum) =
+      import_entries(reader, &header, &imported, settings.rocksdb()).unwrap();
+
+    assert_eq!(entries_imported, exported.entries);
+    assert_eq!(checksum, exported.checksum);
+
+    let original = entries(settings.index().join("index.db"), settings.rocksdb());
+
+    assert_eq!(u64::try_from(original.len()).unwrap(), exported.entries);
+    assert_eq!(entries(imported, settings.rocksdb()), original);
+  }
+
+  #[test]
+  fn snapshots_are_only_exported_at_the_committed_height() {
+    let (context, _tip) = committed_index();
+    let Context { index, tempdir, .. } = context;
+    index.shut_down().unwrap();
+
+    let settings = Context::settings(&tempdir, Options::default());
+
+    assert_eq!(
+      export(&settings, 1, &tempdir.path().join("snapshot.opi"))
+        .unwrap_err()
+        .to_string(),
+      "index is committed at height 2, not 1; snapshots can only be exported at the committed height",
+    );
+  }
+
+  #[test]
+  fn snapshots_are_not_imported_over_an_existing_index() {
+    let (context, _tip) = committed_index();
+    let Context { index, tempdir, .. } = context;
+    index.shut_down().unwrap();
+
+    let settings = Context::settings(&tempdir, Options::default());
+
+    assert_eq!(
+      import(&settings, &tempdir.path().join("snapshot.opi"))
+        .unwrap_err()
+        .to_string(),
+      format!(
+        "refusing to overwrite existing index at {}",
+        settings.index().join("index.db").display()
+      ),
+    );
+  }
+
+  #[test]
+  fn files_that_are_not_snapshots_are_rejected() {
+    let tempdir = tempfile::TempDir::new().unwrap();
+    let path = tempdir.path().join("snapshot.opi");
+
+    let mut writer = brotli::CompressorWriter::new(File::create(&path).unwrap(), 4096, 4, 22);
+    writer.write_all(b"NOTASNAPSHOT").unwrap();
+    drop(writer);
+
+    assert_eq!(
+      read_header(&path).err().unwrap().to_string(),
+      format!("{} is not an OPI snapshot", path.display()),
+    );
+  }
+}
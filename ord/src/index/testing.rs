@@ -0,0 +1,128 @@
+use {
+  super::*,
+  crate::options::Options,
+  mockcore::{Handle, TransactionTemplate},
+  tempfile::TempDir,
+};
+
+/// A regtest index in a temporary directory, synced from a mock Bitcoin Core
+/// with db_reader's RPC server listening on an ephemeral port.
+pub(crate) struct Context {
+  pub(crate) core: Handle,
+  pub(crate) index: Index,
+  pub(crate) tempdir: TempDir,
+}
+
+impl Context {
+  pub(crate) fn new(options: Options) -> Self {
+    let core = mockcore::builder().network(Network::Regtest).build();
+
+    let tempdir = TempDir::new().unwrap();
+
+    let index = Self::open(&core, &tempdir, options).unwrap();
+
+    Self {
+      core,
+      index,
+      tempdir,
+    }
+  }
+
+  /// Settings of an index in `tempdir`, for opening its database directly.
+  pub(crate) fn settings(tempdir: &TempDir, options: Options) -> Settings {
+    Settings::merge(
+      Options {
+        regtest: true,
+        data_dir: Some(tempdir.path().into()),
+        index_cache_size: Some(1 << 20),
+        ..options
+      },
+      BTreeMap::new(),
+    )
+    .unwrap()
+  }
+
+  /// Settings of an index in `tempdir` that syncs from `core`.
+  pub(crate) fn core_settings(core: &Handle, tempdir: &TempDir, options: Options) -> Settings {
+    Self::settings(
+      tempdir,
+      Options {
+        bitcoin_rpc_url: Some(core.url()),
+        cookie_file: Some(core.cookie_file()),
+        ..options
+      },
+    )
+  }
+
+  pub(crate) fn open(core: &Handle, tempdir: &TempDir, options: Options) -> Result<Index> {
+    env::set_var("DB_READER_API_URL", "127.0.0.1:0");
+
+    Index::open(&Self::core_settings(core, tempdir, options))
+  }
+
+  /// Opens an index in `tempdir` that indexes `block_source` rather than a
+  /// mock Bitcoin Core.
+  pub(crate) fn open_with_block_source(
+    tempdir: &TempDir,
+    options: Options,
+    block_source: Box<dyn BlockSource>,
+  ) -> Result<Index> {
+    env::set_var("DB_READER_API_URL", "127.0.0.1:0");
+
+    Index::open_with_block_source(&Self::settings(tempdir, options), None, block_source)
+  }
+
+  /// Shuts the index down and opens it again with `options`.
+  pub(crate) fn reopen(self, options: Options) -> Result<Self> {
+    let Self {
+      core,
+      index,
+      tempdir,
+    } = self;
+
+    index.shut_down()?;
+
+    let index = Self::open(&core, &tempdir, options)?;
+
+    Ok(Self {
+      core,
+      index,
+      tempdir,
+    })
+  }
+
+  /// Mines `n` blocks, the first holding the transactions broadcast since the
+  /// last one, and indexes them.
+  pub(crate) fn mine_blocks(&mut self, n: u64) -> Vec<Block> {
+    let blocks = self.core.mine_blocks(n);
+    self.index.update_to_tip().unwrap();
+    blocks
+  }
+
+  /// Broadcasts a transaction spending the first output of the coinbase at
+  /// each of `heights` into `outputs` equal outputs.
+  pub(crate) fn spend_coinbases(&self, heights: &[usize], outputs: usize) -> Txid {
+    let inputs = heights
+      .iter()
+      .map(|height| (*height, 0, 0, Witness::new()))
+      .collect::<Vec<_>>();
+
+    self.core.broadcast_tx(TransactionTemplate {
+      inputs: &inputs,
+      outputs,
+      ..default()
+    })
+  }
+
+  pub(crate) fn get(&self, column_family: &str, key: &[u8]) -> Option<Vec<u8>> {
+    self
+      .index
+      .db
+      .get_cf(self.index.db.cf_handle(column_family).unwrap(), key)
+      .unwrap()
+  }
+
+  pub(crate) fn utxo_entry(&self, outpoint: OutPoint) -> Option<Vec<u8>> {
+    self.get("outpoint_to_utxo_entry", &outpoint.store())
+  }
+}
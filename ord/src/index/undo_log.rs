@@ -0,0 +1,235 @@
+use {super::*, rocksdb::WriteBatch, std::{cell::RefCell, ops::Range}};
+
+/// Records the prior value of every key written while indexing a single
+/// block. Records are stored in the `undo_log` column family keyed by height,
+/// and replaying them in reverse order rolls the index back without restoring
+/// a savepoint.
+///
+/// Records are kept for the configured undo log depth, and in any case for
+/// every block since the last commit, so that a block persisted without the
+/// outputs it created can be rolled back when the index is opened.
+///
+/// Entries are encoded back to back as
+/// `cf_name_len(1) ‖ cf_name ‖ key_len(4) ‖ key ‖ present(1) [‖ value_len(4) ‖ value]`.
+pub(crate) struct UndoLog<'a> {
+  db: &'a DB,
+  record: RefCell<Vec<u8>>,
+  recorded: RefCell<HashSet<(&'static str, Vec<u8>)>>,
+}
+
+impl<'a> UndoLog<'a> {
+  pub(crate) fn new(index: &'a Index) -> Self {
+    Self {
+      db: &index.db,
+      record: RefCell::new(Vec::new()),
+      recorded: RefCell::new(HashSet::new()),
+    }
+  }
+
+  /// Reads and records the current value of `key` before it is overwritten or
+  /// deleted. Only the first write to a key in a block is recorded.
+  pub(crate) fn record(&self, column_family: &'static str, key: &[u8]) -> Result {
+    if self.is_recorded(column_family, key) {
+      return Ok(());
+    }
+
+    let cf = self
+      .db
+      .cf_handle(column_family)
+      .ok_or_else(|| anyhow!("Failed to open column family '{column_family}'"))?;
+
+    let prior = self.db.get_cf(cf, key)?;
+
+    self.push(column_family, key, prior.as_deref());
+
+    Ok(())
+  }
+
+  /// Records an already known prior value of `key`, `None` if it is absent.
+  pub(crate) fn record_value(&self, column_family: &'static str, key: &[u8], prior: Option<&[u8]>) {
+    if self.is_recorded(column_family, key) {
+      return;
+    }
+
+    self.push(column_family, key, prior);
+  }
+
+  fn is_recorded(&self, column_family: &'static str, key: &[u8]) -> bool {
+    !self
+      .recorded
+      .borrow_mut()
+      .insert((column_family, key.to_vec()))
+  }
+
+  fn push(&self, column_family: &'static str, key: &[u8], prior: Option<&[u8]>) {
+    let mut record = self.record.borrow_mut();
+
+    record.push(u8::try_from(column_family.len()).unwrap());
+    record.extend_from_slice(column_family.as_bytes());
+    record.extend_from_slice(&u32::try_from(key.len()).unwrap().to_be_bytes());
+    record.extend_from_slice(key);
+
+    match prior {
+      Some(value) => {
+        record.push(1);
+        record.extend_from_slice(&u32::try_from(value.len()).unwrap().to_be_bytes());
+        record.extend_from_slice(value);
+      }
+      None => record.push(0),
+    }
+  }
+
+  /// Adds the record of the block at `height` to `batch`, so it is written
+  /// together with the block.
+  pub(crate) fn store(self, height: u32, batch: &mut WriteBatch) -> Result {
+    let undo_log = self
+      .db
+      .cf_handle("undo_log")
+      .ok_or_else(|| anyhow!("Failed to open column family 'undo_log'"))?;
+
+    batch.put_cf(undo_log, height.to_be_bytes(), self.record.into_inner());
+
+    Ok(())
+  }
+
+  /// Adds the deletion of records no longer needed once the index is
+  /// committed at `committed_height` to `batch`. Without an undo log depth
+  /// only records of uncommitted blocks are needed.
+  pub(crate) fn prune(index: &Index, committed_height: u32, batch: &mut WriteBatch) -> Result {
+    let undo_log = index
+      .db
+      .cf_handle("undo_log")
+      .ok_or_else(|| anyhow!("Failed to open column family 'undo_log'"))?;
+
+    let oldest_kept = match index.settings.undo_log_depth() {
+      Some(depth) => committed_height.saturating_sub(u32::try_from(depth)?),
+      None => committed_height,
+    };
+
+    if oldest_kept > 0 {
+      batch.delete_range_cf(undo_log, 0u32.to_be_bytes(), oldest_kept.to_be_bytes());
+    }
+
+    Ok(())
+  }
+
+  /// Rolls back the blocks in `heights`, newest first, deletes their records
+  /// and marks the index as committed at `heights.start`. Fails without
+  /// touching the index if any record is missing.
+  pub(crate) fn rollback(index: &Index, heights: Range<u32>) -> Result {
+    let undo_log = index
+      .db
+      .cf_handle("undo_log")
+      .ok_or_else(|| anyhow!("Failed to open column family 'undo_log'"))?;
+    let statistic_to_count = index
+      .db
+      .cf_handle("statistic_to_count")
+      .ok_or_else(|| anyhow!("Failed to open column family 'statistic_to_count'"))?;
+
+    let committed_height = heights.start;
+
+    let mut batch = WriteBatch::default();
+
+    for height in heights.rev() {
+      let record = index
+        .db
+        .get_cf(undo_log, height.to_be_bytes())?
+        .ok_or_else(|| anyhow!("undo log for block {height} not found"))?;
+
+      Self::apply(index, &record, &mut batch)
+        .with_context(|| format!("failed to apply undo log for block {height}"))?;
+
+      batch.delete_cf(undo_log, height.to_be_bytes());
+    }
+
+    batch.put_cf(
+      statistic_to_count,
+      Statistic::CommittedHeight.key().to_be_bytes(),
+      committed_height.to_be_bytes(),
+    );
+
+    index.db.write_opt(batch, &index.write_options)?;
+
+    index.flush()
+  }
+
+  fn apply(index: &Index, mut record: &[u8], batch: &mut WriteBatch) -> Result {
+    fn take<'r>(record: &mut &'r [u8], len: usize) -> Result<&'r [u8]> {
+      if record.len() < len {
+        bail!("truncated undo log record");
+      }
+
+      let (head, tail) = record.split_at(len);
+      *record = tail;
+      Ok(head)
+    }
+
+    fn take_len(record: &mut &[u8]) -> Result<usize> {
+      Ok(u32::from_be_bytes(take(record, 4)?.try_into().unwrap()).try_into()?)
+    }
+
+    while !record.is_empty() {
+      let name_len = take(&mut record, 1)?[0].into();
+      let column_family = std::str::from_utf8(take(&mut record, name_len)?)?;
+
+      let cf = index
+        .db
+        .cf_handle(column_family)
+        .ok_or_else(|| anyhow!("Failed to open column family '{column_family}'"))?;
+
+      let key_len = take_len(&mut record)?;
+      let key = take(&mut record, key_len)?;
+
+      if take(&mut record, 1)?[0] == 1 {
+        let value_len = take_len(&mut record)?;
+        batch.put_cf(cf, key, take(&mut record, value_len)?);
+      } else {
+        batch.delete_cf(cf, key);
+      }
+    }
+
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use {
+    super::*,
+    crate::{index::testing::Context, options::Options},
+  };
+
+  #[test]
+  fn rollback_restores_outputs_spent_from_an_uncommitted_block() {
+    let mut context = Context::new(Options {
+      commit_interval: Some(100),
+      undo_log_depth: Some(6),
+      ..Default::default()
+    });
+
+    let outpoint = OutPoint {
+      txid: context.core.mine_blocks(1)[0].txdata[0].compute_txid(),
+      vout: 0,
+    };
+
+    context.spend_coinbases(&[1], 1);
+    context.core.mine_blocks(1);
+
+    // Index both blocks in one run, so the output is spent from the cache
+    context.index.update_to_tip().unwrap();
+
+    assert_eq!(context.index.block_count().unwrap(), 3);
+    assert_eq!(context.utxo_entry(outpoint), None);
+
+    context.core.invalidate_tip();
+    context.mine_blocks(2);
+
+    assert_eq!(context.index.block_count().unwrap(), 4);
+    assert!(context.utxo_entry(outpoint).is_some());
+
+    let context = context.reopen(Options::default()).unwrap();
+
+    assert_eq!(context.index.block_count().unwrap(), 4);
+    assert!(context.utxo_entry(outpoint).is_some());
+  }
+}
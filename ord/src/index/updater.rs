@@ -1,23 +1,23 @@
 use {
-  self::inscription_updater::{InscriptionUpdater, TX_LIMITS}, super::{fetcher::Fetcher, *}, futures::future::try_join_all, rocksdb::ColumnFamily, tokio::sync::{
+  self::{
+    blk_files::{BlkFiles, TIP_SAFETY_MARGIN},
+    inscription_updater::InscriptionUpdater,
+  },
+  super::{fetcher::Fetcher, *}, futures::future::try_join_all, rocksdb::WriteBatch, tokio::sync::{
     broadcast::{self, error::TryRecvError},
     mpsc::{self},
   }
 };
 
+mod blk_files;
 mod inscription_updater;
 
-pub fn get_tx_limits() -> HashMap<String, i16> {
-  let mut tx_limits = HashMap::new();
-  for (key, value) in TX_LIMITS.iter() {
-    tx_limits.insert(key.to_string(), *value);
-  }
-  tx_limits
-}
-
 pub(crate) struct BlockData {
   pub(crate) header: Header,
   pub(crate) txdata: Vec<(Transaction, Txid)>,
+  /// Outputs spent by every transaction but the coinbase, in input order, if
+  /// read from Bitcoin Core's undo files.
+  pub(crate) spent_outputs: Option<Vec<Vec<TxOut>>>,
 }
 
 impl From<Block> for BlockData {
@@ -32,6 +32,7 @@ impl From<Block> for BlockData {
           (transaction, txid)
         })
         .collect(),
+      spent_outputs: None,
     }
   }
 }
@@ -46,7 +47,10 @@ pub(crate) struct Updater<'index> {
 
 impl Updater<'_> {
   pub(crate) fn update_index(&mut self) -> Result {
-    let starting_height = u32::try_from(self.index.client.get_block_count()?).unwrap() + 1;
+    let starting_height = self.index.block_source.block_count()?;
+
+    self.index.metrics.bitcoind_height.set(f64::from(starting_height.saturating_sub(1)));
+    self.index.metrics.indexed_height.set(f64::from(self.height.saturating_sub(1)));
 
     let mut progress_bar = if cfg!(test)
       || log_enabled!(log::Level::Info)
@@ -63,9 +67,9 @@ impl Updater<'_> {
       Some(progress_bar)
     };
 
-    let rx = Self::fetch_blocks_from(self.index, self.height)?;
+    let rx = self.index.block_source.blocks(self.index, self.height)?;
 
-    let (mut output_sender, mut txout_receiver) = Self::spawn_fetcher(self.index)?;
+    let (mut output_sender, mut txout_receiver) = self.index.block_source.prevouts(self.index)?;
 
     println!(
       "Indexing blocks from height {} to {}…",
@@ -85,6 +89,7 @@ impl Updater<'_> {
       let mut tms = [0; 3];
       tms[0] = tm.elapsed().as_millis();
       gtms[0] += tms[0];
+      self.index.metrics.block_fetch_duration.observe(tms[0] as f64 / 1000.0);
       tm = Instant::now();
 
       self.index.db.property_value("rocksdb.cur-size-all-mem-tables")
@@ -95,6 +100,8 @@ impl Updater<'_> {
           }
 
           if let Ok(size) = size.unwrap().parse::<u64>() {
+            self.index.metrics.memtable_bytes.set(size as f64);
+
             if size > 1024 * 1024 {
               println!("RocksDB memtable size is too large: {size} bytes");
             } else {
@@ -118,6 +125,8 @@ impl Updater<'_> {
             // split line from : and parse the right part as u64
             if let Some(value) = line.split_once(": ") {
               if let Ok(value) = value.1.trim().parse::<u64>() {
+                self.index.metrics.flush_write_bytes.set(value as f64);
+
                 if value != last_flush_bytes {
                   let diff = value.saturating_sub(last_flush_bytes);
                   let diff_mb = diff as f64 / (1024.0 * 1024.0);
@@ -133,6 +142,8 @@ impl Updater<'_> {
             // split line from : and parse the right part as u64
             if let Some(value) = line.split_once(": ") {
               if let Ok(value) = value.1.trim().parse::<u64>() {
+                self.index.metrics.wal_bytes.set(value as f64);
+
                 if value != 0 {
                   let value_kb = value as f64 / (1024.0);
                   println!("RocksDB total WAL size: {value_kb:.3} KB");
@@ -143,23 +154,36 @@ impl Updater<'_> {
         })
         .unwrap_or_else(|err| println!("Failed to get RocksDB options-statistics: {err}"));
 
-      self.index_block(
+      if let Err(err) = self.index_block(
         &mut output_sender,
         &mut txout_receiver,
         block,
         &mut utxo_cache,
-      )?;
+      ) {
+        // Undo logs can only roll back blocks whose outputs have been
+        // committed, so flush the cache before the reorg is handled
+        if self.index.settings.undo_log_depth().is_some()
+          && matches!(err.downcast_ref(), Some(reorg::Error::Recoverable { .. }))
+        {
+          self.commit(utxo_cache)?;
+        }
+
+        return Err(err);
+      }
 
       tms[1] = tm.elapsed().as_millis();
       gtms[1] += tms[1];
+      self.index.metrics.block_index_duration.observe(tms[1] as f64 / 1000.0);
+      self.index.metrics.indexed_height.set(f64::from(self.height.saturating_sub(1)));
       tm = Instant::now();
 
       if let Some(progress_bar) = &mut progress_bar {
         progress_bar.inc(1);
 
         if progress_bar.position() > progress_bar.length().unwrap() {
-          if let Ok(count) = self.index.client.get_block_count() {
-            progress_bar.set_length(count + 1);
+          if let Ok(count) = self.index.block_source.block_count() {
+            self.index.metrics.bitcoind_height.set(f64::from(count.saturating_sub(1)));
+            progress_bar.set_length(count.into());
           } else {
             log::warn!("Failed to fetch latest block height");
           }
@@ -194,6 +218,7 @@ impl Updater<'_> {
 
       tms[2] = tm.elapsed().as_millis();
       gtms[2] += tms[2];
+      self.index.metrics.block_commit_duration.observe(tms[2] as f64 / 1000.0);
       tm = Instant::now();
 
       let ord_index_stat_key = self.height.to_be_bytes();
@@ -205,7 +230,7 @@ impl Updater<'_> {
       ].concat();
       self.index.db.put_cf_opt(
         ord_index_stats,
-        &ord_index_stat_key,
+        ord_index_stat_key,
         &ord_index_stat_data,
         &self.index.write_options,
       )?;
@@ -222,10 +247,10 @@ impl Updater<'_> {
         println!(
           "Height {}: {} ms for fetch per block, {} ms for index per block, {} ms for savepoint/commit per block, {} ms total per block",
           self.height,
-          gtms[0] / ((self.height - last_stat_print_height) as u128),
-          gtms[1] / ((self.height - last_stat_print_height) as u128),
-          gtms[2] / ((self.height - last_stat_print_height) as u128),
-          (gtms[0] + gtms[1] + gtms[2]) / ((self.height - last_stat_print_height) as u128),
+          gtms[0] / u128::from(self.height - last_stat_print_height),
+          gtms[1] / u128::from(self.height - last_stat_print_height),
+          gtms[2] / u128::from(self.height - last_stat_print_height),
+          (gtms[0] + gtms[1] + gtms[2]) / u128::from(self.height - last_stat_print_height),
         );
         last_stat_print_height = self.height;
         gtms = [0; 3];
@@ -269,7 +294,7 @@ impl Updater<'_> {
     Ok(())
   }
 
-  fn fetch_blocks_from(
+  pub(super) fn fetch_blocks_from(
     index: &Index,
     mut height: u32,
   ) -> Result<std::sync::mpsc::Receiver<BlockData>> {
@@ -281,25 +306,46 @@ impl Updater<'_> {
 
     let client = index.settings.bitcoin_rpc_client(None)?;
 
-    thread::spawn(move || loop {
-      if let Some(height_limit) = height_limit {
-        if height >= height_limit {
-          break;
+    let settings = index.settings.clone();
+
+    thread::spawn(move || {
+      if settings.index_from_blk_files() {
+        match Self::read_blocks_from_files(
+          &settings,
+          &client,
+          &tx,
+          &mut height,
+          first_index_height,
+          height_limit,
+        ) {
+          Ok(true) => {}
+          Ok(false) => return,
+          Err(err) => {
+            log::warn!("failed to read block {height} from blk files, fetching over RPC: {err}")
+          }
         }
       }
 
-      match Self::get_block_with_retries(&client, height, first_index_height) {
-        Ok(Some(block)) => {
-          if let Err(err) = tx.send(block.into()) {
-            log::info!("Block receiver disconnected: {err}");
+      loop {
+        if let Some(height_limit) = height_limit {
+          if height >= height_limit {
             break;
           }
-          height += 1;
         }
-        Ok(None) => break,
-        Err(err) => {
-          log::error!("failed to fetch block {height}: {err}");
-          break;
+
+        match Self::get_block_with_retries(&client, height, first_index_height) {
+          Ok(Some(block)) => {
+            if let Err(err) = tx.send(block.into()) {
+              log::info!("Block receiver disconnected: {err}");
+              break;
+            }
+            height += 1;
+          }
+          Ok(None) => break,
+          Err(err) => {
+            log::error!("failed to fetch block {height}: {err}");
+            break;
+          }
         }
       }
     });
@@ -307,6 +353,52 @@ impl Updater<'_> {
     Ok(rx)
   }
 
+  /// Sends blocks from Bitcoin Core's block files until within
+  /// `TIP_SAFETY_MARGIN` of the tip. Returns false if the receiver hung up.
+  fn read_blocks_from_files(
+    settings: &Settings,
+    client: &Client,
+    tx: &std::sync::mpsc::SyncSender<BlockData>,
+    height: &mut u32,
+    first_index_height: u32,
+    height_limit: Option<u32>,
+  ) -> Result<bool> {
+    let end = u32::try_from(client.get_block_count()?)?
+      .saturating_add(1)
+      .saturating_sub(TIP_SAFETY_MARGIN);
+
+    let end = height_limit.map_or(end, |height_limit| end.min(height_limit));
+
+    if *height >= end {
+      return Ok(true);
+    }
+
+    let mut blk_files = BlkFiles::open(settings)?;
+
+    while *height < end {
+      let hash = client.get_block_hash((*height).into())?;
+
+      let Some(mut block) = blk_files.read_block(&hash)? else {
+        log::info!("block {height} not found in blk files, fetching over RPC");
+        break;
+      };
+
+      if *height < first_index_height {
+        block.txdata.clear();
+        block.spent_outputs = None;
+      }
+
+      if let Err(err) = tx.send(block) {
+        log::info!("Block receiver disconnected: {err}");
+        return Ok(false);
+      }
+
+      *height += 1;
+    }
+
+    Ok(true)
+  }
+
   fn get_block_with_retries(
     client: &Client,
     height: u32,
@@ -352,7 +444,7 @@ impl Updater<'_> {
     }
   }
 
-  fn spawn_fetcher(index: &Index) -> Result<(mpsc::Sender<OutPoint>, broadcast::Receiver<TxOut>)> {
+  pub(super) fn spawn_fetcher(index: &Index) -> Result<(mpsc::Sender<OutPoint>, broadcast::Receiver<TxOut>)> {
     let fetcher = Fetcher::new(&index.settings)?;
 
     // A block probably has no more than 20k inputs
@@ -463,30 +555,21 @@ impl Updater<'_> {
       block.txdata.len()
     );
 
-    let height_to_block_header = self.index.db.cf_handle("height_to_block_header")
-      .ok_or_else(|| anyhow!("Failed to open column family 'height_to_block_header'"))?;
-    let inscription_id_to_sequence_number = self.index.db.cf_handle("inscription_id_to_sequence_number")
-      .ok_or_else(|| anyhow!("Failed to open column family 'inscription_id_to_sequence_number'"))?;
-    let statistic_to_count = self.index.db.cf_handle("statistic_to_count")
-      .ok_or_else(|| anyhow!("Failed to open column family 'statistic_to_count'"))?;
+    let block_batch = BlockBatch::new(self.index);
 
     self.index_utxo_entries(
       &block,
       txout_receiver,
       output_sender,
       utxo_cache,
-      inscription_id_to_sequence_number,
-      statistic_to_count,
+      &block_batch,
       &mut sat_ranges_written,
       &mut outputs_in_block,
     )?;
 
-    self.index.db.put_cf_opt(
-      height_to_block_header,
-      &self.height.to_be_bytes(),
-      &block.header.store(),
-      &self.index.write_options,
-    )?;
+    block_batch.put("height_to_block_header", &self.height.to_be_bytes(), &block.header.store())?;
+
+    block_batch.write(self.height, self.index)?;
 
     self.height += 1;
     self.outputs_traversed += outputs_in_block;
@@ -504,48 +587,27 @@ impl Updater<'_> {
             } */
   fn get_and_remove_if_exists(
     &mut self,
-    column_family: &ColumnFamily,
+    block_batch: &BlockBatch,
     key: &[u8],
   ) -> Option<UtxoEntryBuf> {
-    let res = self.index.db.get_cf(column_family, key).unwrap();
-
-    if res.is_none() {
-      return None;
-    }
-
-    self.index.db.delete_cf_opt(
-      column_family,
-      key,
-      &self.index.write_options,
-    ).unwrap();
-    Some(UtxoEntryBuf::new_with_values(res.unwrap()))
+    block_batch
+      .remove("outpoint_to_utxo_entry", key)
+      .unwrap()
+      .map(UtxoEntryBuf::new_with_values)
   }
 
-  fn index_utxo_entries<'wtx>(
+  fn index_utxo_entries(
     &mut self,
     block: &BlockData,
     txout_receiver: &mut broadcast::Receiver<TxOut>,
     output_sender: &mut mpsc::Sender<OutPoint>,
     utxo_cache: &mut HashMap<OutPoint, UtxoEntryBuf>,
-    inscription_id_to_sequence_number: &ColumnFamily,
-    statistic_to_count: &ColumnFamily,
+    block_batch: &BlockBatch,
     _sat_ranges_written: &mut u64,
     _outputs_in_block: &mut u64,
   ) -> Result<(), Error> {
-    let height_to_last_sequence_number = self.index.db.cf_handle("height_to_last_sequence_number")
-      .ok_or_else(|| anyhow!("Failed to open column family 'height_to_last_sequence_number'"))?;
-    let inscription_number_to_sequence_number = self.index.db.cf_handle("inscription_number_to_sequence_number")
-      .ok_or_else(|| anyhow!("Failed to open column family 'inscription_number_to_sequence_number'"))?;
-    let outpoint_to_utxo_entry = self.index.db.cf_handle("outpoint_to_utxo_entry")
-      .ok_or_else(|| anyhow!("Failed to open column family 'outpoint_to_utxo_entry'"))?;
-    let inscription_id_to_txcnt = self.index.db.cf_handle("inscription_id_to_txcnt")
-      .ok_or_else(|| anyhow!("Failed to open column family 'inscription_id_to_txcnt'"))?;
     let sequence_number_to_inscription_entry = self.index.db.cf_handle("sequence_number_to_inscription_entry")
       .ok_or_else(|| anyhow!("Failed to open column family 'sequence_number_to_inscription_entry'"))?;
-    let ord_transfers = self.index.db.cf_handle("ord_transfers")
-      .ok_or_else(|| anyhow!("Failed to open column family 'ord_transfers'"))?;
-    let ord_inscription_info = self.index.db.cf_handle("ord_inscription_info")
-      .ok_or_else(|| anyhow!("Failed to open column family 'ord_inscription_info'"))?;
 
     let index_inscriptions = self.height >= self.index.settings.first_inscription_height();
 
@@ -558,7 +620,7 @@ impl Updater<'_> {
       );
     }
 
-    if !self.index.have_full_utxo_index() {
+    if !self.index.have_full_utxo_index() && block.spent_outputs.is_none() {
       // Send all missing input outpoints to be fetched
       let txids = block
         .txdata
@@ -583,7 +645,7 @@ impl Updater<'_> {
             continue;
           }
           // We don't need inputs we already have in our database
-          if self.index.db.get_cf(outpoint_to_utxo_entry, &prev_output.store())?.is_some() {
+          if block_batch.get("outpoint_to_utxo_entry", &prev_output.store())?.is_some() {
             continue;
           }
           // Send this outpoint to background thread to be fetched
@@ -592,13 +654,13 @@ impl Updater<'_> {
       }
     }
 
-    let cursed_inscription_count = self.index.db
-      .get_cf(statistic_to_count, &Statistic::CursedInscriptions.key().to_be_bytes())?
+    let cursed_inscription_count = block_batch
+      .get("statistic_to_count", &Statistic::CursedInscriptions.key().to_be_bytes())?
       .map(|count| u64::from_be_bytes(count.try_into().unwrap()))
       .unwrap_or(0);
 
-    let blessed_inscription_count = self.index.db
-      .get_cf(statistic_to_count, &Statistic::BlessedInscriptions.key().to_be_bytes())?
+    let blessed_inscription_count = block_batch
+      .get("statistic_to_count", &Statistic::BlessedInscriptions.key().to_be_bytes())?
       .map(|count| u64::from_be_bytes(count.try_into().unwrap()))
       .unwrap_or(0);
 
@@ -614,18 +676,11 @@ impl Updater<'_> {
       cursed_inscription_count,
       flotsam: Vec::new(),
       height: self.height,
-      db: &self.index.db,
-      id_to_sequence_number: inscription_id_to_sequence_number,
-      inscription_number_to_sequence_number: inscription_number_to_sequence_number,
-      id_to_txcnt: inscription_id_to_txcnt,
+      block_batch,
       next_sequence_number,
       reward: Height(self.height).subsidy(),
-      sequence_number_to_entry: sequence_number_to_inscription_entry,
-      ord_transfers,
-      ord_inscription_info,
       transfer_idx: 0,
       early_transfer_info: HashMap::new(),
-      write_options: &self.index.write_options,
     };
 
     for (tx_offset, (tx, txid)) in block
@@ -642,16 +697,36 @@ impl Updater<'_> {
       } else {
         tx.input
           .iter()
-          .map(|input| {
+          .enumerate()
+          .map(|(input_index, input)| {
             let outpoint = input.previous_output.store();
 
             let entry = if let Some(entry) = utxo_cache.remove(&OutPoint::load(outpoint)) {
               self.outputs_cached += 1;
+              // The output is written on commit if it was created by an
+              // earlier block, so rolling this block back must restore it
+              block_batch.record_removed("outpoint_to_utxo_entry", &outpoint, &entry.vec);
+              entry
+            } else if let Some(entry) = self.get_and_remove_if_exists(block_batch, &outpoint) {
               entry
-            } else if let Some(entry) = self.get_and_remove_if_exists(outpoint_to_utxo_entry, &outpoint) {
+            } else if let Some(spent_outputs) = &block.spent_outputs {
+              let mut entry = UtxoEntryBuf::new();
+              entry.push_value(spent_outputs[tx_offset - 1][input_index].value.to_sat());
+
               entry
             } else {
-              assert!(!self.index.have_full_utxo_index());
+              // Outputs of an index with every output are not requested up
+              // front, so one missing from it is fetched now
+              if self.index.have_full_utxo_index() {
+                log::warn!(
+                  "Output {} spent in block {} is missing from the UTXO index, fetching it",
+                  input.previous_output,
+                  self.height,
+                );
+
+                output_sender.blocking_send(input.previous_output)?;
+              }
+
               let txout = txout_receiver.blocking_recv().map_err(|err| {
                 anyhow!(
                   "failed to get transaction for {}: {err}",
@@ -681,8 +756,7 @@ impl Updater<'_> {
         .map(|_| UtxoEntryBuf::new())
         .collect::<Vec<UtxoEntryBuf>>();
 
-      let input_sat_ranges;
-      input_sat_ranges = None;
+      let input_sat_ranges = None;
 
       for (vout, txout) in tx.output.iter().enumerate() {
         output_utxo_entries[vout].push_value(txout.value.to_sat());
@@ -702,33 +776,39 @@ impl Updater<'_> {
 
       for (vout, output_utxo_entry) in output_utxo_entries.into_iter().enumerate() {
         let vout = u32::try_from(vout).unwrap();
-        utxo_cache.insert(OutPoint { txid: *txid, vout }, output_utxo_entry);
+        let outpoint = OutPoint { txid: *txid, vout };
+        // New outputs are only written on commit, but they are attributed to
+        // the block that created them so rolling it back deletes them
+        block_batch.record_created("outpoint_to_utxo_entry", &outpoint.store());
+        utxo_cache.insert(outpoint, output_utxo_entry);
       }
     }
 
     if index_inscriptions {
       inscription_updater.end_block()?;
-      self.index.db
-        .put_cf_opt(
-          height_to_last_sequence_number,
-          &self.height.to_be_bytes(),
-          inscription_updater.next_sequence_number.to_be_bytes(),
-          &self.index.write_options
-        )?;
+
+      let inscriptions = inscription_updater.blessed_inscription_count + inscription_updater.cursed_inscription_count
+        - blessed_inscription_count
+        - cursed_inscription_count;
+      self.index.metrics.inscriptions_per_block.observe(inscriptions as f64);
+
+      block_batch.put(
+        "height_to_last_sequence_number",
+        &self.height.to_be_bytes(),
+        &inscription_updater.next_sequence_number.to_be_bytes(),
+      )?;
     }
 
-    self.index.db.put_cf_opt(
-      statistic_to_count,
+    block_batch.put(
+      "statistic_to_count",
       &Statistic::CursedInscriptions.key().to_be_bytes(),
       &inscription_updater.cursed_inscription_count.to_be_bytes(),
-      &self.index.write_options,
     )?;
 
-    self.index.db.put_cf_opt(
-      statistic_to_count,
+    block_batch.put(
+      "statistic_to_count",
       &Statistic::BlessedInscriptions.key().to_be_bytes(),
       &inscription_updater.blessed_inscription_count.to_be_bytes(),
-      &self.index.write_options,
     )?;
 
     Ok(())
@@ -775,8 +855,22 @@ impl Updater<'_> {
           continue;
         }*/
 
-        self.index.db.put_cf_opt(outpoint_to_utxo_entry, &outpoint.store(), utxo_entry.vec, &self.index.write_options)?;
+        self.index.db.put_cf_opt(outpoint_to_utxo_entry, outpoint.store(), utxo_entry.vec, &self.index.write_options)?;
       }
+
+      // Blocks above the committed height are rolled back when the index is
+      // opened, since their outputs may not have been written
+      let statistic_to_count = self.index.db.cf_handle("statistic_to_count")
+        .ok_or_else(|| anyhow!("Failed to open column family 'statistic_to_count'"))?;
+
+      let mut batch = WriteBatch::default();
+      batch.put_cf(
+        statistic_to_count,
+        Statistic::CommittedHeight.key().to_be_bytes(),
+        self.height.to_be_bytes(),
+      );
+      UndoLog::prune(self.index, self.height, &mut batch)?;
+      self.index.db.write_opt(batch, &self.index.write_options)?;
     }
 
     println!("Prepared db in {} ms", st_tm.elapsed().as_millis());
@@ -785,38 +879,10 @@ impl Updater<'_> {
     self.outputs_traversed = 0;
     self.sat_ranges_since_flush = 0;
 
-    let mut flush_opts = rocksdb::FlushOptions::default();
-    flush_opts.set_wait(true);
-
-    let cfs = vec! [
-      self.index.db.cf_handle("height_to_block_header")
-        .ok_or_else(|| anyhow!("Failed to open column family 'height_to_block_header'"))?,
-      self.index.db.cf_handle("height_to_last_sequence_number")
-        .ok_or_else(|| anyhow!("Failed to open column family 'height_to_last_sequence_number'"))?,
-      self.index.db.cf_handle("outpoint_to_utxo_entry")
-        .ok_or_else(|| anyhow!("Failed to open column family 'outpoint_to_utxo_entry'"))?,
-      self.index.db.cf_handle("inscription_id_to_sequence_number")
-        .ok_or_else(|| anyhow!("Failed to open column family 'inscription_id_to_sequence_number'"))?,
-      self.index.db.cf_handle("inscription_number_to_sequence_number")
-        .ok_or_else(|| anyhow!("Failed to open column family 'inscription_number_to_sequence_number'"))?,
-      self.index.db.cf_handle("inscription_id_to_txcnt")
-        .ok_or_else(|| anyhow!("Failed to open column family 'inscription_id_to_txcnt'"))?,
-      self.index.db.cf_handle("sequence_number_to_inscription_entry")
-        .ok_or_else(|| anyhow!("Failed to open column family 'sequence_number_to_inscription_entry'"))?,
-      self.index.db.cf_handle("statistic_to_count")
-        .ok_or_else(|| anyhow!("Failed to open column family 'statistic_to_count'"))?,
-      self.index.db.cf_handle("ord_transfers")
-        .ok_or_else(|| anyhow!("Failed to open column family 'ord_transfers'"))?,
-      self.index.db.cf_handle("ord_inscription_info")
-        .ok_or_else(|| anyhow!("Failed to open column family 'ord_inscription_info'"))?,
-      self.index.db.cf_handle("ord_index_stats")
-        .ok_or_else(|| anyhow!("Failed to open column family 'ord_index_stats'"))?,
-    ];
-
-    //self.index.db.flush_opt(&flush_opts)?;
-    self.index.db.flush_cfs_opt(&cfs, &flush_opts)?;
+    self.index.flush()?;
 
     println!("First commit done in {} ms", st_tm_2.elapsed().as_millis());
+    self.index.metrics.commit_duration.observe_duration(st_tm.elapsed());
     let st_tm_3 = Instant::now();
 
     Reorg::update_savepoints(self.index, self.height)?;
@@ -826,3 +892,82 @@ impl Updater<'_> {
     Ok(())
   }
 }
+
+#[cfg(test)]
+mod tests {
+  use {
+    super::*,
+    crate::{index::testing::Context, options::Options},
+  };
+
+  #[test]
+  fn outputs_missing_from_a_full_utxo_index_are_fetched() {
+    let mut context = Context::new(Options {
+      index_prevouts: true,
+      ..Default::default()
+    });
+
+    let outpoint = OutPoint {
+      txid: context.mine_blocks(1)[0].txdata[0].compute_txid(),
+      vout: 0,
+    };
+
+    assert!(context.index.have_full_utxo_index());
+
+    let outpoint_to_utxo_entry = context
+      .index
+      .db
+      .cf_handle("outpoint_to_utxo_entry")
+      .unwrap();
+    context
+      .index
+      .db
+      .delete_cf(outpoint_to_utxo_entry, outpoint.store())
+      .unwrap();
+
+    let txid = context.spend_coinbases(&[1], 1);
+    context.mine_blocks(1);
+
+    assert_eq!(context.index.block_count().unwrap(), 3);
+    assert_eq!(context.utxo_entry(outpoint), None);
+    assert!(context.utxo_entry(OutPoint { txid, vout: 0 }).is_some());
+  }
+
+  #[test]
+  fn outputs_that_cannot_be_fetched_are_an_error() {
+    let mut context = Context::new(Options {
+      index_prevouts: true,
+      ..Default::default()
+    });
+
+    let outpoint = OutPoint {
+      txid: context.mine_blocks(1)[0].txdata[0].compute_txid(),
+      vout: 0,
+    };
+
+    // The output is neither in the index nor known to Bitcoin Core
+    let outpoint_to_utxo_entry = context
+      .index
+      .db
+      .cf_handle("outpoint_to_utxo_entry")
+      .unwrap();
+    context
+      .index
+      .db
+      .delete_cf(outpoint_to_utxo_entry, outpoint.store())
+      .unwrap();
+
+    context.spend_coinbases(&[1], 1);
+    context.core.mine_blocks(1);
+    context.core.state().transactions.remove(&outpoint.txid);
+
+    assert!(context
+      .index
+      .update_to_tip()
+      .unwrap_err()
+      .to_string()
+      .starts_with(&format!("failed to get transaction for {outpoint}")));
+
+    assert_eq!(context.index.block_count().unwrap(), 2);
+  }
+}
@@ -0,0 +1,554 @@
+use {
+  super::*,
+  bitcoin::{
+    consensus::deserialize,
+    hashes::{sha256d, Hash, HashEngine},
+    opcodes::all::OP_CHECKSIG,
+    script::{Builder, PushBytesBuf},
+    secp256k1::PublicKey,
+    PubkeyHash, ScriptHash,
+  },
+  std::{
+    fs::File,
+    io::{Read, Seek, SeekFrom},
+  },
+};
+
+/// Blocks this close to the chain tip are fetched over RPC, since Bitcoin Core
+/// may still switch them to another chain.
+pub(crate) const TIP_SAFETY_MARGIN: u32 = 100;
+
+/// Scripts longer than this are stored truncated in undo data and restored as
+/// `OP_RETURN`, see `ScriptCompression` in Bitcoin Core.
+const MAX_SCRIPT_SIZE: usize = 10_000;
+
+#[derive(Clone, Copy)]
+struct Location {
+  file: u32,
+  offset: u64,
+  len: u32,
+}
+
+/// Reads blocks from Bitcoin Core's `blk*.dat` files and the outputs they
+/// spend from the matching `rev*.dat` undo files, deobfuscating them with the
+/// key in `xor.dat` if present.
+///
+/// Block locations are found by scanning the record headers of every block
+/// file when opening. Undo records carry no block hash, only a checksum over
+/// the previous block hash and the undo data, so the undo files are scanned
+/// lazily and searched from where the last match in the same file was found.
+pub(crate) struct BlkFiles {
+  blocks: HashMap<BlockHash, Location>,
+  blocks_dir: PathBuf,
+  magic: [u8; 4],
+  undo_cursors: HashMap<u32, usize>,
+  undo_records: HashMap<u32, Vec<Location>>,
+  xor_key: [u8; 8],
+}
+
+impl BlkFiles {
+  pub(crate) fn open(settings: &Settings) -> Result<Self> {
+    let blocks_dir = settings.bitcoin_blocks_dir();
+
+    let xor_key = match fs::read(blocks_dir.join("xor.dat")) {
+      Ok(key) => key
+        .try_into()
+        .map_err(|key: Vec<u8>| anyhow!("xor.dat has {} bytes, expected 8", key.len()))?,
+      Err(err) if err.kind() == io::ErrorKind::NotFound => [0; 8],
+      Err(err) => return Err(err.into()),
+    };
+
+    let mut blk_files = Self {
+      blocks: HashMap::new(),
+      blocks_dir,
+      magic: Network::from(settings.chain()).magic().to_bytes(),
+      undo_cursors: HashMap::new(),
+      undo_records: HashMap::new(),
+      xor_key,
+    };
+
+    let start = Instant::now();
+
+    for file in 0.. {
+      let path = blk_files.path("blk", file);
+
+      if !path.exists() {
+        break;
+      }
+
+      let mut reader = File::open(&path)?;
+
+      for location in blk_files.scan(&mut reader, file, 0)? {
+        let header = blk_files.read(&mut reader, location.offset, 80)?;
+        let header = deserialize::<Header>(&header)
+          .with_context(|| format!("failed to parse block header in {}", path.display()))?;
+        blk_files.blocks.insert(header.block_hash(), location);
+      }
+    }
+
+    log::info!(
+      "Found {} blocks in {} in {} ms",
+      blk_files.blocks.len(),
+      blk_files.blocks_dir.display(),
+      start.elapsed().as_millis(),
+    );
+
+    Ok(blk_files)
+  }
+
+  /// Reads the block with `hash`, `None` if it is not in any block file. The
+  /// spent outputs are left out if its undo record cannot be found.
+  pub(crate) fn read_block(&mut self, hash: &BlockHash) -> Result<Option<BlockData>> {
+    let Some(location) = self.blocks.get(hash).copied() else {
+      return Ok(None);
+    };
+
+    let mut reader = File::open(self.path("blk", location.file))?;
+
+    let block = deserialize::<Block>(&self.read(
+      &mut reader,
+      location.offset,
+      location.len.try_into().unwrap(),
+    )?)?;
+
+    ensure!(
+      block.block_hash() == *hash,
+      "block at offset {} of blk{:05}.dat is not {hash}",
+      location.offset,
+      location.file,
+    );
+
+    let spent_outputs = if block.header.prev_blockhash == BlockHash::all_zeros() {
+      Some(Vec::new())
+    } else {
+      self.read_spent_outputs(location.file, &block)?
+    };
+
+    if let Some(spent_outputs) = &spent_outputs {
+      ensure!(
+        spent_outputs.len() + 1 == block.txdata.len()
+          && spent_outputs
+            .iter()
+            .zip(block.txdata.iter().skip(1))
+            .all(|(outputs, tx)| outputs.len() == tx.input.len()),
+        "undo data for block {hash} does not match its transactions",
+      );
+    }
+
+    let mut block = BlockData::from(block);
+    block.spent_outputs = spent_outputs;
+
+    Ok(Some(block))
+  }
+
+  fn read_spent_outputs(&mut self, file: u32, block: &Block) -> Result<Option<Vec<Vec<TxOut>>>> {
+    let path = self.path("rev", file);
+
+    if !path.exists() {
+      return Ok(None);
+    }
+
+    let mut reader = File::open(&path)?;
+
+    if !self.undo_records.contains_key(&file) {
+      let records = self.scan(&mut reader, file, 32)?;
+      self.undo_records.insert(file, records);
+    }
+
+    let records = &self.undo_records[&file];
+    let cursor = self.undo_cursors.get(&file).copied().unwrap_or_default();
+
+    // Blocks are usually connected in the order they are stored, so the next
+    // record after the last match is almost always the one we're looking for
+    for i in (cursor..records.len()).chain(0..cursor) {
+      let location = records[i];
+
+      let record = self.read(
+        &mut reader,
+        location.offset,
+        usize::try_from(location.len).unwrap() + 32,
+      )?;
+
+      let (undo, checksum) = record.split_at(record.len() - 32);
+
+      if UndoReader(undo).compact_size()? + 1 != u64::try_from(block.txdata.len()).unwrap() {
+        continue;
+      }
+
+      let mut engine = sha256d::Hash::engine();
+      engine.input(block.header.prev_blockhash.as_byte_array());
+      engine.input(undo);
+
+      if sha256d::Hash::from_engine(engine).as_byte_array() != checksum {
+        continue;
+      }
+
+      self.undo_cursors.insert(file, i + 1);
+
+      return Ok(Some(UndoReader(undo).block_undo().with_context(|| {
+        format!("failed to parse undo record at offset {} of {}", location.offset, path.display())
+      })?));
+    }
+
+    log::warn!(
+      "undo data for block {} not found in {}",
+      block.block_hash(),
+      path.display()
+    );
+
+    Ok(None)
+  }
+
+  fn path(&self, prefix: &str, file: u32) -> PathBuf {
+    self.blocks_dir.join(format!("{prefix}{file:05}.dat"))
+  }
+
+  /// Returns the location of every record in a block or undo file. Records
+  /// are `magic ‖ len ‖ data`, followed by `trailer` bytes of checksum in undo
+  /// files. Files are preallocated with zeros, which are not obfuscated, so a
+  /// zeroed magic on disk marks the end.
+  fn scan(&self, reader: &mut File, file: u32, trailer: u64) -> Result<Vec<Location>> {
+    let file_len = reader.metadata()?.len();
+
+    let mut records = Vec::new();
+    let mut offset = 0;
+
+    while offset + 8 <= file_len {
+      let mut header = Self::read_raw(reader, offset, 8)?;
+
+      if header[..4] == [0; 4] {
+        break;
+      }
+
+      self.deobfuscate(offset, &mut header);
+
+      ensure!(
+        header[..4] == self.magic,
+        "unexpected network magic at offset {offset} of file {file}"
+      );
+
+      let len = u32::from_le_bytes(header[4..].try_into().unwrap());
+
+      records.push(Location {
+        file,
+        offset: offset + 8,
+        len,
+      });
+
+      offset += 8 + u64::from(len) + trailer;
+    }
+
+    Ok(records)
+  }
+
+  fn read(&self, reader: &mut File, offset: u64, len: usize) -> Result<Vec<u8>> {
+    let mut buffer = Self::read_raw(reader, offset, len)?;
+    self.deobfuscate(offset, &mut buffer);
+    Ok(buffer)
+  }
+
+  fn read_raw(reader: &mut File, offset: u64, len: usize) -> Result<Vec<u8>> {
+    let mut buffer = vec![0; len];
+
+    reader.seek(SeekFrom::Start(offset))?;
+    reader.read_exact(&mut buffer)?;
+
+    Ok(buffer)
+  }
+
+  /// XORs `buffer`, read from `offset`, with the key from `xor.dat`.
+  fn deobfuscate(&self, offset: u64, buffer: &mut [u8]) {
+    if self.xor_key != [0; 8] {
+      for (i, byte) in buffer.iter_mut().enumerate() {
+        *byte ^= self.xor_key[usize::try_from((offset + u64::try_from(i).unwrap()) % 8).unwrap()];
+      }
+    }
+  }
+}
+
+/// Parses Bitcoin Core's `CBlockUndo` serialization.
+struct UndoReader<'a>(&'a [u8]);
+
+impl UndoReader<'_> {
+  /// The outputs spent by each transaction but the coinbase, in input order.
+  fn block_undo(mut self) -> Result<Vec<Vec<TxOut>>> {
+    let mut block_undo = Vec::new();
+
+    for _ in 0..self.compact_size()? {
+      let mut tx_undo = Vec::new();
+
+      for _ in 0..self.compact_size()? {
+        tx_undo.push(self.coin()?);
+      }
+
+      block_undo.push(tx_undo);
+    }
+
+    ensure!(self.0.is_empty(), "trailing bytes in undo record");
+
+    Ok(block_undo)
+  }
+
+  fn coin(&mut self) -> Result<TxOut> {
+    let code = self.varint()?;
+
+    // Versions before 0.15 also stored the spending transaction's version
+    if code / 2 > 0 {
+      self.varint()?;
+    }
+
+    let value = Amount::from_sat(decompress_amount(self.varint()?));
+
+    let script_pubkey = match self.varint()? {
+      0 => ScriptBuf::new_p2pkh(&PubkeyHash::from_byte_array(
+        self.bytes(20)?.try_into().unwrap(),
+      )),
+      1 => ScriptBuf::new_p2sh(&ScriptHash::from_byte_array(
+        self.bytes(20)?.try_into().unwrap(),
+      )),
+      prefix @ (2 | 3) => {
+        let mut key = vec![u8::try_from(prefix).unwrap()];
+        key.extend_from_slice(self.bytes(32)?);
+        p2pk(key)
+      }
+      prefix @ (4 | 5) => {
+        let mut key = vec![u8::try_from(prefix - 2).unwrap()];
+        key.extend_from_slice(self.bytes(32)?);
+        match PublicKey::from_slice(&key) {
+          Ok(key) => p2pk(key.serialize_uncompressed().to_vec()),
+          Err(_) => ScriptBuf::new(),
+        }
+      }
+      len => {
+        let len = usize::try_from(len - 6)?;
+        let script = self.bytes(len)?;
+        if len > MAX_SCRIPT_SIZE {
+          ScriptBuf::from_bytes(vec![bitcoin::opcodes::all::OP_RETURN.to_u8()])
+        } else {
+          ScriptBuf::from_bytes(script.to_vec())
+        }
+      }
+    };
+
+    Ok(TxOut {
+      value,
+      script_pubkey,
+    })
+  }
+
+  fn bytes(&mut self, len: usize) -> Result<&[u8]> {
+    ensure!(self.0.len() >= len, "truncated undo record");
+    let (head, tail) = self.0.split_at(len);
+    self.0 = tail;
+    Ok(head)
+  }
+
+  fn compact_size(&mut self) -> Result<u64> {
+    Ok(match self.bytes(1)?[0] {
+      0xfd => u16::from_le_bytes(self.bytes(2)?.try_into().unwrap()).into(),
+      0xfe => u32::from_le_bytes(self.bytes(4)?.try_into().unwrap()).into(),
+      0xff => u64::from_le_bytes(self.bytes(8)?.try_into().unwrap()),
+      n => n.into(),
+    })
+  }
+
+  /// Bitcoin Core's `VARINT`, base 128 with the high bit marking continuation
+  /// and one added to every continued digit.
+  fn varint(&mut self) -> Result<u64> {
+    let mut n = 0u64;
+
+    loop {
+      let byte = self.bytes(1)?[0];
+
+      n = n
+        .checked_mul(128)
+        .ok_or_else(|| anyhow!("varint overflow in undo record"))?
+        | u64::from(byte & 0x7f);
+
+      if byte & 0x80 == 0 {
+        return Ok(n);
+      }
+
+      n += 1;
+    }
+  }
+}
+
+fn p2pk(key: Vec<u8>) -> ScriptBuf {
+  Builder::new()
+    .push_slice(PushBytesBuf::try_from(key).unwrap())
+    .push_opcode(OP_CHECKSIG)
+    .into_script()
+}
+
+/// Inverse of Bitcoin Core's `CompressAmount`.
+fn decompress_amount(mut x: u64) -> u64 {
+  if x == 0 {
+    return 0;
+  }
+
+  x -= 1;
+
+  let mut e = x % 10;
+  x /= 10;
+
+  let mut n = if e < 9 {
+    let d = x % 9 + 1;
+    x /= 9;
+    x * 10 + d
+  } else {
+    x + 1
+  };
+
+  while e > 0 {
+    n *= 10;
+    e -= 1;
+  }
+
+  n
+}
+
+#[cfg(test)]
+mod tests {
+  use {super::*, crate::options::Options, tempfile::TempDir};
+
+  /// Written by `tests/fixtures/blk_files/generate.py`
+  fn fixture() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/blk_files")
+  }
+
+  fn open(bitcoin_data_dir: &Path) -> Result<BlkFiles> {
+    BlkFiles::open(
+      &Settings::merge(
+        Options {
+          regtest: true,
+          bitcoin_data_dir: Some(bitcoin_data_dir.into()),
+          ..default()
+        },
+        BTreeMap::new(),
+      )
+      .unwrap(),
+    )
+  }
+
+  #[test]
+  fn blocks_are_read_with_the_outputs_they_spend() {
+    let mut blk_files = open(&fixture()).unwrap();
+
+    let mut blocks = blk_files
+      .blocks
+      .keys()
+      .copied()
+      .collect::<Vec<BlockHash>>()
+      .into_iter()
+      .map(|hash| blk_files.read_block(&hash).unwrap().unwrap())
+      .collect::<Vec<BlockData>>();
+
+    blocks.sort_by_key(|block| block.header.time);
+
+    let [genesis, block] = blocks.as_slice() else {
+      panic!("expected two blocks, found {}", blocks.len());
+    };
+
+    assert_eq!(genesis.spent_outputs, Some(Vec::new()));
+    assert_eq!(block.header.prev_blockhash, genesis.header.block_hash());
+    assert_eq!(
+      block.spent_outputs,
+      Some(vec![genesis.txdata[0].0.output.clone()]),
+    );
+  }
+
+  #[test]
+  fn files_are_not_readable_without_their_xor_key() {
+    let tempdir = TempDir::new().unwrap();
+    let blocks_dir = tempdir.path().join("regtest/blocks");
+    fs::create_dir_all(&blocks_dir).unwrap();
+
+    for file in ["blk00000.dat", "rev00000.dat"] {
+      fs::copy(
+        fixture().join("regtest/blocks").join(file),
+        blocks_dir.join(file),
+      )
+      .unwrap();
+    }
+
+    assert_eq!(
+      open(tempdir.path()).err().unwrap().to_string(),
+      "unexpected network magic at offset 0 of file 0",
+    );
+
+    fs::write(blocks_dir.join("xor.dat"), [0; 7]).unwrap();
+
+    assert_eq!(
+      open(tempdir.path()).err().unwrap().to_string(),
+      "xor.dat has 7 bytes, expected 8",
+    );
+  }
+
+  #[test]
+  fn varints() {
+    for (bytes, n) in [
+      (&[0x00][..], 0),
+      (&[0x7f], 127),
+      (&[0x80, 0x00], 128),
+      (&[0x80, 0x7f], 255),
+      (&[0xa3, 0x34], 0x1234),
+      (&[0x82, 0xfe, 0x7f], 0xffff),
+      (&[0x8e, 0xfe, 0xfe, 0xfe, 0x7f], 0xffff_ffff),
+    ] {
+      let mut reader = UndoReader(bytes);
+      assert_eq!(reader.varint().unwrap(), n);
+      assert!(reader.0.is_empty());
+    }
+
+    assert_eq!(
+      UndoReader(&[0xff; 11]).varint().unwrap_err().to_string(),
+      "varint overflow in undo record",
+    );
+
+    assert_eq!(
+      UndoReader(&[0x80]).varint().unwrap_err().to_string(),
+      "truncated undo record",
+    );
+  }
+
+  #[test]
+  fn compressed_amounts() {
+    for (compressed, amount) in [
+      (0x0, 0),
+      (0x1, 1),
+      (0x7, 1_000_000),
+      (0x9, 100_000_000),
+      (0x32, 50 * 100_000_000),
+      (0x1406f40, 21_000_000 * 100_000_000),
+    ] {
+      assert_eq!(decompress_amount(compressed), amount);
+    }
+  }
+
+  #[test]
+  fn coins_spent_from_later_blocks_skip_the_version() {
+    let mut undo = vec![10, 0, 9, 0];
+    undo.extend([0x11; 20]);
+
+    assert_eq!(
+      UndoReader(&undo).coin().unwrap(),
+      TxOut {
+        value: Amount::from_sat(100_000_000),
+        script_pubkey: ScriptBuf::new_p2pkh(&PubkeyHash::from_byte_array([0x11; 20])),
+      },
+    );
+  }
+
+  #[test]
+  fn oversized_scripts_are_restored_as_op_return() {
+    // VARINT of `MAX_SCRIPT_SIZE + 1 + 6`
+    let mut undo = vec![1, 0, 0xcd, 0x17];
+    undo.extend(vec![0; MAX_SCRIPT_SIZE + 1]);
+
+    assert_eq!(
+      UndoReader(&undo).coin().unwrap().script_pubkey,
+      ScriptBuf::from_bytes(vec![bitcoin::opcodes::all::OP_RETURN.to_u8()]),
+    );
+  }
+}
@@ -1,20 +1,9 @@
 use super::*;
 
-use rocksdb::WriteOptions;
 use serde_json::Value;
 
-#[derive(Debug, PartialEq, Copy, Clone)]
-enum Curse {
-  DuplicateField,
-  IncompleteField,
-  NotAtOffsetZero,
-  NotInFirstInput,
-  Pointer,
-  Pushnum,
-  Reinscription,
-  Stutter,
-  UnrecognizedEvenField,
-}
+/// Bodies decoding to more than this many bytes are left undecoded.
+const MAX_DECODED_CONTENT_LEN: u64 = 16 * 1024 * 1024;
 
 #[derive(Debug, Clone)]
 pub(super) struct Flotsam<'a> {
@@ -24,19 +13,11 @@ pub(super) struct Flotsam<'a> {
   tx_option: Option<&'a Transaction>,
 }
 
-lazy_static! {
-  pub static ref TX_LIMITS: HashMap<String, i16> = {
-      let mut m = HashMap::<String, i16>::new();
-      m.insert("default".into(), 2);
-      m
-  };
-}
-
 #[derive(Debug, Clone)]
 enum Origin {
   New {
+    curse: Option<Curse>,
     cursed: bool,
-    cursed_for_brc20: bool,
     fee: u64,
     parents: Vec<InscriptionId>,
     reinscription: bool,
@@ -54,18 +35,11 @@ pub(super) struct InscriptionUpdater<'a> {
   pub(super) cursed_inscription_count: u64,
   pub(super) flotsam: Vec<Flotsam<'a>>,
   pub(super) height: u32,
-  pub(super) db: &'a DB,
-  pub(super) id_to_sequence_number: &'a ColumnFamily,
-  pub(super) inscription_number_to_sequence_number: &'a ColumnFamily,
-  pub(super) id_to_txcnt: &'a ColumnFamily,
+  pub(super) block_batch: &'a BlockBatch<'a>,
   pub(super) next_sequence_number: u32,
   pub(super) reward: u64,
-  pub(super) sequence_number_to_entry: &'a ColumnFamily,
-  pub(super) ord_transfers: &'a ColumnFamily,
-  pub(super) ord_inscription_info: &'a ColumnFamily,
   pub(super) transfer_idx: u32,
   pub(super) early_transfer_info: HashMap<InscriptionId, u32>,
-  pub(super) write_options: &'a WriteOptions,
 }
 
 impl<'a> InscriptionUpdater<'a> {
@@ -112,8 +86,8 @@ impl<'a> InscriptionUpdater<'a> {
 
         let inscription_id = InscriptionEntry::load(
           self
-            .db
-            .get_cf(self.sequence_number_to_entry, sequence_number.to_be_bytes())?
+            .block_batch
+            .get("sequence_number_to_inscription_entry", &sequence_number.to_be_bytes())?
             .unwrap(),
         )
         .id;
@@ -126,7 +100,7 @@ impl<'a> InscriptionUpdater<'a> {
             sequence_number,
             old_satpoint,
           },
-          tx_option: Some(&tx),
+          tx_option: Some(tx),
         });
 
         inscribed_offsets
@@ -172,12 +146,12 @@ impl<'a> InscriptionUpdater<'a> {
             Some(Curse::Reinscription)
           } else {
             let initial_inscription_sequence_number =
-              u32::from_be_bytes(self.db.get_cf(self.id_to_sequence_number, id.store())?.unwrap().try_into().unwrap());
+              u32::from_be_bytes(self.block_batch.get("inscription_id_to_sequence_number", &id.store())?.unwrap().try_into().unwrap());
 
             let entry = InscriptionEntry::load(
               self
-                .db
-                .get_cf(self.sequence_number_to_entry, initial_inscription_sequence_number.to_be_bytes())?
+                .block_batch
+                .get("sequence_number_to_inscription_entry", &initial_inscription_sequence_number.to_be_bytes())?
                 .unwrap()
             );
 
@@ -204,8 +178,8 @@ impl<'a> InscriptionUpdater<'a> {
           inscription_id,
           offset,
           origin: Origin::New {
+            curse,
             cursed: curse.is_some() && !jubilant,
-            cursed_for_brc20: curse.is_some(),
             fee: 0,
             parents: inscription.payload.parents(),
             reinscription: inscribed_offsets.contains_key(&offset),
@@ -214,7 +188,7 @@ impl<'a> InscriptionUpdater<'a> {
               || inscription.payload.unrecognized_even_field,
             vindicated: curse.is_some() && jubilant,
           },
-          tx_option: Some(&tx),
+          tx_option: Some(tx),
         });
 
         inscribed_offsets
@@ -310,9 +284,9 @@ impl<'a> InscriptionUpdater<'a> {
         &mut output_utxo_entries[usize::try_from(new_satpoint.outpoint.vout).unwrap()];
 
 
-      let tx = flotsam.tx_option.clone().unwrap();
+      let tx = flotsam.tx_option.unwrap();
       self.update_inscription_location(
-        Some(&tx),
+        Some(tx),
         Some(&tx_out.script_pubkey),
         Some(&tx_out.value.to_sat()),
         sent_to_coinbase,
@@ -332,9 +306,9 @@ impl<'a> InscriptionUpdater<'a> {
           outpoint: OutPoint::null(),
           offset: flotsam.offset - output_value,
         };
-        let tx = flotsam.tx_option.clone().unwrap();
+        let tx = flotsam.tx_option.unwrap();
         self.update_inscription_location(
-          Some(&tx),
+          Some(tx),
           None,
           None,
           true,
@@ -365,26 +339,89 @@ impl<'a> InscriptionUpdater<'a> {
     }
   }
 
-  fn get_json_tx_limit(inscription_content_option: &Option<Vec<u8>>) -> i16 {
+  fn get_json_tx_limit(
+    inscription_content_option: &Option<Vec<u8>>,
+    decoded_content: Option<&[u8]>,
+    settings: &Settings,
+  ) -> i16 {
     if inscription_content_option.is_none() { return 0; }
     let inscription_content = inscription_content_option.as_ref().unwrap();
 
-    let json = serde_json::from_slice::<Value>(&inscription_content);
-    if json.is_err() {
-      return 0;
-    } else {
-      // check for event type and return tx limit
-      return TX_LIMITS["default"];
+    match serde_json::from_slice::<Value>(inscription_content) {
+      // look up the tx limit by protocol and operation
+      Ok(json) => settings.tx_limit(
+        json.get("p").and_then(Value::as_str),
+        json.get("op").and_then(Value::as_str),
+      ),
+      Err(_) => decoded_content
+        .and_then(|content| serde_json::from_slice::<Value>(content).ok())
+        .map(|json| {
+          settings.tx_limit(
+            json.get("p").and_then(Value::as_str),
+            json.get("op").and_then(Value::as_str),
+          )
+        })
+        .unwrap_or(0),
     }
   }
 
+  /// Decodes the body of an inscription that is tracked as text, as long as
+  /// decoded content counts for some protocol. The decoded body is only kept
+  /// if it is JSON of a protocol configured to allow it.
+  fn decoded_content(
+    inscription: &Inscription,
+    tracked: bool,
+    settings: &Settings,
+  ) -> Option<Vec<u8>> {
+    if !tracked || settings.decoded_content_protocols().is_empty() {
+      return None;
+    }
+
+    Self::decode_content(
+      inscription.body.as_deref(),
+      inscription.content_encoding.as_deref(),
+    )
+    .filter(|content| {
+      serde_json::from_slice::<Value>(content).is_ok_and(|json| {
+        json
+          .get("p")
+          .and_then(Value::as_str)
+          .is_some_and(|protocol| settings.decoded_content_protocols().contains(protocol))
+      })
+    })
+  }
+
+  /// Decodes `content` if `content_encoding` is `br` or `gzip`. Returns `None`
+  /// for other encodings, or if decoding fails.
+  fn decode_content(content: Option<&[u8]>, content_encoding: Option<&[u8]>) -> Option<Vec<u8>> {
+    let content = content?;
+    let content_encoding = std::str::from_utf8(content_encoding?).ok()?.trim();
+
+    let decoder: Box<dyn Read> = if content_encoding.eq_ignore_ascii_case("br") {
+      Box::new(brotli::Decompressor::new(content, 4096))
+    } else if content_encoding.eq_ignore_ascii_case("gzip") {
+      Box::new(flate2::read::GzDecoder::new(content))
+    } else {
+      return None;
+    };
+
+    let mut decoded = Vec::new();
+
+    decoder
+      .take(MAX_DECODED_CONTENT_LEN + 1)
+      .read_to_end(&mut decoded)
+      .ok()?;
+
+    (decoded.len() as u64 <= MAX_DECODED_CONTENT_LEN).then_some(decoded)
+  }
+
   fn is_text(inscription_content_type_option: &Option<Vec<u8>>) -> bool {
     if inscription_content_type_option.is_none() { return false; }
 
     let inscription_content_type = inscription_content_type_option.as_ref().unwrap();
-    let inscription_content_type_str = std::str::from_utf8(&inscription_content_type).unwrap_or("");
-    return inscription_content_type_str == "text/plain" || inscription_content_type_str.starts_with("text/plain;") ||
-            inscription_content_type_str == "application/json" || inscription_content_type_str.starts_with("application/json;"); // NOTE: added application/json for JSON5 etc.
+    let inscription_content_type_str = std::str::from_utf8(inscription_content_type).unwrap_or("");
+    inscription_content_type_str == "text/plain" || inscription_content_type_str.starts_with("text/plain;") ||
+            inscription_content_type_str == "application/json" || inscription_content_type_str.starts_with("application/json;") // NOTE: added application/json for JSON5 etc.
   }
 
   /*fn write_to_file(
@@ -453,6 +490,58 @@ impl<'a> InscriptionUpdater<'a> {
     unreachable!()
   }
 
+  /// Moves inscription `sequence_number` to `script_pubkey` in the script
+  /// pubkey index, or drops it if it has no spendable holder.
+  ///
+  /// Keys are `script_pubkey_len(2) ‖ script_pubkey ‖ sequence_number(4)`, so
+  /// the inscriptions of a script pubkey can be listed with a prefix scan, and
+  /// values are `inscription_id(36) ‖ satpoint(44)`.
+  fn update_script_pubkey_index(
+    &self,
+    sequence_number: u32,
+    inscription_id: InscriptionId,
+    satpoint: SatPoint,
+    script_pubkey: Option<&ScriptBuf>,
+  ) -> Result {
+    let key = |script_pubkey: &[u8]| {
+      [
+        &u16::try_from(script_pubkey.len()).unwrap().to_be_bytes(),
+        script_pubkey,
+        &sequence_number.to_be_bytes(),
+      ]
+      .concat()
+    };
+
+    if let Some(old) = self
+      .block_batch
+      .remove("sequence_number_to_script_pubkey", &sequence_number.to_be_bytes())?
+    {
+      self.block_batch.remove("script_pubkey_to_inscriptions", &key(&old))?;
+    }
+
+    let Some(script_pubkey) = script_pubkey.filter(|script_pubkey| {
+      !script_pubkey.is_empty()
+        && !script_pubkey.is_op_return()
+        && script_pubkey.len() <= usize::from(u16::MAX)
+    }) else {
+      return Ok(());
+    };
+
+    self.block_batch.put(
+      "script_pubkey_to_inscriptions",
+      &key(script_pubkey.as_bytes()),
+      &[inscription_id.store(), satpoint.store().to_vec()].concat(),
+    )?;
+
+    self.block_batch.put(
+      "sequence_number_to_script_pubkey",
+      &sequence_number.to_be_bytes(),
+      script_pubkey.as_bytes(),
+    )?;
+
+    Ok(())
+  }
+
   fn update_inscription_location(
     &mut self,
     tx_option: Option<&Transaction>,
@@ -470,11 +559,11 @@ impl<'a> InscriptionUpdater<'a> {
     let tx = tx_option.unwrap();
     let txid = tx.compute_txid();
     let inscription_id = flotsam.inscription_id;
-    let txcnt_of_inscr: i64 = self.db.get_cf(self.id_to_txcnt, &inscription_id.store())?
+    let txcnt_of_inscr: i64 = self.block_batch.get("inscription_id_to_txcnt", &inscription_id.store())?
         .map(|txcnt| i64::from_be_bytes(txcnt.try_into().unwrap()))
         .unwrap_or(0) + 1;
     if txcnt_of_inscr == 1 {
-      self.db.put_cf_opt(self.id_to_txcnt, &inscription_id.store(), &txcnt_of_inscr.to_be_bytes(), self.write_options)?;
+      self.block_batch.put("inscription_id_to_txcnt", &inscription_id.store(), &txcnt_of_inscr.to_be_bytes())?;
     }
 
     let (unbound, sequence_number) = match flotsam.origin {
@@ -482,19 +571,9 @@ impl<'a> InscriptionUpdater<'a> {
         sequence_number,
         old_satpoint,
       } => {
-        if let Some(ref sender) = index.event_sender {
-          sender.blocking_send(Event::InscriptionTransferred {
-            block_height: self.height,
-            inscription_id,
-            new_location: new_satpoint,
-            old_location: old_satpoint,
-            sequence_number,
-          })?;
-        }
-
-        let entry = self.db.get_cf(self.sequence_number_to_entry, &sequence_number.to_be_bytes())?;
+        let entry = self.block_batch.get("sequence_number_to_inscription_entry", &sequence_number.to_be_bytes())?;
         let entry = entry
-          .map(|entry| InscriptionEntry::load(entry))
+          .map(InscriptionEntry::load)
           .unwrap();
         let is_json_or_text = entry.is_json_or_text;
         let txcnt_limit = entry.txcnt_limit;
@@ -513,12 +592,30 @@ impl<'a> InscriptionUpdater<'a> {
             flotsam.inscription_id.store(),
             old_satpoint.store().to_vec(),
             new_satpoint.store().to_vec(),
-            vec![send_to_coinbase as u8],
+            vec![u8::from(send_to_coinbase)],
             new_output_value.unwrap_or(&0).to_be_bytes().to_vec(),
             txid.store().to_vec(),
             new_script_pubkey.unwrap_or(&ScriptBuf::new()).clone().into_bytes(),
           ].concat();
-          self.db.put_cf_opt(self.ord_transfers, &transfer_key, &transfer_data, self.write_options)?;
+          self.block_batch.put("ord_transfers", &transfer_key, &transfer_data)?;
+          self.block_batch.put(
+            "sequence_number_to_transfers",
+            &[sequence_number.to_be_bytes().as_slice(), &transfer_key].concat(),
+            &[],
+          )?;
+          index.metrics.transfers_written.inc();
+
+          if index.index_addresses {
+            self.update_script_pubkey_index(sequence_number, inscription_id, new_satpoint, new_script_pubkey)?;
+          }
+
+          index.emit(Event::InscriptionTransferred {
+            block_height: self.height,
+            inscription_id,
+            new_location: new_satpoint,
+            old_location: old_satpoint,
+            sequence_number,
+          })?;
 
           /* self.write_to_file(format!("cmd;{0};insert;transfer;{1};{old_satpoint};{new_satpoint};{send_to_coinbase};{2};{3}",
                     self.height, flotsam.inscription_id,
@@ -526,15 +623,18 @@ impl<'a> InscriptionUpdater<'a> {
                     new_output_value.unwrap_or(&0)), false)?; */
 
           if txcnt_of_inscr != 1 {
-            self.db.put_cf_opt(self.id_to_txcnt, &inscription_id.store(), &txcnt_of_inscr.to_be_bytes(), self.write_options)?;
+            self.block_batch.put("inscription_id_to_txcnt", &inscription_id.store(), &txcnt_of_inscr.to_be_bytes())?;
           }
+        } else if index.index_addresses && is_json_or_text {
+          // later transfers aren't tracked, so the holder is no longer known
+          self.update_script_pubkey_index(sequence_number, inscription_id, new_satpoint, None)?;
         }
 
         (false, sequence_number)
       }
       Origin::New {
+        curse,
         cursed,
-        cursed_for_brc20,
         fee: _,
         parents,
         reinscription,
@@ -555,36 +655,51 @@ impl<'a> InscriptionUpdater<'a> {
         self.next_sequence_number += 1;
 
         self
-          .db
-          .put_cf_opt(self.inscription_number_to_sequence_number, inscription_number.to_be_bytes(), sequence_number.to_be_bytes(), self.write_options)?;
+          .block_batch
+          .put("inscription_number_to_sequence_number", &inscription_number.to_be_bytes(), &sequence_number.to_be_bytes())?;
 
-        let inscription = ParsedEnvelope::from_transaction(&tx)
+        let inscription = ParsedEnvelope::from_transaction(tx)
             .get(flotsam.inscription_id.index as usize)
             .unwrap()
             .payload.clone();
+        let matches_filter = index
+          .inscription_filters
+          .iter()
+          .any(|filter| filter.matches(&inscription));
+        let delegate = inscription.delegate();
+        let pointer = inscription.pointer();
+        let is_text = Self::is_text(&inscription.content_type);
+        let decoded_content =
+          Self::decoded_content(&inscription, is_text || matches_filter, &index.settings);
         let inscription_content = inscription.body;
         let inscription_content_type = inscription.content_type;
         let inscription_metaprotocol = inscription.metaprotocol;
-        let json_txcnt_limit = Self::get_json_tx_limit(&inscription_content);
+        let json_txcnt_limit = Self::get_json_tx_limit(
+          &inscription_content,
+          decoded_content.as_deref(),
+          &index.settings,
+        );
         let is_json = json_txcnt_limit > 0;
-        let is_text = Self::is_text(&inscription_content_type);
-        let is_json_or_text = is_json || is_text;
+        // inscriptions matching a configured filter are tracked like text
+        let is_json_or_text = is_json || is_text || matches_filter;
 
         let txcnt_limit = if !unbound && is_json_or_text {
           let inscription_id_key = flotsam.inscription_id.store();
-          let inscription_info_data = [
-            inscription_number.to_be_bytes().to_vec(),
-            vec![cursed_for_brc20 as u8],
-            parents.get(0).map(|p| p.store()).unwrap_or(InscriptionId::from_str("0000000000000000000000000000000000000000000000000000000000000000i0")?.store()),
-            vec![is_json as u8],
-            inscription_content.as_ref().map(|content| content.len() as u32).unwrap_or(0).to_be_bytes().to_vec(),
-            inscription_content.unwrap_or(Vec::new()),
-            inscription_content_type.as_ref().map(|content_type| content_type.len() as u32).unwrap_or(0).to_be_bytes().to_vec(),
-            inscription_content_type.unwrap_or(Vec::new()),
-            inscription_metaprotocol.as_ref().map(|metaprotocol| metaprotocol.len() as u32).unwrap_or(0).to_be_bytes().to_vec(),
-            inscription_metaprotocol.unwrap_or(Vec::new()),
-          ].concat();
-          self.db.put_cf_opt(self.ord_inscription_info, &inscription_id_key, &inscription_info_data, self.write_options)?;
+          let inscription_info_data = InscriptionInfoEntry {
+            content: inscription_content,
+            content_encoding: inscription.content_encoding,
+            content_type: inscription_content_type,
+            cursed_for_brc20: curse.is_some(),
+            decoded_content,
+            delegate,
+            inscription_number,
+            is_json,
+            metadata: inscription.metadata,
+            metaprotocol: inscription_metaprotocol,
+            parents: parents.clone(),
+            pointer,
+          }.store();
+          self.block_batch.put("ord_inscription_info", &inscription_id_key, &inscription_info_data)?;
 
 
           //self.write_to_file(format!("cmd;{0};insert;number_to_id;{1};{2};{3};{4}", self.height, inscription_number, flotsam.inscription_id, if cursed_for_brc20 {"1"} else {"0"}, parents.get(0).map(|p| p.to_string()).unwrap_or(String::from(""))), false)?;
@@ -605,7 +720,7 @@ impl<'a> InscriptionUpdater<'a> {
             self.write_to_file(format!("cmd;{0};insert;content;{1};{2};{3};{4};{5}",
                                     self.height, flotsam.inscription_id, is_json, inscription_content_type_str, inscription_metaprotocol_str, inscription_content_hex_str), false)?; */
 
-            TX_LIMITS["default"]
+            index.settings.tx_limit(None, None)
           }
         } else {
           0
@@ -647,22 +762,12 @@ impl<'a> InscriptionUpdater<'a> {
           Charm::Vindicated.set(&mut charms);
         }
 
-        if let Some(ref sender) = index.event_sender {
-          sender.blocking_send(Event::InscriptionCreated {
-            block_height: self.height,
-            charms,
-            inscription_id,
-            location: (!unbound).then_some(new_satpoint),
-            parent_inscription_ids: parents,
-            sequence_number,
-          })?;
-        }
-
-        self.db.put_cf_opt(
-          self.sequence_number_to_entry,
-          sequence_number.to_be_bytes(),
+        self.block_batch.put(
+          "sequence_number_to_inscription_entry",
+          &sequence_number.to_be_bytes(),
           &InscriptionEntry {
             charms,
+            curse,
             id: inscription_id,
             inscription_number,
             sequence_number,
@@ -670,12 +775,11 @@ impl<'a> InscriptionUpdater<'a> {
             txcnt_limit,
           }
           .store(),
-          self.write_options,
         )?;
 
         self
-          .db
-          .put_cf_opt(self.id_to_sequence_number, &inscription_id.store(), sequence_number.to_be_bytes(), self.write_options)?;
+          .block_batch
+          .put("inscription_id_to_sequence_number", &inscription_id.store(), &sequence_number.to_be_bytes())?;
 
         if !unbound && is_json_or_text {
           let transfer_idx = if self.early_transfer_info.contains_key(&inscription_id) {
@@ -695,12 +799,31 @@ impl<'a> InscriptionUpdater<'a> {
               offset: 0,
             }.store().to_vec(),
             new_satpoint.store().to_vec(),
-            vec![send_to_coinbase as u8],
+            vec![u8::from(send_to_coinbase)],
             new_output_value.unwrap_or(&0).to_be_bytes().to_vec(),
             txid.store().to_vec(),
             new_script_pubkey.unwrap_or(&ScriptBuf::new()).clone().into_bytes(),
           ].concat();
-          self.db.put_cf_opt(self.ord_transfers, &transfer_key, &transfer_data, self.write_options)?;
+          self.block_batch.put("ord_transfers", &transfer_key, &transfer_data)?;
+          self.block_batch.put(
+            "sequence_number_to_transfers",
+            &[sequence_number.to_be_bytes().as_slice(), &transfer_key].concat(),
+            &[],
+          )?;
+          index.metrics.transfers_written.inc();
+
+          if index.index_addresses {
+            self.update_script_pubkey_index(sequence_number, inscription_id, new_satpoint, new_script_pubkey)?;
+          }
+
+          index.emit(Event::InscriptionCreated {
+            block_height: self.height,
+            charms,
+            inscription_id,
+            location: Some(new_satpoint),
+            parent_inscription_ids: parents,
+            sequence_number,
+          })?;
 
           /* self.write_to_file(format!("cmd;{0};insert;transfer;{1};;{new_satpoint};{send_to_coinbase};{2};{3}",
                     self.height, flotsam.inscription_id,
@@ -741,3 +864,74 @@ impl<'a> InscriptionUpdater<'a> {
     Ok(())
   }
 }
+
+#[cfg(test)]
+mod tests {
+  use {super::*, crate::options::Options, flate2::write::GzEncoder, std::io::Write};
+
+  const BRC20: &[u8] = br#"{"p":"brc-20","op":"mint","tick":"ordi","amt":"1000"}"#;
+
+  fn gzip(content: &[u8]) -> Inscription {
+    let mut encoder = GzEncoder::new(Vec::new(), flate2::Compression::default());
+    encoder.write_all(content).unwrap();
+
+    Inscription {
+      body: Some(encoder.finish().unwrap()),
+      content_encoding: Some("gzip".into()),
+      content_type: Some("text/plain".into()),
+      ..default()
+    }
+  }
+
+  fn settings(decoded_content_protocols: &[&str]) -> Settings {
+    Settings::merge(
+      Options {
+        regtest: true,
+        decoded_content_protocols: decoded_content_protocols
+          .iter()
+          .map(|protocol| protocol.to_string())
+          .collect(),
+        ..default()
+      },
+      BTreeMap::new(),
+    )
+    .unwrap()
+  }
+
+  #[test]
+  fn tracked_content_of_configured_protocols_is_decoded() {
+    assert_eq!(
+      InscriptionUpdater::decoded_content(&gzip(BRC20), true, &settings(&["brc-20"])),
+      Some(BRC20.to_vec()),
+    );
+  }
+
+  #[test]
+  fn untracked_content_is_not_decoded() {
+    assert_eq!(
+      InscriptionUpdater::decoded_content(&gzip(BRC20), false, &settings(&["brc-20"])),
+      None,
+    );
+  }
+
+  #[test]
+  fn content_is_not_decoded_without_configured_protocols() {
+    assert_eq!(
+      InscriptionUpdater::decoded_content(&gzip(BRC20), true, &settings(&[])),
+      None,
+    );
+  }
+
+  #[test]
+  fn decoded_content_of_other_protocols_is_dropped() {
+    assert_eq!(
+      InscriptionUpdater::decoded_content(&gzip(BRC20), true, &settings(&["sns"])),
+      None,
+    );
+
+    assert_eq!(
+      InscriptionUpdater::decoded_content(&gzip(b"hello"), true, &settings(&["brc-20"])),
+      None,
+    );
+  }
+}
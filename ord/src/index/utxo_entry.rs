@@ -35,12 +35,10 @@ pub struct UtxoEntry {
 }
 
 impl UtxoEntry {
-  pub fn parse(&self) -> ParsedUtxoEntry {
-    let sats;
-
+  pub fn parse(&self) -> ParsedUtxoEntry<'_> {
     let mut offset = 0;
     let (value, varint_len) = varint::decode(&self.bytes).unwrap();
-    sats = Sats::Value(value.try_into().unwrap());
+    let sats = Sats::Value(value.try_into().unwrap());
     offset += varint_len;
 
     ParsedUtxoEntry {
@@ -0,0 +1,734 @@
+use {super::*, ordinals::varint};
+
+/// Each check reports at most this many example errors.
+const MAX_EXAMPLES: usize = 10;
+
+/// Length of an `ord_transfers` value without the trailing script pubkey.
+const TRANSFER_FIXED_LEN: usize = 36 + 44 + 44 + 1 + 8 + 32;
+
+#[derive(Serialize, Debug, Default)]
+pub struct CheckReport {
+  pub checked: u64,
+  pub errors: u64,
+  pub examples: Vec<String>,
+}
+
+impl CheckReport {
+  fn error(&mut self, message: impl FnOnce() -> String) {
+    self.errors += 1;
+    if self.examples.len() < MAX_EXAMPLES {
+      self.examples.push(message());
+    }
+  }
+}
+
+#[derive(Serialize, Debug)]
+pub struct VerifyReport {
+  pub blocks_indexed: u32,
+  pub checks: BTreeMap<&'static str, CheckReport>,
+  pub inscriptions: u64,
+  pub ok: bool,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Known {
+  Missing,
+  Untracked,
+  Tracked,
+}
+
+/// Cross-checks the column families of the index at `settings.index()`. The
+/// index is opened read-only, so this sees the last flushed state and can run
+/// while the index is being updated.
+pub(crate) fn run(settings: &Settings) -> Result<VerifyReport> {
+  let path = settings.index().join("index.db");
+
+  ensure!(path.exists(), "no index found at {}", path.display());
+
+  let existing = DB::list_cf(&Options::default(), &path)?;
+
+  let db = DB::open_cf_for_read_only(
+    &settings.rocksdb().db_options(),
+    &path,
+    COLUMN_FAMILIES
+      .iter()
+      .filter(|name| existing.iter().any(|existing| existing == *name)),
+    false,
+  )?;
+
+  let cf = |name: &str| {
+    db.cf_handle(name)
+      .ok_or_else(|| anyhow!("Failed to open column family '{name}'"))
+  };
+
+  let height_to_block_header = cf("height_to_block_header")?;
+  let inscription_id_to_sequence_number = cf("inscription_id_to_sequence_number")?;
+  let inscription_id_to_txcnt = cf("inscription_id_to_txcnt")?;
+  let inscription_number_to_sequence_number = cf("inscription_number_to_sequence_number")?;
+  let ord_inscription_info = cf("ord_inscription_info")?;
+  let ord_transfers = cf("ord_transfers")?;
+  let outpoint_to_utxo_entry = cf("outpoint_to_utxo_entry")?;
+  let sequence_number_to_inscription_entry = cf("sequence_number_to_inscription_entry")?;
+  let sequence_number_to_transfers = cf("sequence_number_to_transfers")?;
+  let statistic_to_count = cf("statistic_to_count")?;
+
+  let load_entry = |sequence_number: u32| -> Result<Option<InscriptionEntry>> {
+    Ok(
+      db.get_cf(
+        sequence_number_to_inscription_entry,
+        sequence_number.to_be_bytes(),
+      )?
+      .filter(|entry| entry.len() >= 49)
+      .map(InscriptionEntry::load),
+    )
+  };
+
+  let load_sequence_number = |inscription_id: &[u8]| -> Result<Option<u32>> {
+    Ok(
+      db.get_cf(inscription_id_to_sequence_number, inscription_id)?
+        .and_then(|value| value.try_into().ok())
+        .map(u32::from_be_bytes),
+    )
+  };
+
+  let statistic = |statistic: Statistic| -> Result<u64> {
+    Ok(
+      db.get_cf(statistic_to_count, statistic.key().to_be_bytes())?
+        .map(|count| u64::from_be_bytes(count.try_into().unwrap()))
+        .unwrap_or_default(),
+    )
+  };
+
+  let blocks_indexed = db
+    .iterator_cf(height_to_block_header, IteratorMode::End)
+    .next()
+    .transpose()?
+    .map(|(height, _header)| u32::from_be_bytes((*height).try_into().unwrap()) + 1)
+    .unwrap_or(0);
+
+  let mut checks = BTreeMap::new();
+
+  log::info!("Checking sequence_number_to_inscription_entry…");
+
+  let mut known = Vec::new();
+  let mut report = CheckReport::default();
+
+  for entry in db.iterator_cf(sequence_number_to_inscription_entry, IteratorMode::Start) {
+    let (key, value) = entry?;
+    report.checked += 1;
+
+    let Ok(key) = <[u8; 4]>::try_from(&*key) else {
+      report.error(|| format!("invalid key {}", hex::encode(&key)));
+      continue;
+    };
+
+    let sequence_number = u32::from_be_bytes(key);
+
+    if usize::try_from(sequence_number).unwrap() != known.len() {
+      let expected = known.len();
+      report.error(|| format!("sequence numbers {expected}..{sequence_number} are missing"));
+    }
+
+    known.resize(usize::try_from(sequence_number).unwrap(), Known::Missing);
+
+    if value.len() < 49 {
+      report.error(|| format!("entry {sequence_number} is truncated"));
+      known.push(Known::Missing);
+      continue;
+    }
+
+    let entry = InscriptionEntry::load(value.to_vec());
+
+    if entry.sequence_number != sequence_number {
+      report.error(|| {
+        format!(
+          "entry {sequence_number} has sequence number {}",
+          entry.sequence_number
+        )
+      });
+    }
+
+    if load_sequence_number(&entry.id.store())? != Some(sequence_number) {
+      report.error(|| {
+        format!(
+          "inscription {} of entry {sequence_number} does not map back to it",
+          entry.id
+        )
+      });
+    }
+
+    if db
+      .get_cf(
+        inscription_number_to_sequence_number,
+        entry.inscription_number.to_be_bytes(),
+      )?
+      .as_deref()
+      != Some(&sequence_number.to_be_bytes())
+    {
+      report.error(|| {
+        format!(
+          "inscription number {} of entry {sequence_number} does not map back to it",
+          entry.inscription_number
+        )
+      });
+    }
+
+    known.push(if entry.is_json_or_text && entry.txcnt_limit > 0 {
+      Known::Tracked
+    } else {
+      Known::Untracked
+    });
+  }
+
+  let inscriptions = report.checked;
+  checks.insert("sequence_number_to_inscription_entry", report);
+
+  let known_at = |sequence_number: u32| {
+    known
+      .get(usize::try_from(sequence_number).unwrap())
+      .copied()
+      .unwrap_or(Known::Missing)
+  };
+
+  log::info!("Checking inscription_id_to_sequence_number…");
+
+  let mut report = CheckReport::default();
+
+  for entry in db.iterator_cf(inscription_id_to_sequence_number, IteratorMode::Start) {
+    let (key, value) = entry?;
+    report.checked += 1;
+
+    let (Ok(id), Ok(value)) = (<[u8; 36]>::try_from(&*key), <[u8; 4]>::try_from(&*value)) else {
+      report.error(|| format!("invalid entry {}", hex::encode(&key)));
+      continue;
+    };
+
+    let inscription_id = InscriptionId::load(id.to_vec());
+    let sequence_number = u32::from_be_bytes(value);
+
+    match load_entry(sequence_number)? {
+      Some(entry) if entry.id == inscription_id => {}
+      Some(entry) => report.error(|| {
+        format!(
+          "inscription {inscription_id} maps to sequence number {sequence_number} of inscription {}",
+          entry.id
+        )
+      }),
+      None => report.error(|| {
+        format!("inscription {inscription_id} maps to unknown sequence number {sequence_number}")
+      }),
+    }
+  }
+
+  if report.checked != inscriptions {
+    let checked = report.checked;
+    report.error(|| format!("{checked} inscription ids for {inscriptions} inscription entries"));
+  }
+
+  checks.insert("inscription_id_to_sequence_number", report);
+
+  log::info!("Checking inscription_number_to_sequence_number…");
+
+  let mut report = CheckReport::default();
+  let mut blessed = 0u64;
+  let mut cursed = 0u64;
+  let mut max_blessed = None;
+  let mut min_cursed = None;
+
+  for entry in db.iterator_cf(inscription_number_to_sequence_number, IteratorMode::Start) {
+    let (key, value) = entry?;
+    report.checked += 1;
+
+    let (Ok(key), Ok(value)) = (<[u8; 4]>::try_from(&*key), <[u8; 4]>::try_from(&*value)) else {
+      report.error(|| format!("invalid entry {}", hex::encode(&key)));
+      continue;
+    };
+
+    let inscription_number = i32::from_be_bytes(key);
+    let sequence_number = u32::from_be_bytes(value);
+
+    if inscription_number < 0 {
+      cursed += 1;
+      min_cursed =
+        Some(min_cursed.map_or(inscription_number, |min: i32| min.min(inscription_number)));
+    } else {
+      blessed += 1;
+      max_blessed = max_blessed.max(Some(inscription_number));
+    }
+
+    match load_entry(sequence_number)? {
+      Some(entry) if entry.inscription_number == inscription_number => {}
+      Some(entry) => report.error(|| {
+        format!(
+          "inscription number {inscription_number} maps to sequence number {sequence_number} numbered {}",
+          entry.inscription_number
+        )
+      }),
+      None => report.error(|| {
+        format!(
+          "inscription number {inscription_number} maps to unknown sequence number {sequence_number}"
+        )
+      }),
+    }
+  }
+
+  let blessed_span = max_blessed.map_or(0, |max| u64::try_from(max).unwrap() + 1);
+  let cursed_span = min_cursed.map_or(0, |min: i32| u64::from(min.unsigned_abs()));
+
+  if blessed != blessed_span || blessed != statistic(Statistic::BlessedInscriptions)? {
+    let recorded = statistic(Statistic::BlessedInscriptions)?;
+    report.error(|| {
+      format!(
+        "{blessed} blessed inscription numbers, spanning 0..{blessed_span}, with {recorded} recorded"
+      )
+    });
+  }
+
+  if cursed != cursed_span || cursed != statistic(Statistic::CursedInscriptions)? {
+    let recorded = statistic(Statistic::CursedInscriptions)?;
+    report.error(|| {
+      format!(
+        "{cursed} cursed inscription numbers, spanning -{cursed_span}..0, with {recorded} recorded"
+      )
+    });
+  }
+
+  checks.insert("inscription_number_to_sequence_number", report);
+
+  log::info!("Checking outpoint_to_utxo_entry…");
+
+  let mut report = CheckReport::default();
+  let mut located = vec![false; known.len()];
+
+  for entry in db.iterator_cf(outpoint_to_utxo_entry, IteratorMode::Start) {
+    let (key, value) = entry?;
+    report.checked += 1;
+
+    let outpoint = match <[u8; 36]>::try_from(&*key) {
+      Ok(key) => OutPoint::load(key),
+      Err(_) => {
+        report.error(|| format!("invalid key {}", hex::encode(&key)));
+        continue;
+      }
+    };
+
+    let Some(sequence_numbers) = utxo_sequence_numbers(&value) else {
+      report.error(|| format!("entry of {outpoint} is malformed"));
+      continue;
+    };
+
+    for sequence_number in sequence_numbers {
+      if known_at(sequence_number) == Known::Missing {
+        report.error(|| format!("{outpoint} holds unknown sequence number {sequence_number}"));
+        continue;
+      }
+
+      let located = &mut located[usize::try_from(sequence_number).unwrap()];
+
+      if *located {
+        report.error(|| {
+          format!(
+            "sequence number {sequence_number} is in more than one output, including {outpoint}"
+          )
+        });
+      }
+
+      *located = true;
+    }
+  }
+
+  checks.insert("outpoint_to_utxo_entry", report);
+
+  log::info!("Checking ord_transfers…");
+
+  let mut report = CheckReport::default();
+  let mut transfers = vec![0u16; known.len()];
+
+  for entry in db.iterator_cf(ord_transfers, IteratorMode::Start) {
+    let (key, value) = entry?;
+    report.checked += 1;
+
+    if key.len() != 8 || value.len() < TRANSFER_FIXED_LEN {
+      report.error(|| format!("invalid entry {}", hex::encode(&key)));
+      continue;
+    }
+
+    let inscription_id = InscriptionId::load(value[..36].to_vec());
+
+    let Some(sequence_number) = load_sequence_number(&value[..36])? else {
+      report.error(|| {
+        format!(
+          "transfer {} is of unknown inscription {inscription_id}",
+          hex::encode(&key)
+        )
+      });
+      continue;
+    };
+
+    if known_at(sequence_number) != Known::Tracked {
+      report.error(|| {
+        format!(
+          "transfer {} is of untracked inscription {inscription_id}",
+          hex::encode(&key)
+        )
+      });
+    }
+
+    if let Some(count) = transfers.get_mut(usize::try_from(sequence_number).unwrap()) {
+      *count = count.saturating_add(1);
+    }
+  }
+
+  checks.insert("ord_transfers", report);
+
+  log::info!("Checking ord_inscription_info…");
+
+  let mut report = CheckReport::default();
+
+  for entry in db.iterator_cf(ord_inscription_info, IteratorMode::Start) {
+    let (key, _value) = entry?;
+    report.checked += 1;
+
+    match load_sequence_number(&key)? {
+      Some(sequence_number) if known_at(sequence_number) == Known::Tracked => {}
+      Some(_) => report.error(|| format!("info of untracked inscription {}", hex::encode(&key))),
+      None => report.error(|| format!("info of unknown inscription {}", hex::encode(&key))),
+    }
+  }
+
+  checks.insert("ord_inscription_info", report);
+
+  log::info!("Checking inscription_id_to_txcnt…");
+
+  let mut report = CheckReport::default();
+
+  for entry in db.iterator_cf(inscription_id_to_txcnt, IteratorMode::Start) {
+    let (key, value) = entry?;
+    report.checked += 1;
+
+    let Ok(value) = <[u8; 8]>::try_from(&*value) else {
+      report.error(|| format!("invalid entry {}", hex::encode(&key)));
+      continue;
+    };
+
+    let txcnt = i64::from_be_bytes(value);
+
+    let Some(sequence_number) = load_sequence_number(&key)? else {
+      report.error(|| format!("txcnt of unknown inscription {}", hex::encode(&key)));
+      continue;
+    };
+
+    let expected = match known_at(sequence_number) {
+      Known::Tracked => txcnt,
+      _ => 0,
+    };
+
+    let Some(count) = transfers.get_mut(usize::try_from(sequence_number).unwrap()) else {
+      continue;
+    };
+
+    if i64::from(*count) != expected {
+      let actual = *count;
+      report.error(|| {
+        format!(
+          "inscription {} has txcnt {txcnt} but {actual} transfer rows",
+          hex::encode(&key)
+        )
+      });
+    }
+
+    // Inscriptions with transfer rows left over after this have no txcnt
+    *count = 0;
+  }
+
+  for (sequence_number, count) in transfers.iter().enumerate() {
+    if *count > 0 {
+      report.error(|| {
+        format!("sequence number {sequence_number} has {count} transfer rows but no txcnt")
+      });
+    }
+  }
+
+  checks.insert("inscription_id_to_txcnt", report);
+
+  log::info!("Checking sequence_number_to_transfers…");
+
+  let mut report = CheckReport::default();
+
+  for entry in db.iterator_cf(sequence_number_to_transfers, IteratorMode::Start) {
+    let (key, _value) = entry?;
+    report.checked += 1;
+
+    if key.len() != 12 {
+      report.error(|| format!("invalid key {}", hex::encode(&key)));
+      continue;
+    }
+
+    let sequence_number = u32::from_be_bytes(key[..4].try_into().unwrap());
+    let transfer_key = &key[4..];
+
+    let Some(transfer) = db.get_cf(ord_transfers, transfer_key)? else {
+      report.error(|| {
+        format!(
+          "sequence number {sequence_number} has missing transfer {}",
+          hex::encode(transfer_key)
+        )
+      });
+      continue;
+    };
+
+    if transfer.len() < 36 || load_sequence_number(&transfer[..36])? != Some(sequence_number) {
+      report.error(|| {
+        format!(
+          "transfer {} is not of sequence number {sequence_number}",
+          hex::encode(transfer_key)
+        )
+      });
+    }
+  }
+
+  let transfer_rows = checks["ord_transfers"].checked;
+
+  if report.checked != transfer_rows {
+    let checked = report.checked;
+    report.error(|| format!("{checked} entries for {transfer_rows} transfer rows"));
+  }
+
+  checks.insert("sequence_number_to_transfers", report);
+
+  if let (Some(script_pubkey_to_inscriptions), Some(sequence_number_to_script_pubkey)) = (
+    db.cf_handle("script_pubkey_to_inscriptions"),
+    db.cf_handle("sequence_number_to_script_pubkey"),
+  ) {
+    log::info!("Checking script_pubkey_to_inscriptions…");
+
+    let mut report = CheckReport::default();
+
+    for entry in db.iterator_cf(script_pubkey_to_inscriptions, IteratorMode::Start) {
+      let (key, value) = entry?;
+      report.checked += 1;
+
+      let script_pubkey_len = key
+        .get(..2)
+        .map(|len| usize::from(u16::from_be_bytes(len.try_into().unwrap())));
+
+      if script_pubkey_len.map(|len| 2 + len + 4) != Some(key.len()) || value.len() != 36 + 44 {
+        report.error(|| format!("invalid entry {}", hex::encode(&key)));
+        continue;
+      }
+
+      let script_pubkey = &key[2..key.len() - 4];
+      let sequence_number = u32::from_be_bytes(key[key.len() - 4..].try_into().unwrap());
+
+      if known_at(sequence_number) != Known::Tracked {
+        report
+          .error(|| format!("script pubkey entry of untracked sequence number {sequence_number}"));
+      }
+
+      if load_sequence_number(&value[..36])? != Some(sequence_number) {
+        report.error(|| {
+          format!(
+            "script pubkey entry of sequence number {sequence_number} is of inscription {}",
+            InscriptionId::load(value[..36].to_vec())
+          )
+        });
+      }
+
+      if db
+        .get_cf(
+          sequence_number_to_script_pubkey,
+          sequence_number.to_be_bytes(),
+        )?
+        .as_deref()
+        != Some(script_pubkey)
+      {
+        report.error(|| {
+          format!(
+            "sequence number {sequence_number} is held by {} but does not map back to it",
+            hex::encode(script_pubkey)
+          )
+        });
+      }
+    }
+
+    let reverse = db
+      .iterator_cf(sequence_number_to_script_pubkey, IteratorMode::Start)
+      .count() as u64;
+
+    if reverse != report.checked {
+      let checked = report.checked;
+      report.error(|| format!("{checked} script pubkey entries for {reverse} sequence numbers"));
+    }
+
+    checks.insert("script_pubkey_to_inscriptions", report);
+  }
+
+  let ok = checks.values().all(|report| report.errors == 0);
+
+  Ok(VerifyReport {
+    blocks_indexed,
+    checks,
+    inscriptions,
+    ok,
+  })
+}
+
+/// The sequence numbers in a UTXO entry, `None` if it is malformed.
+fn utxo_sequence_numbers(mut value: &[u8]) -> Option<Vec<u32>> {
+  let (_value, len) = varint::decode(value).ok()?;
+  value = &value[len..];
+
+  let mut sequence_numbers = Vec::new();
+
+  while !value.is_empty() {
+    sequence_numbers.push(u32::from_be_bytes(value.get(..4)?.try_into().unwrap()));
+    let (_offset, len) = varint::decode(&value[4..]).ok()?;
+    value = &value[4 + len..];
+  }
+
+  Some(sequence_numbers)
+}
+
+#[cfg(test)]
+mod tests {
+  use {
+    super::*,
+    crate::{index::testing::Context, options::Options},
+    mockcore::TransactionTemplate,
+  };
+
+  /// Indexes a text inscription and a transfer of it, and shuts the index
+  /// down so that it can be verified.
+  fn inscribed() -> (tempfile::TempDir, Settings, InscriptionId) {
+    let mut context = Context::new(Options::default());
+
+    context.mine_blocks(1);
+
+    let inscription = Inscription {
+      content_type: Some("text/plain".into()),
+      body: Some("foo".into()),
+      ..default()
+    };
+
+    let reveal = context.core.broadcast_tx(TransactionTemplate {
+      inputs: &[(
+        1,
+        0,
+        0,
+        Witness::from_slice(&[
+          inscription
+            .append_reveal_script_to_builder(script::Builder::new())
+            .into_script()
+            .into_bytes(),
+          Vec::new(),
+        ]),
+      )],
+      ..default()
+    });
+    context.mine_blocks(1);
+
+    context.core.broadcast_tx(TransactionTemplate {
+      inputs: &[(2, 1, 0, Witness::new())],
+      ..default()
+    });
+    context.mine_blocks(1);
+
+    let Context { index, tempdir, .. } = context;
+
+    index.shut_down().unwrap();
+
+    let settings = Context::settings(&tempdir, Options::default());
+
+    let inscription_id = InscriptionId {
+      txid: reveal,
+      index: 0,
+    };
+
+    (tempdir, settings, inscription_id)
+  }
+
+  #[test]
+  fn consistent_index_is_ok() {
+    let (_tempdir, settings, _inscription_id) = inscribed();
+
+    let report = run(&settings).unwrap();
+
+    assert!(report.ok, "{report:?}");
+    assert_eq!(report.blocks_indexed, 4);
+    assert_eq!(report.inscriptions, 1);
+    assert_eq!(report.checks["ord_inscription_info"].checked, 1);
+    assert_eq!(report.checks["ord_transfers"].checked, 2);
+    assert_eq!(report.checks["sequence_number_to_transfers"].checked, 2);
+  }
+
+  #[test]
+  fn missing_entries_are_reported() {
+    let (_tempdir, settings, inscription_id) = inscribed();
+
+    {
+      let db = Database::open(settings.index().join("index.db"), settings.rocksdb()).unwrap();
+      db.delete_cf(
+        db.cf_handle("inscription_id_to_sequence_number").unwrap(),
+        inscription_id.store(),
+      )
+      .unwrap();
+    }
+
+    let report = run(&settings).unwrap();
+
+    assert!(!report.ok);
+
+    assert_eq!(
+      report.checks["inscription_id_to_sequence_number"].examples,
+      ["0 inscription ids for 1 inscription entries"],
+    );
+
+    assert_eq!(
+      report.checks["ord_inscription_info"].examples,
+      [format!(
+        "info of unknown inscription {}",
+        hex::encode(inscription_id.store())
+      )],
+    );
+
+    let transfers = &report.checks["ord_transfers"];
+    assert_eq!(transfers.errors, 2);
+    assert!(transfers.examples[0].ends_with(&format!("is of unknown inscription {inscription_id}")));
+
+    assert_eq!(
+      report.checks["sequence_number_to_inscription_entry"].examples,
+      [format!(
+        "inscription {inscription_id} of entry 0 does not map back to it"
+      )],
+    );
+  }
+
+  #[test]
+  fn missing_index_is_an_error() {
+    let tempdir = tempfile::TempDir::new().unwrap();
+
+    assert!(run(&Context::settings(&tempdir, Options::default()))
+      .unwrap_err()
+      .to_string()
+      .starts_with("no index found at "));
+  }
+
+  #[test]
+  fn utxo_entries_are_parsed() {
+    let entry = [
+      varint::encode(100_000_000),
+      7u32.to_be_bytes().to_vec(),
+      varint::encode(0),
+      300u32.to_be_bytes().to_vec(),
+      varint::encode(1_000),
+    ]
+    .concat();
+
+    assert_eq!(utxo_sequence_numbers(&entry), Some(vec![7, 300]));
+    assert_eq!(utxo_sequence_numbers(&varint::encode(0)), Some(Vec::new()));
+
+    assert_eq!(utxo_sequence_numbers(&[]), None);
+    assert_eq!(utxo_sequence_numbers(&entry[..entry.len() - 1]), None);
+    assert_eq!(utxo_sequence_numbers(&entry[..entry.len() - 3]), None);
+  }
+}
@@ -0,0 +1,320 @@
+use super::*;
+
+/// Selects inscriptions that are tracked in `ord_inscription_info` and
+/// `ord_transfers` even though their content is neither JSON nor text.
+pub trait InscriptionFilter: Send + Sync {
+  fn matches(&self, inscription: &Inscription) -> bool;
+}
+
+/// A built-in inscription filter, written as `<KIND>=<VALUE>[,<VALUE>…]`.
+#[derive(Debug, PartialEq, Clone, DeserializeFromStr, SerializeDisplay)]
+pub enum InscriptionFilterKind {
+  ContentType(Vec<String>),
+  MetadataKey(Vec<String>),
+  Metaprotocol(Vec<String>),
+  Parent(Vec<InscriptionId>),
+}
+
+impl InscriptionFilterKind {
+  pub fn build(&self) -> Box<dyn InscriptionFilter> {
+    match self {
+      Self::ContentType(content_types) => Box::new(ContentTypeFilter(content_types.clone())),
+      Self::MetadataKey(keys) => Box::new(MetadataKeyFilter(keys.clone())),
+      Self::Metaprotocol(metaprotocols) => Box::new(MetaprotocolFilter(metaprotocols.clone())),
+      Self::Parent(parents) => Box::new(ParentFilter(parents.clone())),
+    }
+  }
+}
+
+impl Display for InscriptionFilterKind {
+  fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+    match self {
+      Self::ContentType(content_types) => write!(f, "content-type={}", content_types.join(",")),
+      Self::MetadataKey(keys) => write!(f, "metadata-key={}", keys.join(",")),
+      Self::Metaprotocol(metaprotocols) => write!(f, "metaprotocol={}", metaprotocols.join(",")),
+      Self::Parent(parents) => write!(
+        f,
+        "parent={}",
+        parents
+          .iter()
+          .map(|parent| parent.to_string())
+          .collect::<Vec<String>>()
+          .join(",")
+      ),
+    }
+  }
+}
+
+impl FromStr for InscriptionFilterKind {
+  type Err = Error;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    let (kind, values) = s
+      .split_once('=')
+      .ok_or_else(|| anyhow!("invalid inscription filter `{s}`, expected `<KIND>=<VALUE>`"))?;
+
+    let values = values
+      .split(',')
+      .map(str::trim)
+      .filter(|value| !value.is_empty())
+      .map(String::from)
+      .collect::<Vec<String>>();
+
+    ensure!(!values.is_empty(), "inscription filter `{s}` has no values");
+
+    Ok(match kind.trim() {
+      "content-type" => Self::ContentType(values),
+      "metadata-key" => Self::MetadataKey(values),
+      "metaprotocol" => Self::Metaprotocol(values),
+      "parent" => Self::Parent(
+        values
+          .iter()
+          .map(|value| value.parse())
+          .collect::<Result<Vec<InscriptionId>, _>>()?,
+      ),
+      kind => bail!(
+        "unknown inscription filter kind `{kind}`, expected one of `content-type`, `metadata-key`, `metaprotocol` or `parent`"
+      ),
+    })
+  }
+}
+
+/// Matches content types exactly, ignoring parameters such as `;charset=utf-8`.
+/// A `type/*` entry matches every subtype.
+pub struct ContentTypeFilter(pub Vec<String>);
+
+impl InscriptionFilter for ContentTypeFilter {
+  fn matches(&self, inscription: &Inscription) -> bool {
+    let Some(content_type) = inscription.content_type() else {
+      return false;
+    };
+
+    let media_type = content_type
+      .split(';')
+      .next()
+      .unwrap_or_default()
+      .trim();
+
+    self.0.iter().any(|allowed| match allowed.strip_suffix("/*") {
+      Some(top_level) => media_type
+        .split_once('/')
+        .is_some_and(|(media_top_level, _)| media_top_level == top_level),
+      None => media_type == allowed,
+    })
+  }
+}
+
+/// Matches inscriptions whose CBOR metadata is a map containing one of the keys.
+pub struct MetadataKeyFilter(pub Vec<String>);
+
+impl InscriptionFilter for MetadataKeyFilter {
+  fn matches(&self, inscription: &Inscription) -> bool {
+    let Some(Value::Map(entries)) = inscription.metadata() else {
+      return false;
+    };
+
+    entries.iter().any(|(key, _)| {
+      key
+        .as_text()
+        .is_some_and(|key| self.0.iter().any(|wanted| wanted == key))
+    })
+  }
+}
+
+/// Matches the metaprotocol field exactly.
+pub struct MetaprotocolFilter(pub Vec<String>);
+
+impl InscriptionFilter for MetaprotocolFilter {
+  fn matches(&self, inscription: &Inscription) -> bool {
+    inscription
+      .metaprotocol()
+      .is_some_and(|metaprotocol| self.0.iter().any(|wanted| wanted == metaprotocol))
+  }
+}
+
+/// Matches children of any of the parent inscriptions.
+pub struct ParentFilter(pub Vec<InscriptionId>);
+
+impl InscriptionFilter for ParentFilter {
+  fn matches(&self, inscription: &Inscription) -> bool {
+    inscription
+      .parents()
+      .iter()
+      .any(|parent| self.0.contains(parent))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  const PARENT: &str = "1111111111111111111111111111111111111111111111111111111111111111i0";
+
+  fn parent() -> InscriptionId {
+    PARENT.parse().unwrap()
+  }
+
+  fn matches(filter: &str, inscription: Inscription) -> bool {
+    filter
+      .parse::<InscriptionFilterKind>()
+      .unwrap()
+      .build()
+      .matches(&inscription)
+  }
+
+  #[test]
+  fn filters_are_parsed() {
+    assert_eq!(
+      "content-type=image/png, text/*"
+        .parse::<InscriptionFilterKind>()
+        .unwrap(),
+      InscriptionFilterKind::ContentType(vec!["image/png".into(), "text/*".into()]),
+    );
+
+    assert_eq!(
+      "metadata-key=title"
+        .parse::<InscriptionFilterKind>()
+        .unwrap(),
+      InscriptionFilterKind::MetadataKey(vec!["title".into()]),
+    );
+
+    assert_eq!(
+      " metaprotocol = brc-20,,sns "
+        .parse::<InscriptionFilterKind>()
+        .unwrap(),
+      InscriptionFilterKind::Metaprotocol(vec!["brc-20".into(), "sns".into()]),
+    );
+
+    assert_eq!(
+      format!("parent={PARENT}")
+        .parse::<InscriptionFilterKind>()
+        .unwrap(),
+      InscriptionFilterKind::Parent(vec![parent()]),
+    );
+  }
+
+  #[test]
+  fn filters_round_trip_through_their_display() {
+    for filter in [
+      "content-type=image/png,text/*".to_string(),
+      "metadata-key=title".into(),
+      "metaprotocol=brc-20,sns".into(),
+      format!("parent={PARENT}"),
+    ] {
+      assert_eq!(
+        filter.parse::<InscriptionFilterKind>().unwrap().to_string(),
+        filter,
+      );
+    }
+  }
+
+  #[test]
+  fn invalid_filters_are_rejected() {
+    for (filter, err) in [
+      (
+        "content-type",
+        "invalid inscription filter `content-type`, expected `<KIND>=<VALUE>`",
+      ),
+      (
+        "content-type= , ",
+        "inscription filter `content-type= , ` has no values",
+      ),
+      (
+        "delegate=foo",
+        "unknown inscription filter kind `delegate`, expected one of `content-type`, `metadata-key`, `metaprotocol` or `parent`",
+      ),
+    ] {
+      assert_eq!(
+        filter
+          .parse::<InscriptionFilterKind>()
+          .unwrap_err()
+          .to_string(),
+        err,
+      );
+    }
+
+    assert!("parent=foo".parse::<InscriptionFilterKind>().is_err());
+  }
+
+  #[test]
+  fn content_type_filters_ignore_parameters_and_expand_wildcards() {
+    let inscription = |content_type: &str| Inscription {
+      content_type: Some(content_type.into()),
+      ..default()
+    };
+
+    assert!(matches("content-type=image/png", inscription("image/png")));
+    assert!(matches(
+      "content-type=text/html",
+      inscription("text/html;charset=utf-8")
+    ));
+    assert!(matches("content-type=image/*", inscription("image/webp")));
+    assert!(!matches(
+      "content-type=image/*",
+      inscription("imagery/webp")
+    ));
+    assert!(!matches(
+      "content-type=image/png",
+      inscription("image/pngx")
+    ));
+    assert!(!matches("content-type=image/png", Inscription::default()));
+  }
+
+  #[test]
+  fn metadata_key_filters_match_keys_of_metadata_maps() {
+    let inscription = |metadata: Value| {
+      let mut cbor = Vec::new();
+      ciborium::into_writer(&metadata, &mut cbor).unwrap();
+
+      Inscription {
+        metadata: Some(cbor),
+        ..default()
+      }
+    };
+
+    assert!(matches(
+      "metadata-key=title",
+      inscription(Value::Map(vec![(
+        Value::Text("title".into()),
+        Value::Text("foo".into())
+      )]))
+    ));
+    assert!(!matches(
+      "metadata-key=title",
+      inscription(Value::Map(vec![(
+        Value::Text("name".into()),
+        Value::Text("title".into())
+      )]))
+    ));
+    assert!(!matches(
+      "metadata-key=title",
+      inscription(Value::Text("title".into()))
+    ));
+  }
+
+  #[test]
+  fn metaprotocol_filters_match_exactly() {
+    let inscription = |metaprotocol: &str| Inscription {
+      metaprotocol: Some(metaprotocol.into()),
+      ..default()
+    };
+
+    assert!(matches("metaprotocol=brc-20,sns", inscription("sns")));
+    assert!(!matches("metaprotocol=brc-20", inscription("brc-20x")));
+    assert!(!matches("metaprotocol=brc-20", Inscription::default()));
+  }
+
+  #[test]
+  fn parent_filters_match_children() {
+    let child = Inscription {
+      parents: vec![parent().value()],
+      ..default()
+    };
+
+    assert!(matches(&format!("parent={PARENT}"), child.clone()));
+    assert!(!matches(
+      "parent=2222222222222222222222222222222222222222222222222222222222222222i0",
+      child
+    ));
+  }
+}
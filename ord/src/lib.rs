@@ -44,12 +44,12 @@ use {
     Sat, SatPoint, SpacedRune,
   },
   regex::Regex,
-  serde::{Deserialize, Deserializer, Serialize},
+  serde::{Deserialize, Serialize},
   serde_with::{DeserializeFromStr, SerializeDisplay},
   snafu::{Backtrace, ErrorCompat, Snafu},
   std::{
     backtrace::BacktraceStatus,
-    collections::{BTreeMap, HashSet},
+    collections::{BTreeMap, BTreeSet, HashSet},
     env,
     ffi::OsString,
     fmt::{self, Display, Formatter},
@@ -73,6 +73,7 @@ pub use self::{
   chain::Chain,
   fee_rate::FeeRate,
   index::Index,
+  inscription_filter::{InscriptionFilter, InscriptionFilterKind},
   inscriptions::{Envelope, Inscription, InscriptionId, ParsedEnvelope, RawEnvelope},
   object::Object,
   options::Options,
@@ -81,10 +82,10 @@ pub use self::{
 pub mod arguments;
 pub mod chain;
 pub mod decimal;
-mod deserialize_from_str;
 mod error;
 mod fee_rate;
 pub mod index;
+pub mod inscription_filter;
 mod inscriptions;
 mod object;
 pub mod options;
@@ -179,7 +180,9 @@ pub fn main() {
       process::exit(1);
     }
 
-    eprintln!("Shutting down gracefully. Press <CTRL-C> again to shutdown immediately.");
+    eprintln!(
+      "Shutting down gracefully. Press <CTRL-C> again to shutdown immediately, uncommitted blocks will be rolled back when the index is next opened."
+    );
 
     LISTENERS
       .lock()
@@ -199,6 +202,13 @@ pub fn main() {
     Err(err) => {
       eprintln!("error: {err}");
 
+      let code = match &err {
+        SnafuError::Anyhow { err } if err.is::<index::UncommittedBlocks>() => {
+          index::UncommittedBlocks::EXIT_CODE
+        }
+        _ => 1,
+      };
+
       if let SnafuError::Anyhow { err } = err {
         for (i, err) in err.chain().skip(1).enumerate() {
           if i == 0 {
@@ -235,7 +245,7 @@ pub fn main() {
 
       gracefully_shut_down_indexer();
 
-      process::exit(1);
+      process::exit(code);
     }
     Ok(output) => {
       if let Some(output) = output {
@@ -1,4 +1,4 @@
-use super::*;
+use {super::*, std::net::IpAddr};
 
 #[derive(Clone, Default, Debug, Parser)]
 #[command(group(
@@ -21,6 +21,11 @@ pub struct Options {
     help = "Authenticate to Bitcoin Core RPC as <BITCOIN_RPC_USERNAME>."
   )]
   pub(crate) bitcoin_rpc_username: Option<String>,
+  #[arg(
+    long,
+    help = "Index new blocks as soon as Bitcoin Core announces them on its `-zmqpubhashblock` endpoint <BITCOIN_ZMQ_HASHBLOCK>, e.g. `tcp://127.0.0.1:28332`. Polling every 5 seconds continues as a fallback."
+  )]
+  pub(crate) bitcoin_zmq_hashblock: Option<String>,
   #[arg(long, help = "Max <N> requests in flight. [default: 12]")]
   pub(crate) bitcoin_rpc_limit: Option<u32>,
   #[arg(long = "chain", value_enum, help = "Use <CHAIN>. [default: mainnet]")]
@@ -37,6 +42,11 @@ pub struct Options {
   pub(crate) savepoint_interval: Option<usize>,
   #[arg(long, help = "Store maximum <MAX_SAVEPOINTS> blocks. [default: 2]")]
   pub(crate) max_savepoints: Option<usize>,
+  #[arg(
+    long,
+    help = "Keep undo logs for the last <UNDO_LOG_DEPTH> blocks and roll back reorgs with them instead of savepoints."
+  )]
+  pub(crate) undo_log_depth: Option<usize>,
   #[arg(long, help = "Load configuration from <CONFIG>.")]
   pub(crate) config: Option<PathBuf>,
   #[arg(long, help = "Load configuration from <CONFIG_DIR>.")]
@@ -45,25 +55,57 @@ pub struct Options {
   pub(crate) cookie_file: Option<PathBuf>,
   #[arg(long, alias = "datadir", help = "Store index in <DATA_DIR>.")]
   pub(crate) data_dir: Option<PathBuf>,
+  #[arg(
+    long = "decoded-content-protocol",
+    value_name = "PROTOCOL",
+    help = "Also recognize JSON inscriptions of protocol <PROTOCOL> in brotli or gzip content-encoded bodies. May be repeated."
+  )]
+  pub(crate) decoded_content_protocols: Vec<String>,
   #[arg(long, help = "Limit index to <HEIGHT_LIMIT> blocks.")]
   pub(crate) height_limit: Option<u32>,
   #[arg(long, help = "Use index at <INDEX>.")]
   pub(crate) index: Option<PathBuf>,
-  #[arg(long, help = "Track unspent output addresses.")]
+  #[arg(long, help = "Track inscriptions by the script pubkey holding them. Only applies to a new index.")]
   pub(crate) index_addresses: bool,
   #[arg(
     long,
     help = "Set index cache size to <INDEX_CACHE_SIZE> bytes. [default: 1/4 available RAM]"
   )]
   pub(crate) index_cache_size: Option<usize>,
+  #[arg(
+    long,
+    help = "Read blocks and spent outputs from Bitcoin Core's blk*.dat and rev*.dat files instead of RPC while far behind the chain tip."
+  )]
+  pub(crate) index_from_blk_files: bool,
+  #[arg(
+    long,
+    help = "Index every output from genesis rather than from the first inscription height, so inputs can be resolved without bitcoind's -txindex. This keeps the full UTXO set in outpoint_to_utxo_entry, which takes several GB more disk and a full sync from genesis. Only applies to a new index."
+  )]
+  pub(crate) index_prevouts: bool,
   #[arg(long, help = "Track location of runes.")]
   pub(crate) index_runes: bool,
   #[arg(long, help = "Track location of all satoshis.")]
   pub(crate) index_sats: bool,
   #[arg(long, help = "Store transactions in index.")]
   pub(crate) index_transactions: bool,
+  #[arg(
+    long = "inscription-filter",
+    value_name = "FILTER",
+    help = "Also track inscriptions matching <FILTER>, one of `content-type=<TYPE>,…`, `metaprotocol=<NAME>,…`, `parent=<INSCRIPTION_ID>,…` or `metadata-key=<KEY>,…`. May be repeated."
+  )]
+  pub(crate) inscription_filters: Vec<InscriptionFilterKind>,
   #[arg(long, help = "Run in integration test mode.")]
   pub(crate) integration_test: bool,
+  #[arg(
+    long,
+    help = "Bind the metrics server to <METRICS_ADDRESS>. [default: 127.0.0.1]"
+  )]
+  pub(crate) metrics_address: Option<IpAddr>,
+  #[arg(
+    long,
+    help = "Serve Prometheus metrics of the indexer at http://<METRICS_ADDRESS>:<METRICS_PORT>/metrics."
+  )]
+  pub(crate) metrics_port: Option<u16>,
   #[clap(long, short, long, help = "Specify output format. [default: json]")]
   pub(crate) format: Option<OutputFormat>,
   #[arg(
@@ -73,6 +115,13 @@ pub struct Options {
     help = "Do not index inscriptions."
   )]
   pub(crate) no_index_inscriptions: bool,
+  #[arg(
+    long = "tx-limit",
+    value_name = "KEY=LIMIT",
+    value_parser = Settings::parse_tx_limit,
+    help = "Track the first <LIMIT> transfers of JSON inscriptions matching <KEY>, which is `default`, a protocol `p` or a protocol and operation `p:op`. May be repeated. [default: default=2]"
+  )]
+  pub(crate) tx_limits: Vec<(String, i16)>,
   #[arg(
     long,
     help = "Require basic HTTP authentication with <SERVER_PASSWORD>. Credentials are sent in cleartext. Consider using authentication in conjunction with HTTPS."
@@ -91,4 +140,9 @@ pub struct Options {
   pub(crate) testnet: bool,
   #[arg(long, help = "Use testnet4. Equivalent to `--chain testnet4`.")]
   pub(crate) testnet4: bool,
+  #[arg(
+    long,
+    help = "Simulate transfers of tracked inscriptions by transactions in bitcoind's mempool while at the chain tip, and serve them with db_reader's getPendingTransfers."
+  )]
+  pub(crate) watch_mempool: bool,
 }
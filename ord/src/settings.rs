@@ -1,4 +1,9 @@
-use {super::*, bitcoincore_rpc::Auth};
+use {
+  super::*,
+  bitcoincore_rpc::Auth,
+  db_reader::RocksDbSettings,
+  std::net::{IpAddr, Ipv4Addr},
+};
 
 #[derive(Default, Debug, Clone, Serialize, Deserialize, PartialEq)]
 #[serde(default, deny_unknown_fields)]
@@ -8,19 +13,31 @@ pub struct Settings {
   bitcoin_rpc_password: Option<String>,
   bitcoin_rpc_url: Option<String>,
   bitcoin_rpc_username: Option<String>,
+  bitcoin_zmq_hashblock: Option<String>,
   chain: Option<Chain>,
   commit_interval: Option<usize>,
   savepoint_interval: Option<usize>,
   max_savepoints: Option<usize>,
+  undo_log_depth: Option<usize>,
   config: Option<PathBuf>,
   config_dir: Option<PathBuf>,
   cookie_file: Option<PathBuf>,
   data_dir: Option<PathBuf>,
+  decoded_content_protocols: Option<BTreeSet<String>>,
   height_limit: Option<u32>,
   index: Option<PathBuf>,
+  index_addresses: bool,
   index_cache_size: Option<usize>,
+  index_from_blk_files: bool,
+  index_prevouts: bool,
+  inscription_filters: Option<Vec<InscriptionFilterKind>>,
   integration_test: bool,
+  metrics_address: Option<IpAddr>,
+  metrics_port: Option<u16>,
   no_index_inscriptions: bool,
+  rocksdb: RocksDbSettings,
+  tx_limits: Option<BTreeMap<String, i16>>,
+  watch_mempool: bool,
 }
 
 impl Settings {
@@ -100,19 +117,33 @@ impl Settings {
       bitcoin_rpc_password: self.bitcoin_rpc_password.or(source.bitcoin_rpc_password),
       bitcoin_rpc_url: self.bitcoin_rpc_url.or(source.bitcoin_rpc_url),
       bitcoin_rpc_username: self.bitcoin_rpc_username.or(source.bitcoin_rpc_username),
+      bitcoin_zmq_hashblock: self.bitcoin_zmq_hashblock.or(source.bitcoin_zmq_hashblock),
       chain: self.chain.or(source.chain),
       commit_interval: self.commit_interval.or(source.commit_interval),
       savepoint_interval: self.savepoint_interval.or(source.savepoint_interval),
       max_savepoints: self.max_savepoints.or(source.max_savepoints),
+      undo_log_depth: self.undo_log_depth.or(source.undo_log_depth),
       config: self.config.or(source.config),
       config_dir: self.config_dir.or(source.config_dir),
       cookie_file: self.cookie_file.or(source.cookie_file),
       data_dir: self.data_dir.or(source.data_dir),
+      decoded_content_protocols: self
+        .decoded_content_protocols
+        .or(source.decoded_content_protocols),
       height_limit: self.height_limit.or(source.height_limit),
       index: self.index.or(source.index),
+      index_addresses: self.index_addresses || source.index_addresses,
       index_cache_size: self.index_cache_size.or(source.index_cache_size),
+      index_from_blk_files: self.index_from_blk_files || source.index_from_blk_files,
+      index_prevouts: self.index_prevouts || source.index_prevouts,
+      inscription_filters: self.inscription_filters.or(source.inscription_filters),
       integration_test: self.integration_test || source.integration_test,
+      metrics_address: self.metrics_address.or(source.metrics_address),
+      metrics_port: self.metrics_port.or(source.metrics_port),
       no_index_inscriptions: self.no_index_inscriptions || source.no_index_inscriptions,
+      rocksdb: self.rocksdb.or(source.rocksdb),
+      tx_limits: self.tx_limits.or(source.tx_limits),
+      watch_mempool: self.watch_mempool || source.watch_mempool,
     }
   }
 
@@ -123,6 +154,7 @@ impl Settings {
       bitcoin_rpc_password: options.bitcoin_rpc_password,
       bitcoin_rpc_url: options.bitcoin_rpc_url,
       bitcoin_rpc_username: options.bitcoin_rpc_username,
+      bitcoin_zmq_hashblock: options.bitcoin_zmq_hashblock,
       chain: options
         .signet
         .then_some(Chain::Signet)
@@ -133,15 +165,27 @@ impl Settings {
       commit_interval: options.commit_interval,
       savepoint_interval: options.savepoint_interval,
       max_savepoints: options.max_savepoints,
+      undo_log_depth: options.undo_log_depth,
       config: options.config,
       config_dir: options.config_dir,
       cookie_file: options.cookie_file,
       data_dir: options.data_dir,
+      decoded_content_protocols: (!options.decoded_content_protocols.is_empty())
+        .then(|| options.decoded_content_protocols.into_iter().collect()),
       height_limit: options.height_limit,
       index: options.index,
+      index_addresses: options.index_addresses,
       index_cache_size: options.index_cache_size,
+      index_from_blk_files: options.index_from_blk_files,
+      index_prevouts: options.index_prevouts,
+      inscription_filters: (!options.inscription_filters.is_empty()).then_some(options.inscription_filters),
       integration_test: options.integration_test,
+      metrics_address: options.metrics_address,
+      metrics_port: options.metrics_port,
       no_index_inscriptions: options.no_index_inscriptions,
+      rocksdb: RocksDbSettings::default(),
+      tx_limits: (!options.tx_limits.is_empty()).then(|| options.tx_limits.into_iter().collect()),
+      watch_mempool: options.watch_mempool,
     }
   }
 
@@ -173,6 +217,22 @@ impl Settings {
         .with_context(|| format!("failed to parse environment variable ORD_{key} as u32"))
     };
 
+    let get_u16 = |key| {
+      env
+        .get(key)
+        .map(|int| int.parse::<u16>())
+        .transpose()
+        .with_context(|| format!("failed to parse environment variable ORD_{key} as u16"))
+    };
+
+    let get_ip_addr = |key| {
+      env
+        .get(key)
+        .map(|address| address.parse::<IpAddr>())
+        .transpose()
+        .with_context(|| format!("failed to parse environment variable ORD_{key} as IP address"))
+    };
+
     let get_usize = |key| {
       env
         .get(key)
@@ -181,25 +241,80 @@ impl Settings {
         .with_context(|| format!("failed to parse environment variable ORD_{key} as usize"))
     };
 
+    let get_inscription_filters = |key| {
+      env
+        .get(key)
+        .map(|filters| {
+          filters
+            .split(';')
+            .map(str::parse)
+            .collect::<Result<Vec<InscriptionFilterKind>>>()
+        })
+        .transpose()
+        .with_context(|| format!("failed to parse environment variable ORD_{key} as inscription filters"))
+    };
+
+    let get_protocols = |key| {
+      env.get(key).map(|protocols| {
+        protocols
+          .split(',')
+          .map(|protocol| protocol.trim().to_string())
+          .collect::<BTreeSet<String>>()
+      })
+    };
+
+    let get_rocksdb = |key| {
+      env
+        .get(key)
+        .map(|rocksdb| serde_yaml::from_str::<RocksDbSettings>(rocksdb))
+        .transpose()
+        .with_context(|| format!("failed to parse environment variable ORD_{key} as RocksDB settings"))
+    };
+
+    let get_tx_limits = |key| {
+      env
+        .get(key)
+        .map(|tx_limits| {
+          tx_limits
+            .split(',')
+            .map(Self::parse_tx_limit)
+            .collect::<Result<BTreeMap<String, i16>>>()
+        })
+        .transpose()
+        .with_context(|| format!("failed to parse environment variable ORD_{key} as transfer limits"))
+    };
+
     Ok(Self {
       bitcoin_data_dir: get_path("BITCOIN_DATA_DIR"),
       bitcoin_rpc_limit: get_u32("BITCOIN_RPC_LIMIT")?,
       bitcoin_rpc_password: get_string("BITCOIN_RPC_PASSWORD"),
       bitcoin_rpc_url: get_string("BITCOIN_RPC_URL"),
       bitcoin_rpc_username: get_string("BITCOIN_RPC_USERNAME"),
+      bitcoin_zmq_hashblock: get_string("BITCOIN_ZMQ_HASHBLOCK"),
       chain: get_chain("CHAIN")?,
       commit_interval: get_usize("COMMIT_INTERVAL")?,
       savepoint_interval: get_usize("SAVEPOINT_INTERVAL")?,
       max_savepoints: get_usize("MAX_SAVEPOINTS")?,
+      undo_log_depth: get_usize("UNDO_LOG_DEPTH")?,
       config: get_path("CONFIG"),
       config_dir: get_path("CONFIG_DIR"),
       cookie_file: get_path("COOKIE_FILE"),
       data_dir: get_path("DATA_DIR"),
+      decoded_content_protocols: get_protocols("DECODED_CONTENT_PROTOCOLS"),
       height_limit: get_u32("HEIGHT_LIMIT")?,
       index: get_path("INDEX"),
+      index_addresses: get_bool("INDEX_ADDRESSES"),
       index_cache_size: get_usize("INDEX_CACHE_SIZE")?,
+      index_from_blk_files: get_bool("INDEX_FROM_BLK_FILES"),
+      index_prevouts: get_bool("INDEX_PREVOUTS"),
+      inscription_filters: get_inscription_filters("INSCRIPTION_FILTERS")?,
       integration_test: get_bool("INTEGRATION_TEST"),
+      metrics_address: get_ip_addr("METRICS_ADDRESS")?,
+      metrics_port: get_u16("METRICS_PORT")?,
       no_index_inscriptions: get_bool("NO_INDEX_INSCRIPTIONS"),
+      rocksdb: get_rocksdb("ROCKSDB")?.unwrap_or_default(),
+      tx_limits: get_tx_limits("TX_LIMITS")?,
+      watch_mempool: get_bool("WATCH_MEMPOOL"),
     })
   }
 
@@ -209,20 +324,32 @@ impl Settings {
       bitcoin_rpc_password: None,
       bitcoin_rpc_url: Some(rpc_url.into()),
       bitcoin_rpc_username: None,
+      bitcoin_zmq_hashblock: None,
       bitcoin_rpc_limit: None,
       chain: Some(Chain::Regtest),
       commit_interval: None,
       savepoint_interval: None,
       max_savepoints: None,
+      undo_log_depth: None,
       config: None,
       config_dir: None,
       cookie_file: None,
       data_dir: Some(dir.into()),
+      decoded_content_protocols: None,
       height_limit: None,
       index: None,
+      index_addresses: false,
       index_cache_size: None,
+      index_from_blk_files: false,
+      index_prevouts: false,
+      inscription_filters: None,
       integration_test: false,
+      metrics_address: None,
+      metrics_port: None,
       no_index_inscriptions: false,
+      rocksdb: RocksDbSettings::default(),
+      tx_limits: None,
+      watch_mempool: false,
     }
   }
 
@@ -259,6 +386,14 @@ impl Settings {
       None => data_dir.join("dbs"),
     };
 
+    let mut tx_limits = self.tx_limits.unwrap_or_default();
+
+    tx_limits.entry("default".into()).or_insert(2);
+
+    if let Some((key, limit)) = tx_limits.iter().find(|(_, limit)| **limit < 1) {
+      bail!("transfer limit for `{key}` must be at least 1, got {limit}");
+    }
+
     Ok(Self {
       bitcoin_data_dir: Some(bitcoin_data_dir),
       bitcoin_rpc_limit: Some(self.bitcoin_rpc_limit.unwrap_or(12)),
@@ -270,16 +405,20 @@ impl Settings {
           .unwrap_or_else(|| format!("127.0.0.1:{}", chain.default_rpc_port())),
       ),
       bitcoin_rpc_username: self.bitcoin_rpc_username,
+      bitcoin_zmq_hashblock: self.bitcoin_zmq_hashblock,
       chain: Some(chain),
       commit_interval: Some(self.commit_interval.unwrap_or(5000)),
       savepoint_interval: Some(self.savepoint_interval.unwrap_or(10)),
       max_savepoints: Some(self.max_savepoints.unwrap_or(2)),
+      undo_log_depth: self.undo_log_depth,
       config: None,
       config_dir: None,
       cookie_file: Some(cookie_file),
       data_dir: Some(data_dir),
+      decoded_content_protocols: Some(self.decoded_content_protocols.unwrap_or_default()),
       height_limit: self.height_limit,
       index: Some(index),
+      index_addresses: self.index_addresses,
       index_cache_size: Some(match self.index_cache_size {
         Some(index_cache_size) => index_cache_size,
         None => {
@@ -288,8 +427,20 @@ impl Settings {
           usize::try_from(sys.total_memory() / 4)?
         }
       }),
+      index_from_blk_files: self.index_from_blk_files,
+      index_prevouts: self.index_prevouts,
+      inscription_filters: Some(self.inscription_filters.unwrap_or_default()),
       integration_test: self.integration_test,
+      metrics_address: Some(
+        self
+          .metrics_address
+          .unwrap_or(Ipv4Addr::LOCALHOST.into()),
+      ),
+      metrics_port: self.metrics_port,
       no_index_inscriptions: self.no_index_inscriptions,
+      rocksdb: self.rocksdb,
+      tx_limits: Some(tx_limits),
+      watch_mempool: self.watch_mempool,
     })
   }
 
@@ -400,6 +551,10 @@ impl Settings {
     self.max_savepoints.unwrap()
   }
 
+  pub fn undo_log_depth(&self) -> Option<usize> {
+    self.undo_log_depth
+  }
+
   pub fn cookie_file(&self) -> Result<PathBuf> {
     if let Some(cookie_file) = &self.cookie_file {
       return Ok(cookie_file.clone());
@@ -454,14 +609,56 @@ impl Settings {
     !self.no_index_inscriptions
   }
 
+  pub fn index_addresses(&self) -> bool {
+    self.index_addresses
+  }
+
   pub fn index_cache_size(&self) -> usize {
     self.index_cache_size.unwrap()
   }
 
+  pub fn index_from_blk_files(&self) -> bool {
+    self.index_from_blk_files
+  }
+
+  pub fn index_prevouts(&self) -> bool {
+    self.index_prevouts
+  }
+
+  /// Bitcoin Core's `blocks` directory, holding `blk*.dat` and `rev*.dat`.
+  pub fn bitcoin_blocks_dir(&self) -> PathBuf {
+    self
+      .chain()
+      .join_with_data_dir(self.bitcoin_data_dir.as_ref().unwrap())
+      .join("blocks")
+  }
+
+  /// Protocols whose JSON inscriptions are also recognized in content decoded
+  /// from their content encoding.
+  pub fn decoded_content_protocols(&self) -> &BTreeSet<String> {
+    self.decoded_content_protocols.as_ref().unwrap()
+  }
+
+  pub fn inscription_filters(&self) -> &[InscriptionFilterKind] {
+    self.inscription_filters.as_ref().unwrap()
+  }
+
   pub fn integration_test(&self) -> bool {
     self.integration_test
   }
 
+  pub fn metrics_address(&self) -> IpAddr {
+    self.metrics_address.unwrap()
+  }
+
+  pub fn metrics_port(&self) -> Option<u16> {
+    self.metrics_port
+  }
+
+  pub fn rocksdb(&self) -> &RocksDbSettings {
+    &self.rocksdb
+  }
+
   pub fn bitcoin_rpc_url(&self, wallet_name: Option<String>) -> String {
     let base_url = self.bitcoin_rpc_url.as_ref().unwrap();
     match wallet_name {
@@ -473,4 +670,206 @@ impl Settings {
   pub fn bitcoin_rpc_limit(&self) -> u32 {
     self.bitcoin_rpc_limit.unwrap()
   }
+
+  pub fn bitcoin_zmq_hashblock(&self) -> Option<&str> {
+    self.bitcoin_zmq_hashblock.as_deref()
+  }
+
+  pub fn watch_mempool(&self) -> bool {
+    self.watch_mempool
+  }
+
+  pub fn tx_limits(&self) -> &BTreeMap<String, i16> {
+    self.tx_limits.as_ref().unwrap()
+  }
+
+  /// Number of transfers tracked for an inscription of protocol `p` with
+  /// operation `op`. The most specific of `p:op`, `p` and `default` applies.
+  pub fn tx_limit(&self, protocol: Option<&str>, op: Option<&str>) -> i16 {
+    let tx_limits = self.tx_limits();
+
+    protocol
+      .and_then(|protocol| {
+        op.and_then(|op| tx_limits.get(&format!("{protocol}:{op}")))
+          .or_else(|| tx_limits.get(protocol))
+      })
+      .or_else(|| tx_limits.get("default"))
+      .copied()
+      .unwrap()
+  }
+
+  /// Parses a transfer limit in `KEY=LIMIT` form.
+  pub(crate) fn parse_tx_limit(s: &str) -> Result<(String, i16)> {
+    let (key, limit) = s
+      .split_once('=')
+      .ok_or_else(|| anyhow!("invalid transfer limit `{s}`, expected `KEY=LIMIT`"))?;
+
+    Ok((
+      key.trim().into(),
+      limit
+        .trim()
+        .parse()
+        .with_context(|| format!("invalid transfer limit `{s}`"))?,
+    ))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use {super::*, tempfile::TempDir};
+
+  fn settings(tx_limits: &[(&str, i16)], env: &[(&str, &str)]) -> Result<Settings> {
+    let tempdir = TempDir::new().unwrap();
+
+    Settings::merge(
+      Options {
+        regtest: true,
+        data_dir: Some(tempdir.path().into()),
+        tx_limits: tx_limits
+          .iter()
+          .map(|(key, limit)| (key.to_string(), *limit))
+          .collect(),
+        ..default()
+      },
+      env
+        .iter()
+        .map(|(key, value)| (key.to_string(), value.to_string()))
+        .collect(),
+    )
+  }
+
+  #[test]
+  fn tx_limits_are_parsed() {
+    assert_eq!(
+      Settings::parse_tx_limit("brc-20=3").unwrap(),
+      ("brc-20".into(), 3),
+    );
+
+    assert_eq!(
+      Settings::parse_tx_limit(" brc-20:transfer = 1 ").unwrap(),
+      ("brc-20:transfer".into(), 1),
+    );
+  }
+
+  #[test]
+  fn invalid_tx_limits_are_rejected() {
+    assert_eq!(
+      Settings::parse_tx_limit("brc-20").unwrap_err().to_string(),
+      "invalid transfer limit `brc-20`, expected `KEY=LIMIT`",
+    );
+
+    for s in ["brc-20=x", "brc-20=", "brc-20=40000"] {
+      assert_eq!(
+        Settings::parse_tx_limit(s).unwrap_err().to_string(),
+        format!("invalid transfer limit `{s}`"),
+      );
+    }
+  }
+
+  #[test]
+  fn most_specific_tx_limit_applies() {
+    let settings = settings(&[("brc-20", 3), ("brc-20:mint", 1)], &[]).unwrap();
+
+    assert_eq!(settings.tx_limit(Some("brc-20"), Some("mint")), 1);
+    assert_eq!(settings.tx_limit(Some("brc-20"), Some("transfer")), 3);
+    assert_eq!(settings.tx_limit(Some("brc-20"), None), 3);
+    assert_eq!(settings.tx_limit(Some("sns"), Some("reg")), 2);
+    assert_eq!(settings.tx_limit(None, Some("mint")), 2);
+  }
+
+  #[test]
+  fn tx_limits_are_read_from_the_environment() {
+    let from_env = settings(&[], &[("TX_LIMITS", "brc-20=3,default=5")]).unwrap();
+
+    assert_eq!(from_env.tx_limit(Some("brc-20"), None), 3);
+    assert_eq!(from_env.tx_limit(Some("sns"), None), 5);
+
+    assert_eq!(
+      settings(&[], &[("TX_LIMITS", "brc-20")])
+        .unwrap_err()
+        .to_string(),
+      "failed to parse environment variable ORD_TX_LIMITS as transfer limits",
+    );
+  }
+
+  #[test]
+  fn inscription_filters_are_read_from_the_environment() {
+    assert_eq!(
+      settings(
+        &[],
+        &[(
+          "INSCRIPTION_FILTERS",
+          "content-type=image/png;metaprotocol=sns"
+        )]
+      )
+      .unwrap()
+      .inscription_filters(),
+      [
+        InscriptionFilterKind::ContentType(vec!["image/png".into()]),
+        InscriptionFilterKind::Metaprotocol(vec!["sns".into()]),
+      ],
+    );
+
+    assert_eq!(
+      settings(&[], &[("INSCRIPTION_FILTERS", "content-type")])
+        .unwrap_err()
+        .to_string(),
+      "failed to parse environment variable ORD_INSCRIPTION_FILTERS as inscription filters",
+    );
+  }
+
+  #[test]
+  fn metrics_address_defaults_to_localhost() {
+    assert_eq!(
+      settings(&[], &[]).unwrap().metrics_address(),
+      IpAddr::from(Ipv4Addr::LOCALHOST),
+    );
+
+    assert_eq!(
+      settings(&[], &[("METRICS_ADDRESS", "0.0.0.0")])
+        .unwrap()
+        .metrics_address(),
+      IpAddr::from(Ipv4Addr::UNSPECIFIED),
+    );
+
+    assert_eq!(
+      settings(&[], &[("METRICS_ADDRESS", "localhost")])
+        .unwrap_err()
+        .to_string(),
+      "failed to parse environment variable ORD_METRICS_ADDRESS as IP address",
+    );
+  }
+
+  #[test]
+  fn tx_limits_below_one_are_rejected() {
+    assert_eq!(
+      settings(&[("brc-20", 0)], &[]).unwrap_err().to_string(),
+      "transfer limit for `brc-20` must be at least 1, got 0",
+    );
+  }
+
+  #[test]
+  fn rocksdb_settings_for_unknown_column_families_are_rejected() {
+    let err = settings(
+      &[],
+      &[(
+        "ROCKSDB",
+        "column_families:\n  ord_transfer:\n    compression: zstd",
+      )],
+    )
+    .unwrap_err();
+
+    assert_eq!(
+      err.to_string(),
+      "failed to parse environment variable ORD_ROCKSDB as RocksDB settings",
+    );
+
+    assert!(
+      err
+        .root_cause()
+        .to_string()
+        .starts_with("unknown column family `ord_transfer`, expected one of "),
+      "{err:#}",
+    );
+  }
 }
@@ -2,8 +2,6 @@ use super::*;
 
 pub mod index;
 
-use crate::index::get_tx_limits;
-
 #[derive(Debug, Parser)]
 pub(crate) enum Subcommand {
   #[command(subcommand, about = "Index commands")]
@@ -12,9 +10,8 @@ pub(crate) enum Subcommand {
   MaxTransferCounts,
 }
 
-fn max_transfer_counts() -> SubcommandResult {
-  // create a dictionary. set 'default' to 2 and 'brc20-approve-conditional' to 5
-  let max_transfer_counts = get_tx_limits();
+fn max_transfer_counts(settings: &Settings) -> SubcommandResult {
+  let max_transfer_counts = settings.tx_limits().clone();
   Ok(Some(Box::new(max_transfer_counts)))
 }
 
@@ -22,7 +19,7 @@ impl Subcommand {
   pub(crate) fn run(self, settings: Settings) -> SubcommandResult {
     match self {
       Self::Index(index) => index.run(settings),
-      Self::MaxTransferCounts => max_transfer_counts(),
+      Self::MaxTransferCounts => max_transfer_counts(&settings),
     }
   }
 }
@@ -1,17 +1,44 @@
-use super::*;
+use {
+  super::*,
+  crate::index::{block_source::Recording, migration, snapshot},
+};
 
+mod export_snapshot;
+mod import_snapshot;
+mod migrate;
+mod record;
+mod replay;
 mod update;
+mod verify;
 
 #[derive(Debug, Parser)]
 pub(crate) enum IndexSubcommand {
+  #[command(about = "Write a checksummed snapshot of the index")]
+  ExportSnapshot(export_snapshot::ExportSnapshot),
+  #[command(about = "Create the index from a snapshot")]
+  ImportSnapshot(import_snapshot::ImportSnapshot),
+  #[command(about = "Migrate the index to the current schema version")]
+  Migrate(migrate::Migrate),
+  #[command(about = "Record blocks and the outputs they spend for replay")]
+  Record(record::Record),
+  #[command(about = "Update the index from recorded blocks, without Bitcoin Core")]
+  Replay(replay::Replay),
   #[command(about = "Update the index", alias = "run")]
   Update,
+  #[command(about = "Check that the index's column families agree with each other")]
+  Verify,
 }
 
 impl IndexSubcommand {
   pub(crate) fn run(self, settings: Settings) -> SubcommandResult {
     match self {
+      Self::ExportSnapshot(export_snapshot) => export_snapshot.run(settings),
+      Self::ImportSnapshot(import_snapshot) => import_snapshot.run(settings),
+      Self::Migrate(migrate) => migrate.run(settings),
+      Self::Record(record) => record.run(settings),
+      Self::Replay(replay) => replay.run(settings),
       Self::Update => update::run(settings),
+      Self::Verify => verify::run(settings),
     }
   }
 }
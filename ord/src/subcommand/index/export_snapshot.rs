@@ -0,0 +1,19 @@
+use super::*;
+
+#[derive(Debug, Parser)]
+pub(crate) struct ExportSnapshot {
+  #[arg(long, help = "Export the index committed at block <HEIGHT>.")]
+  height: u32,
+  #[arg(help = "Write snapshot to <PATH>.")]
+  path: PathBuf,
+}
+
+impl ExportSnapshot {
+  pub(crate) fn run(self, settings: Settings) -> SubcommandResult {
+    Ok(Some(Box::new(snapshot::export(
+      &settings,
+      self.height,
+      &self.path,
+    )?)))
+  }
+}
@@ -0,0 +1,13 @@
+use super::*;
+
+#[derive(Debug, Parser)]
+pub(crate) struct ImportSnapshot {
+  #[arg(help = "Create the index from the snapshot at <PATH>.")]
+  path: PathBuf,
+}
+
+impl ImportSnapshot {
+  pub(crate) fn run(self, settings: Settings) -> SubcommandResult {
+    Ok(Some(Box::new(snapshot::import(&settings, &self.path)?)))
+  }
+}
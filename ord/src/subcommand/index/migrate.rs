@@ -0,0 +1,16 @@
+use super::*;
+
+#[derive(Debug, Parser)]
+pub(crate) struct Migrate {
+  #[arg(
+    long,
+    help = "Report the migrations that would run without writing them. Write counts are per-step estimates against the unmigrated index."
+  )]
+  dry_run: bool,
+}
+
+impl Migrate {
+  pub(crate) fn run(self, settings: Settings) -> SubcommandResult {
+    Ok(Some(Box::new(migration::run(&settings, self.dry_run)?)))
+  }
+}
@@ -0,0 +1,19 @@
+use super::*;
+
+#[derive(Debug, Parser)]
+pub(crate) struct Record {
+  #[arg(long, help = "Record blocks starting at height <FROM>.")]
+  from: u32,
+  #[arg(long, help = "Record blocks up to and including height <TO>.")]
+  to: u32,
+  #[arg(help = "Write the recording to <PATH>.")]
+  path: PathBuf,
+}
+
+impl Record {
+  pub(crate) fn run(self, settings: Settings) -> SubcommandResult {
+    Ok(Some(Box::new(Recording::record(
+      &settings, self.from, self.to, &self.path,
+    )?)))
+  }
+}
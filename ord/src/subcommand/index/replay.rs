@@ -0,0 +1,33 @@
+use super::*;
+
+#[derive(Debug, Parser)]
+pub(crate) struct Replay {
+  #[arg(help = "Index the blocks recorded at <PATH>.")]
+  path: PathBuf,
+}
+
+#[derive(Serialize, Debug)]
+pub struct Output {
+  pub block_hash: Option<BlockHash>,
+  pub height: Option<u32>,
+}
+
+impl Replay {
+  pub(crate) fn run(self, settings: Settings) -> SubcommandResult {
+    let recording = Recording::open(&settings, &self.path)?;
+
+    let mut index = Index::open_with_block_source(&settings, None, Box::new(recording))?;
+
+    let updated = index.update();
+
+    let height = index.block_count()?.checked_sub(1);
+    let block_hash = index.block_hash(height)?;
+
+    let shut_down = index.shut_down();
+
+    updated?;
+    shut_down?;
+
+    Ok(Some(Box::new(Output { block_hash, height })))
+  }
+}
@@ -1,31 +1,13 @@
 use super::*;
-use crate::index::reorg::Reorg;
-use crate::index::reorg::Error as ReorgError;
 
 pub(crate) fn run(settings: Settings) -> SubcommandResult {
-  loop {
-    let index = Index::open(&settings)?;
+  let mut index = Index::open(&settings)?;
 
-    let res = index.update();
+  let updated = index.update();
+  let shut_down = index.shut_down();
 
-    match res {
-      Err(err) => {
-        match err.downcast_ref() {
-          Some(&ReorgError::Recoverable { height, depth }) => {
-            let index_path = index.path.clone();
-            drop(index); // Ensure the index and dbs are closed before handling the reorg
-            Reorg::handle_reorg(&index_path, height, depth)?;
-          }
-          _ => {
-            break;
-          }
-        }
-      }
-      _ => {
-        break;
-      }
-    }
-  }
+  updated?;
+  shut_down?;
 
   Ok(None)
 }
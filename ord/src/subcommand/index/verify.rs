@@ -0,0 +1,5 @@
+use super::*;
+
+pub(crate) fn run(settings: Settings) -> SubcommandResult {
+  Ok(Some(Box::new(crate::index::verify::run(&settings)?)))
+}
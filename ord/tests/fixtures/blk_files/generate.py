@@ -0,0 +1,118 @@
+#!/usr/bin/env python3
+
+# Writes a regtest `blocks` directory with two blocks, the undo record of the
+# second, and an `xor.dat` key, encoded as Bitcoin Core does. Block 1 spends
+# every output of block 0's coinbase, which covers each script compression.
+
+import hashlib
+import os
+import struct
+
+MAGIC = bytes.fromhex('fabfb5da')
+XOR_KEY = bytes.fromhex('3a91c4e7052bd86f')
+COIN = 100_000_000
+
+G_X = bytes.fromhex('79be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798')
+G_Y = bytes.fromhex('483ada7726a3c4655da4fbfc0e1108a8fd17b448a68554199c47d08ffb10d4b8')
+
+
+def sha256d(data):
+  return hashlib.sha256(hashlib.sha256(data).digest()).digest()
+
+
+def compact_size(n):
+  if n < 0xfd:
+    return bytes([n])
+  if n <= 0xffff:
+    return b'\xfd' + struct.pack('<H', n)
+  if n <= 0xffffffff:
+    return b'\xfe' + struct.pack('<I', n)
+  return b'\xff' + struct.pack('<Q', n)
+
+
+def varint(n):
+  digits = []
+  while True:
+    digits.append((n & 0x7f) | (0x80 if digits else 0))
+    if n <= 0x7f:
+      break
+    n = (n >> 7) - 1
+  return bytes(reversed(digits))
+
+
+def compress_amount(n):
+  if n == 0:
+    return 0
+  e = 0
+  while n % 10 == 0 and e < 9:
+    n //= 10
+    e += 1
+  if e < 9:
+    d = n % 10
+    n //= 10
+    return 1 + (n * 9 + d - 1) * 10 + e
+  return 1 + (n - 1) * 10 + 9
+
+
+def tx(inputs, outputs):
+  data = struct.pack('<i', 2) + compact_size(len(inputs))
+  for (txid, vout), script_sig in inputs:
+    data += txid + struct.pack('<I', vout) + compact_size(len(script_sig)) + script_sig
+    data += struct.pack('<I', 0xffffffff)
+  data += compact_size(len(outputs))
+  for value, script in outputs:
+    data += struct.pack('<Q', value) + compact_size(len(script)) + script
+  return data + struct.pack('<I', 0)
+
+
+def block(prev, txs, time):
+  txids = [sha256d(t) for t in txs]
+  while len(txids) > 1:
+    if len(txids) % 2:
+      txids.append(txids[-1])
+    txids = [sha256d(txids[i] + txids[i + 1]) for i in range(0, len(txids), 2)]
+  header = struct.pack('<i', 2) + prev + txids[0] + struct.pack('<III', time, 0x207fffff, 0)
+  return header, header + compact_size(len(txs)) + b''.join(txs)
+
+
+def obfuscate(data):
+  return bytes(byte ^ XOR_KEY[i % 8] for i, byte in enumerate(data))
+
+
+# (value, script_pubkey, compressed script)
+outputs = [
+  (50 * COIN, b'\x76\xa9\x14' + b'\x11' * 20 + b'\x88\xac', varint(0) + b'\x11' * 20),
+  (1, b'\xa9\x14' + b'\x22' * 20 + b'\x87', varint(1) + b'\x22' * 20),
+  (COIN // 100, b'\x21\x02' + G_X + b'\xac', varint(2) + G_X),
+  (12_345_678, b'\x41\x04' + G_X + G_Y + b'\xac', varint(4 | (G_Y[-1] & 1)) + G_X),
+  (0, b'\x51', varint(6 + 1) + b'\x51'),
+]
+
+coinbase = tx([((b'\x00' * 32, 0xffffffff), b'\x01\x00')], [(v, s) for v, s, _ in outputs])
+header0, block0 = block(b'\x00' * 32, [coinbase], 1)
+hash0 = sha256d(header0)
+
+coinbase1 = tx([((b'\x00' * 32, 0xffffffff), b'\x01\x01')], [(50 * COIN, b'\x51')])
+spend = tx([((sha256d(coinbase), vout), b'') for vout in range(len(outputs))], [(COIN, b'\x51')])
+_, block1 = block(hash0, [coinbase1, spend], 2)
+
+# Outputs of block 0's coinbase have code `height * 2 + coinbase` of 1, so no
+# version follows
+undo = compact_size(1) + compact_size(len(outputs))
+for value, _, compressed in outputs:
+  undo += varint(1) + varint(compress_amount(value)) + compressed
+
+blk = b''.join(MAGIC + struct.pack('<I', len(b)) + b for b in [block0, block1])
+rev = MAGIC + struct.pack('<I', len(undo)) + undo + sha256d(hash0 + undo)
+
+# Bitcoin Core preallocates files, which leaves zeros after the last record
+# that aren't obfuscated
+directory = os.path.join(os.path.dirname(__file__), 'regtest', 'blocks')
+os.makedirs(directory, exist_ok=True)
+
+for name, data in [('blk00000.dat', blk), ('rev00000.dat', rev)]:
+  with open(os.path.join(directory, name), 'wb') as f:
+    f.write(obfuscate(data) + b'\x00' * 64)
+
+with open(os.path.join(directory, 'xor.dat'), 'wb') as f:
+  f.write(XOR_KEY)